@@ -0,0 +1,180 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Compares rendering from a Dom with rendering straight from the templates.
+//!
+//! Run with `cargo bench`, optionally followed by a filter like `cargo bench -- slots`. There's
+//! no benchmarking crate, so this does the bare minimum of what criterion would: warm up, take a
+//! bunch of samples and report how spread out they are.
+
+use std::{
+    collections::HashMap,
+    env,
+    hint::black_box,
+    time::{Duration, Instant},
+};
+use vel::VelInstance;
+
+const WARM_UP: Duration = Duration::from_millis(300);
+const SAMPLES: usize = 50;
+const SAMPLE_TIME: Duration = Duration::from_millis(20);
+
+fn format_time(nanos: f64) -> String {
+    match nanos {
+        nanos if nanos < 1e3 => format!("{nanos:.2} ns"),
+        nanos if nanos < 1e6 => format!("{:.2} µs", nanos / 1e3),
+        nanos if nanos < 1e9 => format!("{:.2} ms", nanos / 1e6),
+        nanos => format!("{:.2} s", nanos / 1e9),
+    }
+}
+
+fn bench<T>(name: &str, mut routine: impl FnMut() -> T) {
+    if let Some(filter) = env::args().skip(1).find(|arg| !arg.starts_with('-')) {
+        if !name.contains(&filter) {
+            return;
+        }
+    }
+
+    // Warming up also gives us a rough idea of how long each iteration takes
+    let start = Instant::now();
+    let mut iterations = 0u32;
+    while start.elapsed() < WARM_UP {
+        black_box(routine());
+        iterations += 1;
+    }
+
+    let per_iteration = start.elapsed() / iterations;
+    let iterations = (SAMPLE_TIME.as_nanos() / per_iteration.as_nanos().max(1)).max(1) as u32;
+
+    let mut samples: Vec<f64> = (0..SAMPLES)
+        .map(|_| {
+            let start = Instant::now();
+            for _ in 0..iterations {
+                black_box(routine());
+            }
+            start.elapsed().as_nanos() as f64 / iterations as f64
+        })
+        .collect();
+
+    samples.sort_unstable_by(f64::total_cmp);
+
+    let mean = samples.iter().sum::<f64>() / SAMPLES as f64;
+    let deviation = (samples
+        .iter()
+        .map(|sample| (sample - mean).powi(2))
+        .sum::<f64>()
+        / (SAMPLES - 1) as f64)
+        .sqrt();
+    // 95% confidence interval of the mean
+    let margin = 1.96 * deviation / (SAMPLES as f64).sqrt();
+
+    println!(
+        "{name:<32} time: [{} {} {}]  median: {}  std dev: {}",
+        format_time(mean - margin),
+        format_time(mean),
+        format_time(mean + margin),
+        format_time(samples[SAMPLES / 2]),
+        format_time(deviation),
+    );
+}
+
+/// Benchmarks rendering `Page` from a fresh instance, from an instance that's already parsed
+/// everything and from the templates directly
+fn compare(group: &str, templates: HashMap<String, String>, inputs: HashMap<String, String>) {
+    let mut parsed = VelInstance::new(templates.clone());
+    let mut streaming = VelInstance::new(templates.clone());
+
+    let expected = parsed
+        .render("Page".to_string(), inputs.clone(), Some)
+        .expect("the benchmark templates are broken");
+    assert_eq!(
+        streaming.render_streaming("Page".to_string(), inputs.clone()),
+        Ok(expected),
+        "the renderers disagree on {group}"
+    );
+
+    bench(&format!("{group}/dom, parsing"), || {
+        VelInstance::new(templates.clone()).render("Page".to_string(), inputs.clone(), Some)
+    });
+    bench(&format!("{group}/dom, parsed"), || {
+        parsed.render("Page".to_string(), inputs.clone(), Some)
+    });
+    bench(&format!("{group}/streaming"), || {
+        streaming.render_streaming("Page".to_string(), inputs.clone())
+    });
+}
+
+/// Fifty components, each wrapping the next
+fn deep_nesting() -> HashMap<String, String> {
+    const DEPTH: usize = 50;
+
+    let mut templates: HashMap<String, String> = (0..DEPTH)
+        .map(|level| {
+            (
+                format!("Level{level}"),
+                format!(
+                    "<section class=\"level-{level}\"><h2>{{title}}</h2><Level{} title=\"{{title}}\"><Slot></Slot></Level{}></section>",
+                    level + 1,
+                    level + 1
+                ),
+            )
+        })
+        .collect();
+
+    templates.insert(format!("Level{DEPTH}"), "<p><Slot></Slot></p>".to_string());
+    templates.insert(
+        "Page".to_string(),
+        "<Level0 title=\"{title}\">The bottom, {name}!</Level0>".to_string(),
+    );
+
+    templates
+}
+
+/// A layout with lots of named slots, all filled by the page
+fn many_slots() -> HashMap<String, String> {
+    const SLOTS: usize = 32;
+
+    let grid = (0..SLOTS)
+        .map(|slot| format!("<div class=\"cell\"><Slot name=\"cell-{slot}\">empty</Slot></div>"))
+        .collect::<String>();
+
+    let page = (0..SLOTS)
+        .map(|slot| format!("\n  <p slot=\"cell-{slot}\">{{name}} is in cell {slot}</p>"))
+        .collect::<String>();
+
+    HashMap::from([
+        ("Grid".to_string(), format!("<main>{grid}</main>")),
+        ("Page".to_string(), format!("<Grid>{page}\n</Grid>")),
+    ])
+}
+
+/// There's no way to loop in a template yet, so this is the unrolled equivalent of a thousand
+/// item list
+fn large_list() -> HashMap<String, String> {
+    const ROWS: usize = 1000;
+
+    let rows = (0..ROWS)
+        .map(|row| format!("\n  <Row index=\"{row}\" name=\"{{name}}\"></Row>"))
+        .collect::<String>();
+
+    HashMap::from([
+        (
+            "Row".to_string(),
+            "<li id=\"row-{index}\"><a href=\"/items/{index}\">{name} #{index}</a></li>"
+                .to_string(),
+        ),
+        ("Page".to_string(), format!("<ul>{rows}\n</ul>")),
+    ])
+}
+
+fn main() {
+    let inputs: HashMap<String, String> = [("title", "Benchmarks & co"), ("name", "<Vel>")]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+
+    compare("deep nesting", deep_nesting(), inputs.clone());
+    compare("many slots", many_slots(), inputs.clone());
+    compare("large list", large_list(), inputs);
+}
@@ -2,8 +2,16 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
-use elements::Document;
-use std::{fmt::Debug, sync::Arc};
+use crate::{
+    query::{Selected, Selector},
+    visit::{Visit, VisitMut},
+};
+use elements::{AttributeValues, Document, Element, SlotContent};
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::Debug,
+    sync::Arc,
+};
 
 #[derive(Debug, Clone)]
 pub struct Dom {
@@ -16,13 +24,333 @@ impl Dom {
             tree: Arc::new(root),
         }
     }
+
+    /// Gets the names of every input the component needs to be rendered.
+    /// Variables inside of the components it uses are filled by their call site attributes, so only
+    /// the ones that reach back up to this component count.
+    pub fn inputs(&self) -> BTreeSet<&str> {
+        let mut inputs = BTreeSet::new();
+        collect_inputs(&self.tree.children, &mut inputs);
+        inputs
+    }
+
+    /// Gets the names of the components this one uses directly, not counting the ones those use.
+    pub fn components(&self) -> BTreeSet<&str> {
+        let mut components = BTreeSet::new();
+        collect_components(&self.tree.children, &mut components);
+        components
+    }
+
+    /// Gets the names of every slot this component declares with `<Slot>`.
+    pub fn slots(&self) -> BTreeSet<&[AttributeValues]> {
+        let mut slots = BTreeSet::new();
+        collect_slots(&self.tree.children, &mut slots);
+        slots
+    }
+
+    /// Gets every component this one uses directly, alongside the slot content it gives them.
+    pub fn calls(&self) -> Vec<(&str, &SlotContent)> {
+        let mut calls = vec![];
+        collect_calls(&self.tree.children, &mut calls);
+        calls
+    }
+
+    /// Finds every element that `selector` matches, in the order they were written. Slot content is
+    /// searched in order of the slot it's for.
+    pub fn select(&self, selector: &Selector) -> Vec<Selected<'_>> {
+        selector.select(self)
+    }
+
+    /// Visits the component's body. The component itself has no [`Element`] to enter or exit.
+    pub fn visit<'a>(&'a self, visitor: &mut impl Visit<'a>) {
+        visitor.visit_document(&self.tree);
+    }
+
+    pub fn visit_mut(&mut self, visitor: &mut impl VisitMut) {
+        visitor.visit_document(Arc::make_mut(&mut self.tree));
+    }
+
+    /// Returns whether any of `components` were inlined into this one while it was parsed, however
+    /// deeply they're nested.
+    pub(crate) fn uses_any(&self, components: &HashSet<String>) -> bool {
+        uses_any(&self.tree.children, components)
+    }
+}
+
+fn collect_attribute_inputs<'a>(
+    values: impl IntoIterator<Item = &'a Vec<AttributeValues>>,
+    inputs: &mut BTreeSet<&'a str>,
+) {
+    for value in values.into_iter().flatten() {
+        if let AttributeValues::Variable(variable) = value {
+            inputs.insert(&variable.name);
+        }
+    }
+}
+
+fn collect_inputs<'a>(children: &'a [Arc<Element>], inputs: &mut BTreeSet<&'a str>) {
+    for child in children {
+        match child.as_ref() {
+            Element::Document(document) => {
+                collect_attribute_inputs(document.attributes.values(), inputs);
+
+                // The slot content is rendered where it was written, unlike the component's body
+                for content in document.slot_content.values() {
+                    collect_inputs(content, inputs);
+                }
+            }
+            Element::Node(node) => {
+                collect_attribute_inputs(node.attributes.values(), inputs);
+                collect_inputs(&node.children, inputs);
+            }
+            Element::Slot(slot) => {
+                collect_attribute_inputs([&slot.name], inputs);
+                collect_attribute_inputs(slot.attributes.values(), inputs);
+                collect_inputs(&slot.children, inputs);
+            }
+            Element::Text(_) => (),
+            Element::Variable(variable) => {
+                inputs.insert(&variable.name);
+            }
+            Element::Void(void) => collect_attribute_inputs(void.attributes.values(), inputs),
+        }
+    }
+}
+
+fn collect_components<'a>(children: &'a [Arc<Element>], components: &mut BTreeSet<&'a str>) {
+    for child in children {
+        match child.as_ref() {
+            Element::Document(document) => {
+                components.insert(&document.name);
+
+                // The children are the used component's own body, so only the slot content is ours
+                for content in document.slot_content.values() {
+                    collect_components(content, components);
+                }
+            }
+            Element::Node(node) => collect_components(&node.children, components),
+            Element::Slot(slot) => collect_components(&slot.children, components),
+            Element::Text(_) | Element::Variable(_) | Element::Void(_) => (),
+        }
+    }
+}
+
+fn collect_slots<'a>(children: &'a [Arc<Element>], slots: &mut BTreeSet<&'a [AttributeValues]>) {
+    for child in children {
+        match child.as_ref() {
+            Element::Document(document) => {
+                for content in document.slot_content.values() {
+                    collect_slots(content, slots);
+                }
+            }
+            Element::Node(node) => collect_slots(&node.children, slots),
+            Element::Slot(slot) => {
+                slots.insert(&slot.name);
+                collect_slots(&slot.children, slots);
+            }
+            Element::Text(_) | Element::Variable(_) | Element::Void(_) => (),
+        }
+    }
+}
+
+fn collect_calls<'a>(children: &'a [Arc<Element>], calls: &mut Vec<(&'a str, &'a SlotContent)>) {
+    for child in children {
+        match child.as_ref() {
+            Element::Document(document) => {
+                calls.push((&document.name, &document.slot_content));
+
+                for content in document.slot_content.values() {
+                    collect_calls(content, calls);
+                }
+            }
+            Element::Node(node) => collect_calls(&node.children, calls),
+            Element::Slot(slot) => collect_calls(&slot.children, calls),
+            Element::Text(_) | Element::Variable(_) | Element::Void(_) => (),
+        }
+    }
+}
+
+fn uses_any(children: &[Arc<Element>], components: &HashSet<String>) -> bool {
+    children.iter().any(|child| match child.as_ref() {
+        Element::Document(document) => {
+            components.contains(&document.name)
+                || uses_any(&document.children, components)
+                || document
+                    .slot_content
+                    .values()
+                    .any(|content| uses_any(content, components))
+        }
+        Element::Node(node) => uses_any(&node.children, components),
+        Element::Slot(slot) => uses_any(&slot.children, components),
+        Element::Text(_) | Element::Variable(_) | Element::Void(_) => false,
+    })
 }
 
 pub mod elements {
     use super::Arc;
-    use std::{collections::HashMap, fmt::Debug};
+    use crate::Span;
+    use std::{
+        borrow::Borrow,
+        cmp::Ordering,
+        collections::{BTreeMap, HashMap},
+        fmt::{self, Debug},
+        hash::{Hash, Hasher},
+        ops::Deref,
+    };
+
+    /// A string taken from a component's template. Parsed templates share their `Arc<str>` with
+    /// every piece of text, variable and attribute in them, rather than copying each out.
+    ///
+    /// It's used like a `&str` through [`Deref`], and compares, hashes and orders like one too.
+    /// Text that had escapes in it, or that came from somewhere other than a template in memory,
+    /// has its own allocation instead.
+    #[derive(Clone)]
+    pub struct TemplateStr {
+        source: Arc<str>,
+        span: Span,
+    }
+
+    impl TemplateStr {
+        /// Takes `span` of `source` without copying it. The span has to be on char boundaries.
+        pub fn new(source: &Arc<str>, span: Span) -> Self {
+            assert!(
+                source.get(span.start..span.end).is_some(),
+                "a TemplateStr's span has to be within its source, on char boundaries"
+            );
 
-    #[derive(Debug, Clone, Hash, PartialEq, Eq)]
+            Self {
+                source: source.clone(),
+                span,
+            }
+        }
+
+        pub fn as_str(&self) -> &str {
+            &self.source[self.span.start..self.span.end]
+        }
+
+        /// The template it was taken from, or its own copy when it couldn't be
+        pub fn source(&self) -> &Arc<str> {
+            &self.source
+        }
+
+        /// Where it is in [`Self::source`]
+        pub fn span(&self) -> Span {
+            self.span
+        }
+    }
+
+    impl Default for TemplateStr {
+        fn default() -> Self {
+            Self::from("")
+        }
+    }
+
+    impl Deref for TemplateStr {
+        type Target = str;
+
+        fn deref(&self) -> &str {
+            self.as_str()
+        }
+    }
+
+    impl AsRef<str> for TemplateStr {
+        fn as_ref(&self) -> &str {
+            self.as_str()
+        }
+    }
+
+    impl Borrow<str> for TemplateStr {
+        fn borrow(&self) -> &str {
+            self.as_str()
+        }
+    }
+
+    impl From<&str> for TemplateStr {
+        fn from(string: &str) -> Self {
+            Self {
+                source: Arc::from(string),
+                span: Span::new(0, string.len()),
+            }
+        }
+    }
+
+    impl From<String> for TemplateStr {
+        fn from(string: String) -> Self {
+            let span = Span::new(0, string.len());
+            Self {
+                source: Arc::from(string),
+                span,
+            }
+        }
+    }
+
+    impl From<TemplateStr> for String {
+        fn from(string: TemplateStr) -> Self {
+            string.as_str().to_string()
+        }
+    }
+
+    impl PartialEq for TemplateStr {
+        fn eq(&self, other: &Self) -> bool {
+            self.as_str() == other.as_str()
+        }
+    }
+
+    impl Eq for TemplateStr {}
+
+    impl PartialEq<str> for TemplateStr {
+        fn eq(&self, other: &str) -> bool {
+            self.as_str() == other
+        }
+    }
+
+    impl PartialEq<&str> for TemplateStr {
+        fn eq(&self, other: &&str) -> bool {
+            self.as_str() == *other
+        }
+    }
+
+    impl PartialOrd for TemplateStr {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for TemplateStr {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.as_str().cmp(other.as_str())
+        }
+    }
+
+    impl Hash for TemplateStr {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.as_str().hash(state);
+        }
+    }
+
+    impl Debug for TemplateStr {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            Debug::fmt(self.as_str(), f)
+        }
+    }
+
+    impl fmt::Display for TemplateStr {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(self.as_str())
+        }
+    }
+
+    /// Where a component was called or a slot was declared, for pointing back at it when
+    /// debugging
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Location {
+        /// The component whose template it's written in
+        pub component: Arc<str>,
+        /// Where its opening tag is in that template
+        pub span: Span,
+    }
+
+    #[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
     pub enum AttributeValues {
         Text(Text),
         Variable(Variable),
@@ -39,8 +367,12 @@ pub mod elements {
         Void(Void),
     }
 
-    /// Just a shorthand alias of HashMap<String, AttributeValues> for use within the crate
-    pub(crate) type Attributes = HashMap<String, Vec<AttributeValues>>;
+    /// Just a shorthand alias of BTreeMap<TemplateStr, AttributeValues> for use within the crate.
+    /// It's ordered so that rendering the same element always gives the same output.
+    pub(crate) type Attributes = BTreeMap<TemplateStr, Vec<AttributeValues>>;
+
+    /// The content a component's call site fills each of its slots with, keyed by slot name
+    pub(crate) type SlotContent = HashMap<Vec<AttributeValues>, Vec<Arc<Element>>>;
 
     /// The document element for the page.
     /// Because this is always the top level element, we can clone it for storing Dom-depending state data here for
@@ -49,8 +381,10 @@ pub mod elements {
     pub struct Document {
         pub name: String,
         pub attributes: Attributes,
-        pub slot_content: HashMap<Vec<AttributeValues>, Vec<Arc<Element>>>,
+        pub slot_content: SlotContent,
         pub children: Vec<Arc<Element>>,
+        /// Where it was called, or [`None`] when it wasn't parsed from a template
+        pub location: Option<Location>,
     }
 
     impl Document {
@@ -58,8 +392,9 @@ pub mod elements {
             Self {
                 name,
                 attributes: Attributes::new(),
-                slot_content: HashMap::new(),
+                slot_content: SlotContent::new(),
                 children: vec![],
+                location: None,
             }
         }
     }
@@ -76,16 +411,18 @@ pub mod elements {
         pub name: Vec<AttributeValues>,
         pub attributes: Attributes,
         pub children: Vec<Arc<Element>>,
+        /// Where it was declared, or [`None`] when it wasn't parsed from a template
+        pub location: Option<Location>,
     }
 
-    #[derive(Debug, Clone, Hash, PartialEq, Eq)]
+    #[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
     pub struct Text {
-        pub value: String,
+        pub value: TemplateStr,
     }
 
-    #[derive(Debug, Clone, Hash, PartialEq, Eq)]
+    #[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
     pub struct Variable {
-        pub name: String,
+        pub name: TemplateStr,
     }
 
     #[derive(Debug, Clone)]
@@ -0,0 +1,17 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! The `vel-lsp` language server, for editors to run. See [`vel::lsp`].
+
+use std::{io, process::ExitCode};
+
+fn main() -> ExitCode {
+    match vel::lsp::run(io::stdin().lock(), io::stdout().lock()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
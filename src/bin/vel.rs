@@ -0,0 +1,425 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! The `vel` command, for rendering and checking templates without writing any Rust.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
+use vel::{
+    format::{self, FormatOptions},
+    inputs,
+    loading::{self, Naming, COMPONENT_EXTENSION},
+    output::{OutputStyle, RenderOptions},
+    serve::Server,
+    ssg::{BuildReport, Site, SiteConfig},
+    ParsingError, VelInstance,
+};
+
+const USAGE: &str = "\
+usage:
+  vel render <component> [--dir <dir>] [--inputs <file>]... [--set <key=value>]... [--minify | --pretty] [--debug]
+            [--source-map <file>]
+      Renders a component, with inputs from `.json` or `.toml` files and `--set`, and with
+      `--source-map` writes where each part of the output came from to a JSON file
+  vel check [<dir>]
+      Parses every component and reports any problems
+  vel deps <component> [--dir <dir>]
+      Prints the components a component uses, and the ones they use
+  vel fmt [--check] <path>...
+      Formats templates, or with `--check` lists the ones that aren't formatted
+  vel build <manifest> [--dir <dir>] [--assets <dir>] [--out <dir>] [--watch] [--minify | --pretty] [--debug]
+      Renders every page in a `.json` or `.toml` manifest to `--out`, which is `dist` by
+      default, and with `--watch` keeps rebuilding what changed
+  vel serve <manifest> [--dir <dir>] [--assets <dir>] [--out <dir>] [--port <port>]
+      Builds the site and serves it on localhost, rebuilding and reloading the page when
+      anything changes
+
+options:
+  --dir <dir>         Where the components are, which is the current directory by default
+  --assets <dir>      Files to copy into the built site as they are
+  --minify            Renders HTML as small as it can be
+  --pretty            Renders HTML indented, with block elements on their own lines
+  --debug             Marks where each component and slot starts and ends with comments,
+                      which say the template and line they were written on
+  --relative-names    Names components by their path, like `ui.Button`, instead of their file name";
+
+/// Something that went wrong, to be printed before exiting
+type Failure = String;
+
+/// The arguments after the subcommand, split into the ones that stand alone and the options
+struct Arguments {
+    positional: Vec<String>,
+    options: Vec<(String, String)>,
+    flags: Vec<String>,
+}
+
+impl Arguments {
+    fn parse(mut arguments: impl Iterator<Item = String>) -> Result<Self, Failure> {
+        let mut parsed = Arguments {
+            positional: vec![],
+            options: vec![],
+            flags: vec![],
+        };
+
+        while let Some(argument) = arguments.next() {
+            match argument.as_str() {
+                "--dir" | "--inputs" | "--set" | "--assets" | "--out" | "--port"
+                | "--source-map" => {
+                    let value = arguments
+                        .next()
+                        .ok_or_else(|| format!("`{argument}` needs a value"))?;
+                    parsed.options.push((argument, value));
+                }
+                "--check" | "--relative-names" | "--watch" | "--minify" | "--pretty"
+                | "--debug" => parsed.flags.push(argument),
+                _ if argument.starts_with("--") => {
+                    return Err(format!("unknown option `{argument}`"))
+                }
+                _ => parsed.positional.push(argument),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    fn options<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.options
+            .iter()
+            .filter(move |(option, _)| option == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|flag| flag == name)
+    }
+
+    fn naming(&self) -> Naming {
+        match self.flag("--relative-names") {
+            true => Naming::RelativePath,
+            false => Naming::FileStem,
+        }
+    }
+
+    fn render_options(&self) -> RenderOptions {
+        RenderOptions {
+            style: match (self.flag("--minify"), self.flag("--pretty")) {
+                (true, _) => OutputStyle::Minified,
+                (false, true) => OutputStyle::Pretty,
+                (false, false) => OutputStyle::AsWritten,
+            },
+            debug: self.flag("--debug"),
+        }
+    }
+
+    fn dir(&self) -> PathBuf {
+        PathBuf::from(self.options("--dir").last().unwrap_or("."))
+    }
+
+    /// Gets the only positional argument, which `what` describes
+    fn single(&self, what: &str) -> Result<&str, Failure> {
+        match self.positional.as_slice() {
+            [single] => Ok(single),
+            [] => Err(format!("expected {what}")),
+            [_, extra, ..] => Err(format!("unexpected argument `{extra}`")),
+        }
+    }
+}
+
+fn instance(arguments: &Arguments, dir: &Path) -> Result<VelInstance, Failure> {
+    VelInstance::from_dir(dir, arguments.naming())
+        .map_err(|error| format!("{}: {error}", dir.display()))
+}
+
+fn render(arguments: Arguments) -> Result<ExitCode, Failure> {
+    let component = arguments.single("a component to render")?;
+    let mut instance = instance(&arguments, &arguments.dir())?;
+    instance.set_render_options(arguments.render_options());
+    let mut values = HashMap::new();
+
+    for path in arguments.options("--inputs") {
+        let source =
+            fs::read_to_string(path).map_err(|error| format!("failed to read {path}: {error}"))?;
+        let file = inputs::from_file_contents(Path::new(path), &source)
+            .map_err(|error| format!("{path}: {error}"))?;
+        values.extend(file);
+    }
+
+    for pair in arguments.options("--set") {
+        let (key, value) = inputs::from_pair(pair).map_err(|error| error.to_string())?;
+        values.insert(key, value);
+    }
+
+    let output = match arguments.options("--source-map").last() {
+        Some(path) => {
+            let (output, map) = instance
+                .render_with_source_map(component.to_string(), values)
+                .map_err(|error| error.to_string())?;
+            fs::write(path, map.to_json())
+                .map_err(|error| format!("failed to write {path}: {error}"))?;
+            output
+        }
+        None => instance
+            .render(component.to_string(), values, Some)
+            .map_err(|error| error.to_string())?,
+    };
+    println!("{output}");
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints where an error is, like `ui/Button.vel:3:7`, falling back to the component's name
+fn locate(error: &ParsingError, paths: &HashMap<String, PathBuf>) -> String {
+    let Some(path) = paths.get(&error.component) else {
+        return error.component.clone();
+    };
+
+    let (line, column) = match fs::read_to_string(path) {
+        Ok(source) => error.span.line_column(&source),
+        Err(_) => (1, 1),
+    };
+
+    format!("{}:{line}:{column}", path.display())
+}
+
+fn check(arguments: Arguments) -> Result<ExitCode, Failure> {
+    let dir = match arguments.positional.as_slice() {
+        [] => arguments.dir(),
+        [dir] => PathBuf::from(dir),
+        [_, extra, ..] => return Err(format!("unexpected argument `{extra}`")),
+    };
+
+    let paths = loading::scan_dir(&dir, arguments.naming())
+        .map_err(|error| format!("{}: {error}", dir.display()))?;
+    let mut instance = instance(&arguments, &dir)?;
+
+    match instance.compile_all(0) {
+        Ok(()) => {
+            eprintln!("checked {} components", paths.len());
+            Ok(ExitCode::SUCCESS)
+        }
+        Err(errors) => {
+            for error in &errors {
+                eprintln!("{}: {error}", locate(error, &paths));
+            }
+            eprintln!("found {} problems", errors.len());
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
+
+fn deps(arguments: Arguments) -> Result<ExitCode, Failure> {
+    let component = arguments.single("a component")?;
+    let mut instance = instance(&arguments, &arguments.dir())?;
+
+    println!("{component}");
+    print_dependencies(&mut instance, component, "")?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_dependencies(
+    instance: &mut VelInstance,
+    component: &str,
+    prefix: &str,
+) -> Result<(), Failure> {
+    let dependencies: Vec<String> = instance
+        .dom(component)
+        .map_err(|error| error.to_string())?
+        .components()
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+
+    for (index, dependency) in dependencies.iter().enumerate() {
+        let last = index + 1 == dependencies.len();
+        let (branch, indent) = match last {
+            true => ("└── ", "    "),
+            false => ("├── ", "│   "),
+        };
+        println!("{prefix}{branch}{dependency}");
+        print_dependencies(instance, dependency, &format!("{prefix}{indent}"))?;
+    }
+
+    Ok(())
+}
+
+/// Every template file `path` is or has in it
+fn template_files(path: &Path) -> Result<Vec<PathBuf>, Failure> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files: Vec<PathBuf> = loading::scan_dir(path, Naming::RelativePath)
+        .map_err(|error| format!("{}: {error}", path.display()))?
+        .into_values()
+        .collect();
+    files.sort_unstable();
+
+    Ok(files)
+}
+
+fn fmt(arguments: Arguments) -> Result<ExitCode, Failure> {
+    if arguments.positional.is_empty() {
+        return Err("expected a file or directory to format".to_string());
+    }
+
+    let check = arguments.flag("--check");
+    let mut unformatted = 0;
+    let mut failed = false;
+
+    for path in &arguments.positional {
+        for file in template_files(Path::new(path))? {
+            if file
+                .extension()
+                .is_some_and(|extension| extension != COMPONENT_EXTENSION)
+            {
+                eprintln!("skipping {}, which isn't a template", file.display());
+                continue;
+            }
+
+            let source = fs::read_to_string(&file)
+                .map_err(|error| format!("failed to read {}: {error}", file.display()))?;
+            let name = file.file_stem().unwrap_or_default().to_string_lossy();
+
+            let formatted = match format::format(&name, &source, &FormatOptions::default()) {
+                Ok(formatted) => formatted,
+                Err(error) => {
+                    let (line, column) = error.span.line_column(&source);
+                    eprintln!("{}:{line}:{column}: {error}", file.display());
+                    failed = true;
+                    continue;
+                }
+            };
+
+            if formatted == source {
+                continue;
+            }
+
+            unformatted += 1;
+            match check {
+                true => println!("{}", file.display()),
+                false => fs::write(&file, formatted)
+                    .map_err(|error| format!("failed to write {}: {error}", file.display()))?,
+            }
+        }
+    }
+
+    match failed || (check && unformatted > 0) {
+        true => Ok(ExitCode::FAILURE),
+        false => Ok(ExitCode::SUCCESS),
+    }
+}
+
+/// Prints what a build did, returning whether every page was rendered
+fn print_report(report: &BuildReport) -> bool {
+    for route in &report.removed {
+        eprintln!("removed {route}");
+    }
+    for (route, error) in &report.failed {
+        eprintln!("failed to render {route}: {error}");
+    }
+
+    eprintln!(
+        "rendered {} pages, {} unchanged, copied {} assets",
+        report.rendered.len(),
+        report.unchanged,
+        report.assets
+    );
+
+    report.failed.is_empty()
+}
+
+fn site(arguments: &Arguments) -> Result<Site, Failure> {
+    let manifest = arguments.single("a manifest listing the pages")?;
+
+    let mut site = Site::new(SiteConfig {
+        components: arguments.dir(),
+        naming: arguments.naming(),
+        manifest: PathBuf::from(manifest),
+        assets: arguments.options("--assets").last().map(PathBuf::from),
+        output: PathBuf::from(arguments.options("--out").last().unwrap_or("dist")),
+    })
+    .map_err(|error| error.to_string())?;
+
+    site.instance_mut()
+        .set_render_options(arguments.render_options());
+    Ok(site)
+}
+
+fn build(arguments: Arguments) -> Result<ExitCode, Failure> {
+    let mut site = site(&arguments)?;
+
+    let built = print_report(&site.build().map_err(|error| error.to_string())?);
+
+    if !arguments.flag("--watch") {
+        return match built {
+            true => Ok(ExitCode::SUCCESS),
+            false => Ok(ExitCode::FAILURE),
+        };
+    }
+
+    loop {
+        // A broken manifest is likely to be fixed soon, so it shouldn't stop the watching
+        match site.wait(Duration::from_millis(250)) {
+            Ok(report) => _ = print_report(&report),
+            Err(error) => eprintln!("error: {error}"),
+        }
+    }
+}
+
+fn serve(arguments: Arguments) -> Result<ExitCode, Failure> {
+    let port = match arguments.options("--port").last() {
+        Some(port) => port
+            .parse::<u16>()
+            .map_err(|_| format!("`{port}` isn't a port"))?,
+        None => 8000,
+    };
+
+    let server = Server::bind(site(&arguments)?, ("127.0.0.1", port))
+        .map_err(|error| format!("failed to listen on port {port}: {error}"))?;
+    let address = server.local_addr().map_err(|error| error.to_string())?;
+    eprintln!("serving on http://{address}");
+
+    server.run().map_err(|error| error.to_string())?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> ExitCode {
+    let mut arguments = std::env::args().skip(1);
+    let command = arguments.next();
+
+    let run = match command.as_deref() {
+        Some("render") => render,
+        Some("check") => check,
+        Some("deps") => deps,
+        Some("fmt") => fmt,
+        Some("build") => build,
+        Some("serve") => serve,
+        Some("help" | "--help" | "-h") => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Some(command) => {
+            eprintln!("unknown command `{command}`\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+        None => {
+            eprintln!("{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match Arguments::parse(arguments).and_then(run) {
+        Ok(code) => code,
+        Err(failure) => {
+            eprintln!("error: {failure}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,295 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Building components in Rust rather than writing them as templates, for when they're generated
+//! from something else, like a table from a schema.
+//!
+//! ```
+//! use vel::{builder, VelInstance};
+//!
+//! let table = builder::component("Table")
+//!     .child(
+//!         builder::node("table").attribute("class", "{kind}").child(
+//!             builder::node("tr")
+//!                 .child(builder::node("td").child(builder::variable("name")))
+//!                 .child(builder::node("td").child(builder::slot("default"))),
+//!         ),
+//!     )
+//!     .build();
+//!
+//! let mut instance = VelInstance::new(Default::default());
+//! instance.register("Table".to_string(), table).unwrap();
+//! ```
+//!
+//! Attribute values are written the same way as in templates, so `{name}` is a variable and `\`
+//! escapes the char after it. Components used with [`call`] are inlined when the component is
+//! registered with [`crate::VelInstance::register`], just like they would be when parsing.
+
+use crate::{
+    ast::{
+        elements::{Attributes, Document, Element, Node, Slot, TemplateStr, Text, Variable, Void},
+        Dom,
+    },
+    parsing::{parse_attribute_value, Span},
+    visit::{self, VisitMut},
+    ComponentsCache, ParsingError, ParsingErrorKind,
+};
+use std::{collections::HashSet, sync::Arc};
+
+/// Starts building a component called `name`
+pub fn component(name: impl Into<String>) -> ComponentBuilder {
+    ComponentBuilder {
+        document: Document::new(name.into()),
+    }
+}
+
+pub fn node(name: impl Into<String>) -> NodeBuilder {
+    NodeBuilder {
+        node: Node {
+            name: name.into(),
+            attributes: Attributes::new(),
+            children: vec![],
+        },
+    }
+}
+
+pub fn void(name: impl Into<String>) -> VoidBuilder {
+    VoidBuilder {
+        void: Void {
+            name: name.into(),
+            attributes: Attributes::new(),
+        },
+    }
+}
+
+/// Declares a slot called `name`, which its children fill when the call site doesn't
+pub fn slot(name: &str) -> SlotBuilder {
+    SlotBuilder {
+        slot: Slot {
+            name: parse_attribute_value(name),
+            attributes: Attributes::new(),
+            children: vec![],
+            location: None,
+        },
+    }
+}
+
+/// Uses the component called `name`
+pub fn call(name: impl Into<String>) -> CallBuilder {
+    CallBuilder {
+        document: Document::new(name.into()),
+    }
+}
+
+/// Text that's written out as it is, like text in a template
+pub fn text(value: impl Into<String>) -> Element {
+    Element::Text(Text {
+        value: TemplateStr::from(value.into()),
+    })
+}
+
+pub fn variable(name: impl Into<String>) -> Element {
+    Element::Variable(Variable {
+        name: TemplateStr::from(name.into()),
+    })
+}
+
+pub struct ComponentBuilder {
+    document: Document,
+}
+
+impl ComponentBuilder {
+    pub fn child(mut self, child: impl Into<Element>) -> Self {
+        self.document.children.push(Arc::new(child.into()));
+        self
+    }
+
+    pub fn children<E: Into<Element>>(mut self, children: impl IntoIterator<Item = E>) -> Self {
+        self.document
+            .children
+            .extend(children.into_iter().map(|child| Arc::new(child.into())));
+        self
+    }
+
+    pub fn build(self) -> Dom {
+        Dom::new(self.document)
+    }
+}
+
+pub struct NodeBuilder {
+    node: Node,
+}
+
+impl NodeBuilder {
+    pub fn attribute(mut self, key: impl Into<String>, value: &str) -> Self {
+        self.node
+            .attributes
+            .insert(TemplateStr::from(key.into()), parse_attribute_value(value));
+        self
+    }
+
+    pub fn child(mut self, child: impl Into<Element>) -> Self {
+        self.node.children.push(Arc::new(child.into()));
+        self
+    }
+
+    pub fn children<E: Into<Element>>(mut self, children: impl IntoIterator<Item = E>) -> Self {
+        self.node
+            .children
+            .extend(children.into_iter().map(|child| Arc::new(child.into())));
+        self
+    }
+}
+
+impl From<NodeBuilder> for Element {
+    fn from(builder: NodeBuilder) -> Self {
+        Element::Node(builder.node)
+    }
+}
+
+pub struct VoidBuilder {
+    void: Void,
+}
+
+impl VoidBuilder {
+    pub fn attribute(mut self, key: impl Into<String>, value: &str) -> Self {
+        self.void
+            .attributes
+            .insert(TemplateStr::from(key.into()), parse_attribute_value(value));
+        self
+    }
+}
+
+impl From<VoidBuilder> for Element {
+    fn from(builder: VoidBuilder) -> Self {
+        Element::Void(builder.void)
+    }
+}
+
+pub struct SlotBuilder {
+    slot: Slot,
+}
+
+impl SlotBuilder {
+    pub fn attribute(mut self, key: impl Into<String>, value: &str) -> Self {
+        self.slot
+            .attributes
+            .insert(TemplateStr::from(key.into()), parse_attribute_value(value));
+        self
+    }
+
+    /// Adds to what's rendered when the call site doesn't fill the slot
+    pub fn child(mut self, child: impl Into<Element>) -> Self {
+        self.slot.children.push(Arc::new(child.into()));
+        self
+    }
+}
+
+impl From<SlotBuilder> for Element {
+    fn from(builder: SlotBuilder) -> Self {
+        Element::Slot(builder.slot)
+    }
+}
+
+pub struct CallBuilder {
+    document: Document,
+}
+
+impl CallBuilder {
+    /// Gives the component an input
+    pub fn attribute(mut self, key: impl Into<String>, value: &str) -> Self {
+        self.document
+            .attributes
+            .insert(TemplateStr::from(key.into()), parse_attribute_value(value));
+        self
+    }
+
+    /// Adds to the content of the default slot
+    pub fn child(self, child: impl Into<Element>) -> Self {
+        self.content("default", child)
+    }
+
+    /// Adds to the content of the slot called `slot`
+    pub fn content(mut self, slot: &str, child: impl Into<Element>) -> Self {
+        self.document
+            .slot_content
+            .entry(parse_attribute_value(slot))
+            .or_default()
+            .push(Arc::new(child.into()));
+        self
+    }
+}
+
+impl From<CallBuilder> for Element {
+    fn from(builder: CallBuilder) -> Self {
+        Element::Document(builder.document)
+    }
+}
+
+/// Fills in the body of every component a built component uses, which the parser would've done
+struct Inliner<'c> {
+    component: &'c str,
+    components: &'c mut ComponentsCache,
+    error: Option<ParsingError>,
+}
+
+impl VisitMut for Inliner<'_> {
+    fn visit_document(&mut self, document: &mut Document) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let component = self.component;
+        let recursive = || {
+            ParsingError::new(
+                component,
+                ParsingErrorKind::RecursiveComponent(document.name.clone()),
+                Span::default(),
+            )
+        };
+
+        if document.name == component {
+            self.error = Some(recursive());
+            return;
+        }
+
+        let body = match self.components.parsed(&document.name) {
+            Ok(used) if used.uses_any(&HashSet::from([component.to_string()])) => Err(recursive()),
+            Ok(used) => Ok(used.tree.children.clone()),
+            Err(error) => Err(error),
+        };
+
+        match body {
+            Ok(body) => {
+                document.children = body;
+
+                // Only the slot content was built here, the body has already been inlined
+                for content in document.slot_content.values_mut() {
+                    self.visit_children(content);
+                }
+            }
+            Err(error) => self.error = Some(error),
+        }
+    }
+}
+
+/// Inlines every component `dom` uses, as `component`
+pub(crate) fn inline_calls(
+    component: &str,
+    dom: &mut Dom,
+    components: &mut ComponentsCache,
+) -> Result<(), ParsingError> {
+    let mut inliner = Inliner {
+        component,
+        components,
+        error: None,
+    };
+
+    visit::walk_children_mut(&mut inliner, &mut Arc::make_mut(&mut dom.tree).children);
+
+    match inliner.error {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,414 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Compiling components into Rust ahead of time, from a build script.
+//!
+//! Each component becomes a struct with a field for every input it needs, and a `render` method
+//! that writes its HTML out directly without parsing anything at runtime:
+//!
+//! ```no_run
+//! // build.rs
+//! use std::{env, path::Path};
+//! use vel::{loading::Naming, VelInstance};
+//!
+//! let mut instance = VelInstance::from_dir("templates", Naming::FileStem).unwrap();
+//! let out = Path::new(&env::var("OUT_DIR").unwrap()).join("templates.rs");
+//!
+//! vel::codegen::write(&mut instance, &["Page"], out).unwrap();
+//! println!("cargo:rerun-if-changed=templates");
+//! ```
+//!
+//! The generated file is then pulled in with
+//! `include!(concat!(env!("OUT_DIR"), "/templates.rs"));`, and calls back into this module, so
+//! the crate needs vel as a normal dependency as well as a build one.
+
+use crate::{
+    ast::elements::{AttributeValues, Attributes, Document, Element},
+    ParsingError, VelInstance,
+};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fmt, fs, io,
+    path::Path,
+    sync::Arc,
+};
+
+pub use crate::rendering::escape_into;
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+    "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while", "abstract", "become", "box", "do", "final", "macro",
+    "override", "priv", "try", "typeof", "unsized", "virtual", "yield",
+];
+
+#[derive(Debug)]
+pub enum CodegenError {
+    Parsing(ParsingError),
+    /// A component used a variable that its call site never gave it, so it could never render
+    MissingInput {
+        component: String,
+        input: String,
+    },
+    /// Two inputs of a component turn into the same field name, like `post-title` and
+    /// `postTitle`
+    ConflictingInputs {
+        component: String,
+        field: String,
+    },
+    Io(io::Error),
+}
+
+impl fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodegenError::Parsing(error) => write!(f, "{error}"),
+            CodegenError::MissingInput { component, input } => {
+                write!(f, "`{component}` uses `{input}` without being given it")
+            }
+            CodegenError::ConflictingInputs { component, field } => write!(
+                f,
+                "more than one input of `{component}` would become the field `{field}`"
+            ),
+            CodegenError::Io(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for CodegenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CodegenError::Parsing(error) => Some(error),
+            CodegenError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParsingError> for CodegenError {
+    fn from(error: ParsingError) -> Self {
+        CodegenError::Parsing(error)
+    }
+}
+
+impl From<io::Error> for CodegenError {
+    fn from(error: io::Error) -> Self {
+        CodegenError::Io(error)
+    }
+}
+
+/// Turns any name into something usable as a Rust identifier. Words are split on anything that
+/// can't go in an identifier, and either capitalised for types or made snake case, so the
+/// generated code doesn't set off naming lints in the crate including it.
+fn identifier(name: &str, type_name: bool) -> String {
+    let words = name
+        .split(|char: char| !(char.is_alphanumeric() || char == '_'))
+        .filter(|word| !word.is_empty());
+
+    let mut identifier = match type_name {
+        true => words
+            .map(|word| {
+                let mut chars = word.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                    .unwrap_or_default()
+            })
+            .collect::<String>(),
+        false => words.map(snake_case).collect::<Vec<_>>().join("_"),
+    };
+
+    if identifier.is_empty() || identifier.starts_with(|char: char| char.is_numeric()) {
+        identifier.insert(0, '_');
+    }
+
+    match identifier.as_str() {
+        // These can't be raw identifiers
+        "_" | "crate" | "self" | "Self" | "super" => identifier + "_",
+        keyword if KEYWORDS.contains(&keyword) => format!("r#{identifier}"),
+        _ => identifier,
+    }
+}
+
+/// Lowercases a word, starting a new `_` separated word wherever a capital letter starts one, so
+/// `postTitle` becomes `post_title` and `HTMLBody` becomes `html_body`
+fn snake_case(word: &str) -> String {
+    let chars: Vec<char> = word.chars().collect();
+    let mut snake = String::with_capacity(word.len());
+
+    for (index, char) in chars.iter().enumerate() {
+        let previous = index.checked_sub(1).map(|index| chars[index]);
+        let next = chars.get(index + 1);
+
+        let starts_word = char.is_uppercase()
+            && previous.is_some_and(|previous| {
+                previous.is_lowercase()
+                    || previous.is_numeric()
+                    || (previous.is_uppercase() && next.is_some_and(|next| next.is_lowercase()))
+            });
+
+        if starts_word && !snake.ends_with('_') {
+            snake.push('_');
+        }
+        snake.extend(char.to_lowercase());
+    }
+
+    snake
+}
+
+/// A piece of an evaluated value, either known while generating or filled by one of the root
+/// component's inputs when rendering
+#[derive(Clone)]
+enum Part {
+    Text(String),
+    Input(String),
+}
+
+struct Scope<'a> {
+    component: &'a str,
+    /// The values of each variable, or [`None`] at the root, where they're the struct's fields
+    inputs: Option<HashMap<String, Vec<Part>>>,
+    call_site: Option<&'a Document>,
+    parent: Option<&'a Scope<'a>>,
+}
+
+impl Scope<'_> {
+    fn get(&self, name: &str) -> Result<Cow<'_, [Part]>, CodegenError> {
+        match &self.inputs {
+            None => Ok(Cow::Owned(vec![Part::Input(name.to_string())])),
+            Some(inputs) => inputs
+                .get(name)
+                .map(|parts| Cow::Borrowed(parts.as_slice()))
+                .ok_or_else(|| CodegenError::MissingInput {
+                    component: self.component.to_string(),
+                    input: name.to_string(),
+                }),
+        }
+    }
+
+    fn evaluate(&self, values: &[AttributeValues]) -> Result<Vec<Part>, CodegenError> {
+        let mut parts = vec![];
+
+        for value in values {
+            match value {
+                AttributeValues::Text(text) => parts.push(Part::Text(text.value.to_string())),
+                AttributeValues::Variable(variable) => {
+                    parts.extend(self.get(&variable.name)?.iter().cloned())
+                }
+            }
+        }
+
+        Ok(parts)
+    }
+}
+
+/// Writes the body of a render method, joining up as much of the static output as it can
+struct Generator<'a> {
+    body: String,
+    /// Static output that hasn't been written into the body yet
+    pending: String,
+    fields: &'a BTreeMap<&'a str, String>,
+}
+
+impl Generator<'_> {
+    fn flush(&mut self) {
+        if !self.pending.is_empty() {
+            self.body += &format!("        output.push_str({:?});\n", self.pending);
+            self.pending.clear();
+        }
+    }
+
+    fn push_escaped(&mut self, parts: &[Part]) {
+        for part in parts {
+            match part {
+                Part::Text(text) => escape_into(&mut self.pending, text),
+                Part::Input(input) => {
+                    self.flush();
+                    self.body += &format!(
+                        "        ::vel::codegen::escape_into(output, self.{});\n",
+                        self.fields[input.as_str()]
+                    );
+                }
+            }
+        }
+    }
+
+    fn attributes(&mut self, attributes: &Attributes, scope: &Scope) -> Result<(), CodegenError> {
+        for (key, values) in attributes {
+            self.pending.push(' ');
+            self.pending.push_str(key);
+
+            if !values.is_empty() {
+                self.pending.push_str("=\"");
+                self.push_escaped(&scope.evaluate(values)?);
+                self.pending.push('"');
+            }
+        }
+
+        Ok(())
+    }
+
+    fn children(&mut self, children: &[Arc<Element>], scope: &Scope) -> Result<(), CodegenError> {
+        children
+            .iter()
+            .try_for_each(|child| self.element(child, scope))
+    }
+
+    fn element(&mut self, element: &Element, scope: &Scope) -> Result<(), CodegenError> {
+        match element {
+            Element::Document(document) => {
+                let inputs = document
+                    .attributes
+                    .iter()
+                    .map(|(key, values)| Ok((key.to_string(), scope.evaluate(values)?)))
+                    .collect::<Result<_, CodegenError>>()?;
+
+                let component_scope = Scope {
+                    component: &document.name,
+                    inputs: Some(inputs),
+                    call_site: Some(document),
+                    parent: Some(scope),
+                };
+
+                self.children(&document.children, &component_scope)?;
+            }
+            Element::Node(node) => {
+                self.pending.push('<');
+                self.pending.push_str(&node.name);
+                self.attributes(&node.attributes, scope)?;
+                self.pending.push('>');
+                self.children(&node.children, scope)?;
+                self.pending.push_str("</");
+                self.pending.push_str(&node.name);
+                self.pending.push('>');
+            }
+            Element::Slot(slot) => {
+                let content = scope
+                    .call_site
+                    .and_then(|call_site| call_site.slot_content.get(&slot.name));
+
+                match (content, scope.parent) {
+                    (Some(content), Some(parent)) => self.children(content, parent)?,
+                    _ => self.children(&slot.children, scope)?,
+                }
+            }
+            Element::Text(text) => self.pending.push_str(&text.value),
+            Element::Variable(variable) => self.push_escaped(&scope.get(&variable.name)?),
+            Element::Void(void) => {
+                self.pending.push('<');
+                self.pending.push_str(&void.name);
+                self.attributes(&void.attributes, scope)?;
+                self.pending.push('>');
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn generate_component(
+    instance: &mut VelInstance,
+    component: &str,
+    code: &mut String,
+) -> Result<(), CodegenError> {
+    let dom = instance.components.parsed(component)?;
+
+    let mut fields = BTreeMap::new();
+    for input in dom.inputs() {
+        let field = identifier(input, false);
+
+        if fields.values().any(|existing| *existing == field) {
+            return Err(CodegenError::ConflictingInputs {
+                component: component.to_string(),
+                field,
+            });
+        }
+
+        fields.insert(input, field);
+    }
+
+    let mut generator = Generator {
+        body: String::new(),
+        pending: String::new(),
+        fields: &fields,
+    };
+
+    let scope = Scope {
+        component,
+        inputs: None,
+        call_site: None,
+        parent: None,
+    };
+
+    generator.children(&dom.tree.children, &scope)?;
+    generator.flush();
+
+    let name = identifier(component, true);
+    // Structs without any fields can't have a lifetime, as it'd go unused
+    let (declaration, implementation) = match fields.is_empty() {
+        true => (format!("pub struct {name};"), format!("impl {name}")),
+        false => (
+            format!(
+                "pub struct {name}<'a> {{\n{}}}",
+                fields
+                    .values()
+                    .map(|field| format!("    pub {field}: &'a str,\n"))
+                    .collect::<String>()
+            ),
+            format!("impl {name}<'_>"),
+        ),
+    };
+
+    *code += &format!(
+        "/// Generated from the `{component}` component.\n\
+         {declaration}\n\
+         \n\
+         {implementation} {{\n\
+         \x20   pub fn render_into(&self, output: &mut String) {{\n\
+         {body}\
+         \x20   }}\n\
+         \n\
+         \x20   pub fn render(&self) -> String {{\n\
+         \x20       let mut output = String::new();\n\
+         \x20       self.render_into(&mut output);\n\
+         \x20       output\n\
+         \x20   }}\n\
+         }}\n",
+        body = generator.body,
+    );
+
+    Ok(())
+}
+
+/// Generates Rust code with a struct, and a method to render it, for each of `components`.
+/// Components they use are compiled into them, so only the ones you render directly need to be
+/// listed.
+pub fn generate(instance: &mut VelInstance, components: &[&str]) -> Result<String, CodegenError> {
+    let mut code = String::from("// Generated by vel, don't edit this by hand!\n");
+
+    for component in components {
+        code.push('\n');
+        generate_component(instance, component, &mut code)?;
+    }
+
+    Ok(code)
+}
+
+/// Generates the code for `components` like [`generate`] and writes it out to `path`. The file
+/// is left alone if nothing changed, so cargo doesn't rebuild anything it doesn't need to.
+pub fn write(
+    instance: &mut VelInstance,
+    components: &[&str],
+    path: impl AsRef<Path>,
+) -> Result<(), CodegenError> {
+    let code = generate(instance, components)?;
+
+    if fs::read_to_string(&path).ok().as_deref() != Some(code.as_str()) {
+        fs::write(path, code)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,218 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! A lossless tree of a single template as it was written, down to its comments, quotes and
+//! whitespace, for tools that work on templates rather than render them, like [`crate::format`].
+//!
+//! Unlike parsing into a [`crate::ast::Dom`], components aren't inlined, so a template can be read
+//! without any of the components it uses. Every node has a [`Span`] into the template, and the
+//! nodes cover all of it between them.
+
+use crate::{
+    lexing::{offset_of, Lexer, RawAttribute, Token},
+    parsing::VOID_ELEMENTS,
+    ParsingError, ParsingErrorKind, Span,
+};
+
+#[derive(Debug, Clone)]
+pub struct Cst<'a> {
+    source: &'a str,
+    pub nodes: Vec<CstNode<'a>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum CstNode<'a> {
+    /// Text as it was written, with its escapes and whitespace
+    Text(&'a str, Span),
+    Variable(&'a str, Span),
+    /// What's between `<!--` and `-->`
+    Comment(&'a str, Span),
+    Element(CstElement<'a>),
+    /// An empty tag, which ends the template early, alongside everything after it
+    End(Span),
+}
+
+impl CstNode<'_> {
+    pub fn span(&self) -> Span {
+        match self {
+            CstNode::Text(_, span)
+            | CstNode::Variable(_, span)
+            | CstNode::Comment(_, span)
+            | CstNode::End(span) => *span,
+            CstNode::Element(element) => element.span(),
+        }
+    }
+}
+
+/// An element, component call or `<Slot>`
+#[derive(Debug, Clone)]
+pub struct CstElement<'a> {
+    pub tag: Tag<'a>,
+    pub children: Vec<CstNode<'a>>,
+    /// Where the closing tag is, which void elements don't have
+    pub close: Option<Span>,
+}
+
+impl CstElement<'_> {
+    pub fn span(&self) -> Span {
+        Span::new(
+            self.tag.span.start,
+            self.close.map_or(self.tag.span.end, |close| close.end),
+        )
+    }
+}
+
+/// An opening tag
+#[derive(Debug, Clone)]
+pub struct Tag<'a> {
+    pub name: &'a str,
+    pub attributes: Vec<CstAttribute<'a>>,
+    /// Whether it ends with `/>`
+    pub self_closing: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct CstAttribute<'a> {
+    pub key: &'a str,
+    /// The value between the quotes, with its variables and escapes, or [`None`] without one
+    pub value: Option<&'a str>,
+    /// The quote the value was written with, or [`None`] when it wasn't quoted
+    pub quote: Option<char>,
+    pub span: Span,
+}
+
+impl<'a> Cst<'a> {
+    /// Reads `source`, the template of `component`, failing on anything the parser would fail on
+    /// that doesn't need any other component to find.
+    pub fn parse(component: &str, source: &'a str) -> Result<Self, ParsingError> {
+        let error = |kind, span| ParsingError::new(component, kind, span);
+        let mut lexer = Lexer::new(source);
+        let mut nodes = vec![];
+        let mut open: Vec<CstElement> = vec![];
+
+        loop {
+            let (token, span) = lexer.next_spanned();
+
+            let node = match token {
+                Token::Text(raw) => CstNode::Text(raw, span),
+                Token::Variable(name) => CstNode::Variable(name, span),
+                Token::Comment(raw) => CstNode::Comment(raw, span),
+                Token::Open { name, attributes } => {
+                    let element = CstElement {
+                        tag: tag(source, name, attributes, span),
+                        children: vec![],
+                        close: None,
+                    };
+
+                    if !VOID_ELEMENTS.contains(&name) {
+                        open.push(element);
+                        continue;
+                    }
+
+                    CstNode::Element(element)
+                }
+                Token::Close(name) => match open.pop() {
+                    Some(mut element) if element.tag.name == name => {
+                        element.close = Some(span);
+                        CstNode::Element(element)
+                    }
+                    Some(element) => {
+                        return Err(error(
+                            ParsingErrorKind::MismatchedClosingTag {
+                                expected: element.tag.name.to_string(),
+                                found: name.to_string(),
+                            },
+                            span,
+                        ))
+                    }
+                    None => {
+                        return Err(error(
+                            ParsingErrorKind::UnexpectedClosingTag(name.to_string()),
+                            span,
+                        ))
+                    }
+                },
+                Token::End => {
+                    if let Some(element) = open.pop() {
+                        return Err(error(
+                            ParsingErrorKind::UnclosedElement(element.tag.name.to_string()),
+                            element.tag.span,
+                        ));
+                    }
+
+                    if span.start != span.end {
+                        nodes.push(CstNode::End(Span::new(span.start, source.len())));
+                    }
+
+                    return Ok(Cst { source, nodes });
+                }
+            };
+
+            match open.last_mut() {
+                Some(parent) => parent.children.push(node),
+                None => nodes.push(node),
+            }
+        }
+    }
+
+    /// The template it was read from
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+}
+
+fn tag<'a>(
+    source: &'a str,
+    name: &'a str,
+    attributes: Vec<RawAttribute<'a>>,
+    span: Span,
+) -> Tag<'a> {
+    let attributes: Vec<CstAttribute> = attributes
+        .into_iter()
+        .map(|(key, value)| {
+            let start = offset_of(source, key);
+            let key_end = start + key.len();
+            let mut after = source[key_end..span.end].chars();
+
+            // Values that were left empty, like `key= `, are read the same as having no value
+            let (value, quote, end) = match (after.next(), after.next()) {
+                (Some('='), Some(quote @ ('"' | '\''))) => {
+                    let value_end = offset_of(source, value) + value.len();
+                    // A quote that wasn't closed runs to the end of the template
+                    let end = match source[value_end..].starts_with(quote) {
+                        true => value_end + 1,
+                        false => value_end,
+                    };
+                    (Some(value), Some(quote), end)
+                }
+                (Some('='), Some(char)) if char != '>' && !char.is_whitespace() => {
+                    (Some(value), None, offset_of(source, value) + value.len())
+                }
+                _ => (None, None, key_end),
+            };
+
+            CstAttribute {
+                key,
+                value,
+                quote,
+                span: Span::new(start, end),
+            }
+        })
+        .collect();
+
+    // The slash has to come straight before the `>`, and not be part of an attribute
+    let slash = span.end.saturating_sub(2);
+    let self_closing = source[span.start..span.end].ends_with("/>")
+        && attributes
+            .last()
+            .is_none_or(|attribute| attribute.span.end <= slash);
+
+    Tag {
+        name,
+        attributes,
+        self_closing,
+        span,
+    }
+}
@@ -0,0 +1,273 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Formatting templates so they're indented the same way everywhere.
+//!
+//! Formatting never changes how a template renders. Block elements are put on lines of their own
+//! wherever whitespace next to them wouldn't be rendered anyway, and other whitespace is only
+//! changed where it already went over more than one line. Either way, it's re-indented to how
+//! deeply it's nested. Comments, quotes and the contents of `<pre>`, `<textarea>`, `<script>` and
+//! `<style>` are kept as they were.
+
+use crate::{
+    cst::{Cst, CstAttribute, CstElement, CstNode, Tag},
+    output::BLOCK_ELEMENTS,
+    ParsingError,
+};
+
+/// Elements whose whitespace matters, or that aren't HTML inside, which are left alone
+const VERBATIM_ELEMENTS: [&str; 4] = ["pre", "textarea", "script", "style"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// How many spaces each level is indented by
+    pub indent: usize,
+    /// How long a line can get before an opening tag's attributes are put on their own lines
+    pub width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            indent: 2,
+            width: 100,
+        }
+    }
+}
+
+/// Formats the template of `component`, which fails if the template can't be parsed.
+pub fn format(
+    component: &str,
+    source: &str,
+    options: &FormatOptions,
+) -> Result<String, ParsingError> {
+    let cst = Cst::parse(component, source)?;
+
+    let mut formatter = Formatter {
+        source,
+        options,
+        output: String::with_capacity(source.len()),
+    };
+    formatter.nodes(&cst.nodes, 0, None);
+
+    Ok(formatter.output)
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    options: &'a FormatOptions,
+    output: String,
+}
+
+impl Formatter<'_> {
+    fn indent(&mut self, depth: usize) {
+        self.output
+            .extend(std::iter::repeat_n(' ', depth * self.options.indent));
+    }
+
+    /// How many chars are on the line being written so far
+    fn column(&self) -> usize {
+        let line_start = self.output.rfind('\n').map_or(0, |index| index + 1);
+        self.output[line_start..].chars().count()
+    }
+
+    /// Writes the children of `parent`, or the whole template when there isn't one. Block
+    /// elements are put on their own lines, but only where whitespace is next to one, or at the
+    /// edges of a block element, since that's where HTML doesn't render it.
+    fn nodes(&mut self, nodes: &[CstNode], depth: usize, parent: Option<&str>) {
+        let blocks = nodes.iter().any(is_block);
+        // Whether what came before the next node is a block, with nothing before the template
+        let mut previous = parent.map(|name| BLOCK_ELEMENTS.contains(&name));
+        let mut gap = "";
+
+        for node in nodes {
+            let (leading, content, trailing) = match node {
+                CstNode::Text(raw, _) => split_whitespace(raw),
+                _ => ("", "", ""),
+            };
+
+            // Whitespace on its own is only ever a gap between the nodes either side of it
+            if let CstNode::Text(..) = node {
+                gap = leading;
+                if content.is_empty() {
+                    continue;
+                }
+            }
+
+            let block = is_block(node);
+            self.gap(
+                gap,
+                blocks && previous.is_some_and(|previous| previous || block),
+                depth,
+                false,
+            );
+
+            match node {
+                CstNode::Text(..) => self.text(content, depth, false),
+                CstNode::Variable(name, _) => {
+                    self.output.push('{');
+                    self.output.push_str(name);
+                    self.output.push('}');
+                }
+                CstNode::Comment(_, span) | CstNode::End(span) => {
+                    self.output.push_str(&self.source[span.start..span.end]);
+                }
+                CstNode::Element(element) => self.element(element, depth),
+            }
+
+            previous = Some(block);
+            gap = trailing;
+        }
+
+        let parent_block = parent.is_some_and(|name| BLOCK_ELEMENTS.contains(&name));
+        let breaks = parent.is_some() && (parent_block || previous == Some(true));
+        self.gap(gap, blocks && breaks, depth, true);
+    }
+
+    /// Writes the whitespace between two nodes, as a line break when `breaks`. The last gap is
+    /// followed by the parent's closing tag, so it's indented to the parent instead.
+    fn gap(&mut self, whitespace: &str, breaks: bool, depth: usize, last: bool) {
+        if !breaks {
+            return self.text(whitespace, depth, last);
+        }
+
+        // Blank lines are kept, but only one in a row
+        match whitespace.matches('\n').count() > 1 {
+            true => self.output.push_str("\n\n"),
+            false => self.output.push('\n'),
+        }
+
+        match last {
+            true => self.indent(depth.saturating_sub(1)),
+            false => self.indent(depth),
+        }
+    }
+
+    fn text(&mut self, raw: &str, depth: usize, last: bool) {
+        let mut rest = raw;
+
+        while !rest.is_empty() {
+            let whitespace = rest.len() - rest.trim_start().len();
+
+            if whitespace == 0 {
+                // Escaped whitespace is part of the word it's in
+                let mut escaped = false;
+                let word = rest
+                    .char_indices()
+                    .find(|(_, char)| {
+                        let end = !escaped && char.is_whitespace();
+                        escaped = !escaped && *char == '\\';
+                        end
+                    })
+                    .map_or(rest.len(), |(index, _)| index);
+
+                self.output.push_str(&rest[..word]);
+                rest = &rest[word..];
+                continue;
+            }
+
+            let run = &rest[..whitespace];
+            rest = &rest[whitespace..];
+
+            match run.matches('\n').count() {
+                0 => self.output.push_str(run),
+                lines => {
+                    // Blank lines are kept, but only one in a row
+                    self.output.push_str(if lines > 1 { "\n\n" } else { "\n" });
+
+                    match rest.is_empty() && last {
+                        true => self.indent(depth.saturating_sub(1)),
+                        false => self.indent(depth),
+                    }
+                }
+            }
+        }
+    }
+
+    fn element(&mut self, element: &CstElement, depth: usize) {
+        self.tag(&element.tag, depth);
+
+        let Some(close) = element.close else {
+            return;
+        };
+
+        match VERBATIM_ELEMENTS.contains(&element.tag.name) {
+            true => self
+                .output
+                .push_str(&self.source[element.tag.span.end..close.start]),
+            false => self.nodes(&element.children, depth + 1, Some(element.tag.name)),
+        }
+
+        self.output.push_str("</");
+        self.output.push_str(element.tag.name);
+        self.output.push('>');
+    }
+
+    fn tag(&mut self, tag: &Tag, depth: usize) {
+        let attributes: Vec<String> = tag.attributes.iter().map(attribute).collect();
+        let ending = match (tag.self_closing, attributes.is_empty()) {
+            (true, true) => "/>",
+            (true, false) => " />",
+            (false, _) => ">",
+        };
+
+        let length = 1
+            + tag.name.chars().count()
+            + attributes
+                .iter()
+                .map(|attribute| attribute.chars().count() + 1)
+                .sum::<usize>()
+            + ending.len();
+        let fits = self.column() + length <= self.options.width;
+
+        self.output.push('<');
+        self.output.push_str(tag.name);
+
+        if fits || attributes.is_empty() {
+            for attribute in attributes {
+                self.output.push(' ');
+                self.output.push_str(&attribute);
+            }
+
+            self.output.push_str(ending);
+            return;
+        }
+
+        for attribute in attributes {
+            self.output.push('\n');
+            self.indent(depth + 1);
+            self.output.push_str(&attribute);
+        }
+
+        self.output.push('\n');
+        self.indent(depth);
+        self.output.push_str(ending.trim_start());
+    }
+}
+
+fn is_block(node: &CstNode) -> bool {
+    matches!(node, CstNode::Element(element) if BLOCK_ELEMENTS.contains(&element.tag.name))
+}
+
+/// Splits text into its leading whitespace, what's between, and its trailing whitespace. An
+/// escaped space at the end stays with what's before it.
+fn split_whitespace(raw: &str) -> (&str, &str, &str) {
+    let start = raw.len() - raw.trim_start().len();
+    let mut end = raw.trim_end().len().max(start);
+
+    let backslashes = raw[start..end].len() - raw[start..end].trim_end_matches('\\').len();
+    if backslashes % 2 == 1 {
+        end += raw[end..].chars().next().map_or(0, char::len_utf8);
+    }
+
+    (&raw[..start], &raw[start..end], &raw[end..])
+}
+
+fn attribute(attribute: &CstAttribute) -> String {
+    match (attribute.value, attribute.quote) {
+        (Some(value), Some(quote)) => format!("{}={quote}{value}{quote}", attribute.key),
+        (Some(value), None) => format!("{}={value}", attribute.key),
+        (None, _) => attribute.key.to_string(),
+    }
+}
@@ -0,0 +1,140 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Which components use which, as worked out from their parsed trees.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+/// The components an instance knows about and the components each one uses directly.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ComponentGraph {
+    dependencies: BTreeMap<String, BTreeSet<String>>,
+    dependants: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl ComponentGraph {
+    /// Adds a component and the components it uses directly.
+    pub(crate) fn insert<'a>(
+        &mut self,
+        component: &str,
+        dependencies: impl IntoIterator<Item = &'a str>,
+    ) {
+        self.dependencies.entry(component.to_string()).or_default();
+        self.dependants.entry(component.to_string()).or_default();
+
+        for dependency in dependencies {
+            self.dependencies.entry(dependency.to_string()).or_default();
+            self.dependencies
+                .get_mut(component)
+                .expect("the component was added above")
+                .insert(dependency.to_string());
+            self.dependants
+                .entry(dependency.to_string())
+                .or_default()
+                .insert(component.to_string());
+        }
+    }
+
+    /// Every component in the graph, in alphabetical order.
+    pub fn components(&self) -> impl Iterator<Item = &str> {
+        self.dependencies.keys().map(String::as_str)
+    }
+
+    /// The components `component` uses directly.
+    pub fn dependencies(&self, component: &str) -> impl Iterator<Item = &str> {
+        self.dependencies
+            .get(component)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    /// The components that use `component` directly.
+    pub fn dependants(&self, component: &str) -> impl Iterator<Item = &str> {
+        self.dependants
+            .get(component)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    /// Every component used by any of `roots`, however indirectly, including the roots themselves.
+    pub fn reachable_from<'a>(
+        &'a self,
+        roots: impl IntoIterator<Item = &'a str>,
+    ) -> BTreeSet<&'a str> {
+        let mut reachable = BTreeSet::new();
+        let mut queue: Vec<&str> = roots.into_iter().collect();
+
+        while let Some(component) = queue.pop() {
+            if reachable.insert(component) {
+                queue.extend(self.dependencies(component));
+            }
+        }
+
+        reachable
+    }
+
+    /// Every component ordered so that each one comes after all of the components it uses.
+    /// Components can't use themselves, even indirectly, as the parser rejects it.
+    pub fn topological_order(&self) -> Vec<&str> {
+        let mut remaining: BTreeMap<&str, usize> = self
+            .dependencies
+            .iter()
+            .map(|(component, dependencies)| (component.as_str(), dependencies.len()))
+            .collect();
+
+        let mut ready: Vec<&str> = remaining
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(component, _)| *component)
+            .rev()
+            .collect();
+
+        let mut order = Vec::with_capacity(remaining.len());
+
+        while let Some(component) = ready.pop() {
+            order.push(component);
+
+            for dependant in self.dependants(component) {
+                let count = remaining
+                    .get_mut(dependant)
+                    .expect("every dependant is also in the graph");
+                *count -= 1;
+
+                if *count == 0 {
+                    ready.push(dependant);
+                }
+            }
+        }
+
+        order
+    }
+
+    /// Exports the graph in the Graphviz DOT format, with an edge from each component to the
+    /// ones it uses.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph components {\n");
+
+        for (component, dependencies) in &self.dependencies {
+            if dependencies.is_empty() {
+                let _ = writeln!(dot, "    {};", dot_id(component));
+            }
+
+            for dependency in dependencies {
+                let _ = writeln!(dot, "    {} -> {};", dot_id(component), dot_id(dependency));
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn dot_id(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
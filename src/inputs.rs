@@ -0,0 +1,409 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Reading the inputs to render a component with from JSON, TOML or `key=value` pairs, so they
+//! can be kept in a file rather than written in Rust.
+//!
+//! Inputs are all strings, so numbers and booleans are written as they'd be in the file. Nested
+//! tables and objects have their keys joined with a `.`, and arrays use each value's index, so
+//! `{"user": {"name": "Vel"}}` gives `{user.name}`.
+
+use crate::{
+    json::{Number, Value},
+    Span,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::Path,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputsError {
+    /// The file couldn't be read as JSON or TOML, with where it went wrong, both counting from 1
+    Syntax {
+        line: usize,
+        column: usize,
+        message: &'static str,
+    },
+    /// A value that can't be turned into an input, like `null`
+    Unsupported(String),
+    /// A `key=value` pair without the `=`
+    MissingEquals(String),
+    /// A file that isn't `.json` or `.toml`
+    UnknownFormat(String),
+}
+
+impl fmt::Display for InputsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputsError::Syntax {
+                line,
+                column,
+                message,
+            } => write!(f, "{message} at {line}:{column}"),
+            InputsError::Unsupported(key) => write!(f, "`{key}` can't be used as an input"),
+            InputsError::MissingEquals(pair) => write!(f, "`{pair}` should look like `key=value`"),
+            InputsError::UnknownFormat(path) => {
+                write!(f, "`{path}` should be a `.json` or `.toml` file")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InputsError {}
+
+fn syntax_error(source: &str, position: usize, message: &'static str) -> InputsError {
+    let (line, column) = Span::new(position, position).line_column(source);
+    InputsError::Syntax {
+        line,
+        column,
+        message,
+    }
+}
+
+/// Reads inputs from a JSON object.
+pub fn from_json(source: &str) -> Result<HashMap<String, String>, InputsError> {
+    flatten(parse_json(source)?)
+}
+
+/// Reads a JSON object, keeping its nesting
+pub(crate) fn parse_json(source: &str) -> Result<BTreeMap<String, Value>, InputsError> {
+    let value = crate::json::parse(source)
+        .map_err(|(position, message)| syntax_error(source, position, message))?;
+
+    match value {
+        Value::Object(entries) => Ok(entries),
+        _ => Err(syntax_error(source, 0, "expected an object")),
+    }
+}
+
+/// Turns the entries of an object into inputs, joining the keys of nested ones with a `.`
+pub(crate) fn flatten(
+    entries: BTreeMap<String, Value>,
+) -> Result<HashMap<String, String>, InputsError> {
+    let mut inputs = HashMap::new();
+    for (key, value) in entries {
+        flatten_value(key, value, &mut inputs)?;
+    }
+
+    Ok(inputs)
+}
+
+fn flatten_value(
+    key: String,
+    value: Value,
+    inputs: &mut HashMap<String, String>,
+) -> Result<(), InputsError> {
+    let value = match value {
+        Value::Null => return Err(InputsError::Unsupported(key)),
+        Value::Bool(bool) => bool.to_string(),
+        Value::Number(number) => number.as_str().to_string(),
+        Value::String(string) => string,
+        Value::Array(values) => {
+            for (index, value) in values.into_iter().enumerate() {
+                flatten_value(format!("{key}.{index}"), value, inputs)?;
+            }
+            return Ok(());
+        }
+        Value::Object(entries) => {
+            for (inner, value) in entries {
+                flatten_value(format!("{key}.{inner}"), value, inputs)?;
+            }
+            return Ok(());
+        }
+    };
+
+    inputs.insert(key, value);
+    Ok(())
+}
+
+/// Reads inputs from TOML. Only keys, `[tables]`, strings, numbers, booleans and arrays that fit
+/// on one line are understood.
+pub fn from_toml(source: &str) -> Result<HashMap<String, String>, InputsError> {
+    flatten(parse_toml(source)?)
+}
+
+/// Reads TOML, keeping its tables nested
+pub(crate) fn parse_toml(source: &str) -> Result<BTreeMap<String, Value>, InputsError> {
+    let mut root = BTreeMap::new();
+    let mut table = vec![];
+    let mut line_start = 0;
+
+    for line in source.split_inclusive('\n') {
+        let mut parser = TomlParser {
+            source,
+            position: line_start,
+            end: line_start + line.trim_end_matches(['\n', '\r']).len(),
+        };
+        line_start += line.len();
+
+        parser.whitespace();
+        match parser.peek() {
+            None | Some('#') => continue,
+            Some('[') => {
+                parser.position += 1;
+                parser.whitespace();
+                table = parser.key()?;
+                parser.whitespace();
+                parser.expect(']', "expected a `]`")?;
+            }
+            Some(_) => {
+                let start = parser.position;
+                let mut key = table.clone();
+                key.extend(parser.key()?);
+
+                parser.whitespace();
+                parser.expect('=', "expected a `=`")?;
+                parser.whitespace();
+                let value = parser.value()?;
+
+                insert(&mut root, &key, value).map_err(|message| {
+                    parser.position = start;
+                    parser.error(message)
+                })?;
+            }
+        }
+
+        parser.whitespace();
+        match parser.peek() {
+            None | Some('#') => (),
+            Some(_) => return Err(parser.error("expected the end of the line")),
+        }
+    }
+
+    Ok(root)
+}
+
+/// Puts `value` at `key` under `root`, making the tables on the way
+fn insert(
+    root: &mut BTreeMap<String, Value>,
+    key: &[String],
+    value: Value,
+) -> Result<(), &'static str> {
+    let (last, tables) = key.split_last().ok_or("expected a key")?;
+    let mut table = root;
+
+    for part in tables {
+        let entry = table
+            .entry(part.clone())
+            .or_insert_with(|| Value::Object(BTreeMap::new()));
+        let Value::Object(inner) = entry else {
+            return Err("the key already has a value");
+        };
+        table = inner;
+    }
+
+    match table.contains_key(last) {
+        true => Err("the key already has a value"),
+        false => {
+            table.insert(last.clone(), value);
+            Ok(())
+        }
+    }
+}
+
+/// Reads a line of TOML at a time
+struct TomlParser<'a> {
+    source: &'a str,
+    position: usize,
+    /// Where the line ends
+    end: usize,
+}
+
+impl TomlParser<'_> {
+    fn error(&self, message: &'static str) -> InputsError {
+        syntax_error(self.source, self.position, message)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.source[self.position..self.end].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let char = self.peek()?;
+        self.position += char.len_utf8();
+        Some(char)
+    }
+
+    fn whitespace(&mut self) {
+        while let Some(' ' | '\t') = self.peek() {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char, message: &'static str) -> Result<(), InputsError> {
+        match self.peek() {
+            Some(char) if char == expected => {
+                self.position += 1;
+                Ok(())
+            }
+            _ => Err(self.error(message)),
+        }
+    }
+
+    /// Reads a key, split into the parts of dotted keys
+    fn key(&mut self) -> Result<Vec<String>, InputsError> {
+        let mut parts = vec![];
+
+        loop {
+            self.whitespace();
+
+            let part = match self.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let start = self.position;
+                    while let Some('A'..='Z' | 'a'..='z' | '0'..='9' | '_' | '-') = self.peek() {
+                        self.position += 1;
+                    }
+
+                    if start == self.position {
+                        return Err(self.error("expected a key"));
+                    }
+                    self.source[start..self.position].to_string()
+                }
+            };
+            parts.push(part);
+
+            self.whitespace();
+            if self.peek() != Some('.') {
+                return Ok(parts);
+            }
+            self.position += 1;
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, InputsError> {
+        let value = match self.peek() {
+            Some('"') if self.source[self.position..].starts_with("\"\"\"") => {
+                return Err(self.error("multi-line strings aren't supported"))
+            }
+            Some('"') => Value::String(self.basic_string()?),
+            Some('\'') => Value::String(self.literal_string()?),
+            Some('[') => {
+                self.position += 1;
+                let mut values = vec![];
+
+                loop {
+                    self.whitespace();
+                    if self.peek() == Some(']') {
+                        self.position += 1;
+                        return Ok(Value::Array(values));
+                    }
+
+                    values.push(self.value()?);
+
+                    self.whitespace();
+                    match self.next() {
+                        Some(',') => (),
+                        Some(']') => return Ok(Value::Array(values)),
+                        _ => return Err(self.error("expected a `,` or `]`")),
+                    }
+                }
+            }
+            Some('{') => return Err(self.error("inline tables aren't supported")),
+            _ => {
+                let start = self.position;
+                while let Some(char) = self.peek() {
+                    if char.is_whitespace() || matches!(char, ',' | ']' | '#') {
+                        break;
+                    }
+                    self.position += char.len_utf8();
+                }
+
+                let value = self.source[start..self.position].replace('_', "");
+                match value.as_str() {
+                    "true" => Value::Bool(true),
+                    "false" => Value::Bool(false),
+                    _ => match Number::parse(&value) {
+                        Some(number) => Value::Number(number),
+                        None => {
+                            self.position = start;
+                            return Err(self.error("expected a value"));
+                        }
+                    },
+                }
+            }
+        };
+
+        Ok(value)
+    }
+
+    fn literal_string(&mut self) -> Result<String, InputsError> {
+        self.position += 1;
+        let start = self.position;
+
+        loop {
+            match self.next() {
+                Some('\'') => return Ok(self.source[start..self.position - 1].to_string()),
+                Some(_) => (),
+                None => return Err(self.error("the string is never closed")),
+            }
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, InputsError> {
+        let mut string = String::new();
+        self.position += 1;
+
+        loop {
+            match self.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => {
+                    let escaped = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some(kind @ ('u' | 'U')) => {
+                            let length = if kind == 'u' { 4 } else { 8 };
+                            let digits = self
+                                .source
+                                .get(self.position..self.position + length)
+                                .filter(|_| self.position + length <= self.end)
+                                .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.error("invalid unicode escape"))?;
+                            self.position += length;
+                            digits
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    string.push(escaped);
+                }
+                Some(char) => string.push(char),
+                None => return Err(self.error("the string is never closed")),
+            }
+        }
+    }
+}
+
+/// Reads a `key=value` pair, like one given on the command line.
+pub fn from_pair(pair: &str) -> Result<(String, String), InputsError> {
+    pair.split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| InputsError::MissingEquals(pair.to_string()))
+}
+
+/// Reads inputs from `source`, as JSON or TOML depending on `path`'s extension.
+pub fn from_file_contents(
+    path: &Path,
+    source: &str,
+) -> Result<HashMap<String, String>, InputsError> {
+    flatten(parse_file_contents(path, source)?)
+}
+
+/// Reads `source` as JSON or TOML depending on `path`'s extension, keeping its nesting
+pub(crate) fn parse_file_contents(
+    path: &Path,
+    source: &str,
+) -> Result<BTreeMap<String, Value>, InputsError> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => parse_json(source),
+        Some("toml") => parse_toml(source),
+        _ => Err(InputsError::UnknownFormat(path.display().to_string())),
+    }
+}
@@ -0,0 +1,355 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Just enough JSON for the crate's debugging output, input files and language server, so we don't
+//! need to pull in serde.
+
+use std::{collections::BTreeMap, fmt};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(String),
+    Array(Vec<Value>),
+    Object(BTreeMap<String, Value>),
+}
+
+impl Value {
+    /// Follows `path` through nested objects
+    pub fn get(&self, path: &[&str]) -> Option<&Value> {
+        path.iter().try_fold(self, |value, key| match value {
+            Value::Object(entries) => entries.get(*key),
+            _ => None,
+        })
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Number(number) => number.as_f64(),
+            _ => None,
+        }
+    }
+}
+
+/// A number as it was written, so integers too big for an [`f64`] come back out unrounded
+#[derive(Debug, Clone, PartialEq)]
+pub struct Number(String);
+
+impl Number {
+    /// Reads a number, keeping its text as long as it's one
+    pub(crate) fn parse(text: &str) -> Option<Self> {
+        text.parse::<f64>().ok().map(|_| Number(text.to_string()))
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        self.0.parse().ok()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<f64> for Number {
+    /// Numbers that aren't finite can't be written in JSON, so they're written as `null`
+    fn from(number: f64) -> Self {
+        match number.is_finite() {
+            true => Number(number.to_string()),
+            false => Number("null".to_string()),
+        }
+    }
+}
+
+impl From<usize> for Number {
+    fn from(number: usize) -> Self {
+        Number(number.to_string())
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(string: &str) -> Self {
+        Value::String(string.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(string: String) -> Self {
+        Value::String(string)
+    }
+}
+
+impl<const N: usize> From<[(&str, Value); N]> for Value {
+    fn from(entries: [(&str, Value); N]) -> Self {
+        Value::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, string: &str) -> fmt::Result {
+    f.write_str("\"")?;
+
+    for char in string.chars() {
+        match char {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            char if char.is_control() => write!(f, "\\u{:04x}", char as u32)?,
+            char => write!(f, "{char}")?,
+        }
+    }
+
+    f.write_str("\"")
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => f.write_str("null"),
+            Value::Bool(bool) => write!(f, "{bool}"),
+            Value::Number(number) => write!(f, "{number}"),
+            Value::String(string) => write_string(f, string),
+            Value::Array(values) => {
+                f.write_str("[")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index != 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                f.write_str("]")
+            }
+            Value::Object(entries) => {
+                f.write_str("{")?;
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index != 0 {
+                        f.write_str(",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+/// Where parsing went wrong, as a byte offset into the JSON, and what was wrong there
+pub(crate) type ParseError = (usize, &'static str);
+
+/// Parses a single JSON value, which can have whitespace around it but nothing else.
+pub(crate) fn parse(source: &str) -> Result<Value, ParseError> {
+    let mut parser = Parser {
+        source,
+        position: 0,
+    };
+    let value = parser.value()?;
+
+    parser.whitespace();
+    match parser.position == source.len() {
+        true => Ok(value),
+        false => Err((parser.position, "expected the end of the JSON")),
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.source[self.position..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let char = self.peek()?;
+        self.position += char.len_utf8();
+        Some(char)
+    }
+
+    fn whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char, message: &'static str) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(char) if char == expected => {
+                self.position += char.len_utf8();
+                Ok(())
+            }
+            _ => Err((self.position, message)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, ParseError> {
+        self.whitespace();
+
+        match self.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('-' | '0'..='9') => self.number(),
+            Some(_) => {
+                for (word, value) in [
+                    ("true", Value::Bool(true)),
+                    ("false", Value::Bool(false)),
+                    ("null", Value::Null),
+                ] {
+                    if self.source[self.position..].starts_with(word) {
+                        self.position += word.len();
+                        return Ok(value);
+                    }
+                }
+
+                Err((self.position, "expected a value"))
+            }
+            None => Err((self.position, "expected a value")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, ParseError> {
+        let mut entries = BTreeMap::new();
+        self.position += 1;
+        self.whitespace();
+
+        if self.peek() == Some('}') {
+            self.position += 1;
+            return Ok(Value::Object(entries));
+        }
+
+        loop {
+            self.whitespace();
+            if self.peek() != Some('"') {
+                return Err((self.position, "expected a key"));
+            }
+            let key = self.string()?;
+
+            self.whitespace();
+            self.expect(':', "expected a `:`")?;
+            entries.insert(key, self.value()?);
+            self.whitespace();
+
+            match self.next() {
+                Some(',') => (),
+                Some('}') => return Ok(Value::Object(entries)),
+                _ => return Err((self.position.saturating_sub(1), "expected a `,` or `}`")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, ParseError> {
+        let mut values = vec![];
+        self.position += 1;
+        self.whitespace();
+
+        if self.peek() == Some(']') {
+            self.position += 1;
+            return Ok(Value::Array(values));
+        }
+
+        loop {
+            values.push(self.value()?);
+            self.whitespace();
+
+            match self.next() {
+                Some(',') => (),
+                Some(']') => return Ok(Value::Array(values)),
+                _ => return Err((self.position.saturating_sub(1), "expected a `,` or `]`")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, ParseError> {
+        let start = self.position;
+
+        while let Some('-' | '+' | '.' | 'e' | 'E' | '0'..='9') = self.peek() {
+            self.position += 1;
+        }
+
+        Number::parse(&self.source[start..self.position])
+            .map(Value::Number)
+            .ok_or((start, "invalid number"))
+    }
+
+    fn hex_escape(&mut self) -> Result<u32, ParseError> {
+        let start = self.position;
+        let digits = self
+            .source
+            .get(start..start + 4)
+            .ok_or((start, "expected 4 hex digits"))?;
+
+        self.position += 4;
+        u32::from_str_radix(digits, 16).map_err(|_| (start, "expected 4 hex digits"))
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        let start = self.position;
+        let mut string = String::new();
+        self.position += 1;
+
+        loop {
+            let escape = self.position;
+
+            match self.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => match self.next() {
+                    Some('"') => string.push('"'),
+                    Some('\\') => string.push('\\'),
+                    Some('/') => string.push('/'),
+                    Some('b') => string.push('\u{8}'),
+                    Some('f') => string.push('\u{c}'),
+                    Some('n') => string.push('\n'),
+                    Some('r') => string.push('\r'),
+                    Some('t') => string.push('\t'),
+                    Some('u') => {
+                        let mut code = self.hex_escape()?;
+
+                        // Chars outside of the basic plane are written as a surrogate pair
+                        if (0xD800..0xDC00).contains(&code)
+                            && self.source[self.position..].starts_with("\\u")
+                        {
+                            self.position += 2;
+                            let low = self.hex_escape()?;
+                            code = 0x10000
+                                + ((code - 0xD800) << 10)
+                                + (low.wrapping_sub(0xDC00) & 0x3FF);
+                        }
+
+                        string
+                            .push(char::from_u32(code).ok_or((escape, "invalid unicode escape"))?);
+                    }
+                    _ => return Err((escape, "invalid escape")),
+                },
+                Some(char) if char.is_control() => {
+                    return Err((escape, "control characters have to be escaped"))
+                }
+                Some(char) => string.push(char),
+                None => return Err((start, "the string is never closed")),
+            }
+        }
+    }
+}
@@ -0,0 +1,356 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Splitting a template into tokens straight from the `&str`, without copying any of it.
+
+use crate::parsing::Span;
+use std::io::{self, Read};
+
+/// The raw `key` and `value` of an attribute, as written between the quotes. Values can still
+/// have `{variables}` and `\` escapes in them, and are empty for attributes without one.
+pub(crate) type RawAttribute<'a> = (&'a str, &'a str);
+
+/// Where `slice`, which has to have been taken from `source`, starts in it
+pub(crate) fn offset_of(source: &str, slice: &str) -> usize {
+    slice.as_ptr() as usize - source.as_ptr() as usize
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Token<'a> {
+    /// Text as it was written, with any `\` escapes still in it
+    Text(&'a str),
+    Variable(&'a str),
+    Open {
+        name: &'a str,
+        attributes: Vec<RawAttribute<'a>>,
+    },
+    Close(&'a str),
+    /// What's between `<!--` and `-->`, which is left out of what's rendered
+    Comment(&'a str),
+    /// The template has run out. An empty tag, like `<>`, also ends it early.
+    End,
+}
+
+pub(crate) struct Lexer<'a> {
+    source: &'a str,
+    position: usize,
+    /// Where to stop lexing, so a part of a template can be lexed with the offsets of the whole
+    end: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Self::within(source, Span::new(0, source.len()))
+    }
+
+    /// Lexes only the part of `source` within `span`
+    pub fn within(source: &'a str, span: Span) -> Self {
+        Self {
+            source,
+            position: span.start,
+            end: span.end,
+        }
+    }
+
+    /// The byte offset of the next token
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    /// Gets the next token alongside where it was in the template
+    pub fn next_spanned(&mut self) -> (Token<'a>, Span) {
+        let start = self.position;
+        let token = self.next_token();
+        (token, Span::new(start, self.position))
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.source[self.position..self.end]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let char = self.peek()?;
+        self.position += char.len_utf8();
+        Some(char)
+    }
+
+    /// Moves past everything up to and including the first char matching `end`, returning what
+    /// came before it and the char itself
+    fn take_until(&mut self, end: impl Fn(char) -> bool) -> (&'a str, Option<char>) {
+        let rest = self.rest();
+
+        match rest.char_indices().find(|(_, char)| end(*char)) {
+            Some((index, char)) => {
+                self.position += index + char.len_utf8();
+                (&rest[..index], Some(char))
+            }
+            None => {
+                self.position = self.end;
+                (rest, None)
+            }
+        }
+    }
+
+    pub fn next_token(&mut self) -> Token<'a> {
+        let start = self.position;
+
+        match self.next_char() {
+            None => Token::End,
+            Some('{') => Token::Variable(self.take_until(|char| char == '}').0),
+            Some('<') if self.rest().starts_with("!--") => {
+                self.position += "!--".len();
+                self.comment()
+            }
+            Some('<') => self.tag(),
+            Some(_) => {
+                let mut escaped = self.source[start..].starts_with('\\');
+
+                while let Some(char) = self.peek() {
+                    match char {
+                        _ if escaped => escaped = false,
+                        '<' | '{' => break,
+                        '\\' => escaped = true,
+                        _ => (),
+                    }
+
+                    self.position += char.len_utf8();
+                }
+
+                Token::Text(&self.source[start..self.position])
+            }
+        }
+    }
+
+    fn comment(&mut self) -> Token<'a> {
+        let rest = self.rest();
+
+        match rest.find("-->") {
+            Some(index) => {
+                self.position += index + "-->".len();
+                Token::Comment(&rest[..index])
+            }
+            // Like any other tag, one that isn't closed runs to the end of the template
+            None => {
+                self.position = self.end;
+                Token::Comment(rest)
+            }
+        }
+    }
+
+    fn tag(&mut self) -> Token<'a> {
+        let (mut name, end) = self.take_until(|char| char == '>' || char.is_whitespace());
+
+        // Self closing void elements, like `<br/>`
+        if name.len() > 1 && name.ends_with('/') {
+            name = &name[..name.len() - 1];
+        }
+
+        let attributes = match end {
+            Some('>') => vec![],
+            _ => self.attributes(),
+        };
+
+        if name.is_empty() {
+            // Nothing after it gets lexed, but where it ended is kept for the reader
+            self.end = self.position;
+            return Token::End;
+        }
+
+        match name.strip_prefix('/') {
+            Some(name) => Token::Close(name),
+            None => Token::Open { name, attributes },
+        }
+    }
+
+    /// Lexes attributes up to and including the closing `>` of a tag. Values may be quoted with
+    /// either `"` or `'`, in which case they can contain whitespace and `>`.
+    fn attributes(&mut self) -> Vec<RawAttribute<'a>> {
+        let mut attributes = vec![];
+        let mut key_start = None;
+
+        loop {
+            let start = self.position;
+
+            let Some(char) = self.next_char() else {
+                break;
+            };
+
+            match char {
+                '>' => break,
+                '=' if key_start.is_some() => {
+                    let key = &self.source[key_start.take().unwrap_or(start)..start];
+
+                    match self.next_char() {
+                        Some(quote @ ('"' | '\'')) => {
+                            attributes.push((key, self.take_until(|char| char == quote).0));
+                        }
+                        Some('>') => {
+                            attributes.push((key, ""));
+                            return attributes;
+                        }
+                        Some(char) if !char.is_whitespace() => {
+                            self.position -= char.len_utf8();
+                            let (value, end) =
+                                self.take_until(|char| char == '>' || char.is_whitespace());
+                            attributes.push((key, value));
+
+                            if end == Some('>') {
+                                return attributes;
+                            }
+                        }
+                        _ => attributes.push((key, "")),
+                    }
+                }
+                // A trailing slash only marks the tag as self closing
+                '/' if key_start.is_none() => (),
+                char if char.is_whitespace() => {
+                    if let Some(key_start) = key_start.take() {
+                        attributes.push((&self.source[key_start..start], ""));
+                    }
+                }
+                _ => {
+                    key_start.get_or_insert(start);
+                }
+            }
+        }
+
+        if let Some(key_start) = key_start {
+            let end = match self.source[..self.position].ends_with('>') {
+                true => self.position - 1,
+                false => self.position,
+            };
+            attributes.push((&self.source[key_start..end], ""));
+        }
+
+        attributes
+    }
+}
+
+/// Pushes text with its `\` escapes taken out
+pub(crate) fn unescape_into(output: &mut String, raw: &str) {
+    let mut chars = raw.chars();
+
+    while let Some(char) = chars.next() {
+        match char {
+            '\\' => output.extend(chars.next()),
+            char => output.push(char),
+        }
+    }
+}
+
+/// How much is read from a reader at a time
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Lexes a template as it's read, only holding onto the token being lexed and whatever was read
+/// past it.
+pub(crate) struct ReadLexer<R> {
+    reader: R,
+    buffer: String,
+    /// Bytes that have been read but don't make up a whole char yet
+    partial: Vec<u8>,
+    /// How far into the template the start of the buffer is
+    offset: usize,
+    finished: bool,
+}
+
+impl<R: Read> ReadLexer<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: String::new(),
+            partial: vec![],
+            offset: 0,
+            finished: false,
+        }
+    }
+
+    /// Reads another chunk onto the end of the buffer
+    fn fill(&mut self) -> io::Result<()> {
+        let mut chunk = [0; CHUNK_SIZE];
+
+        let read = loop {
+            match self.reader.read(&mut chunk) {
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                read => break read?,
+            }
+        };
+
+        if read == 0 {
+            self.finished = true;
+
+            return match self.partial.is_empty() {
+                true => Ok(()),
+                false => Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "the template ended part way through a character",
+                )),
+            };
+        }
+
+        self.partial.extend_from_slice(&chunk[..read]);
+
+        let valid = match std::str::from_utf8(&self.partial) {
+            Ok(_) => self.partial.len(),
+            // The rest of the char just hasn't been read yet
+            Err(error) if error.error_len().is_none() => error.valid_up_to(),
+            Err(error) => return Err(io::Error::new(io::ErrorKind::InvalidData, error)),
+        };
+
+        self.buffer.push_str(
+            std::str::from_utf8(&self.partial[..valid]).expect("only valid UTF-8 is taken"),
+        );
+        self.partial.drain(..valid);
+
+        Ok(())
+    }
+
+    /// Lexes the next token and hands it to `f`, alongside where it was in the template. Text is
+    /// handed over in pieces as it's read, rather than waiting for all of it.
+    pub fn next_spanned<T>(&mut self, f: impl FnOnce(Token, Span) -> T) -> io::Result<T> {
+        loop {
+            let mut lexer = Lexer::new(&self.buffer);
+            let mut token = lexer.next_token();
+            let mut length = lexer.position();
+
+            // Anything running up to the end of what's been read might carry on past it
+            let whole = self.finished || length < self.buffer.len();
+
+            // Empty tags end the template wherever they are
+            let ended_early = matches!(token, Token::End) && length > 0;
+
+            match &mut token {
+                Token::Text(raw) if !whole => {
+                    // An escape needs the char after it too
+                    let trailing = raw.len() - raw.trim_end_matches('\\').len();
+                    length -= trailing % 2;
+                    *raw = &raw[..length];
+                }
+                _ if !whole => length = 0,
+                _ => (),
+            }
+
+            if length == 0 && !self.finished {
+                self.fill()?;
+                continue;
+            }
+
+            let span = Span::new(self.offset, self.offset + length);
+            let output = f(token, span);
+
+            self.buffer.drain(..length);
+            self.offset += length;
+            self.finished |= ended_early;
+
+            return Ok(output);
+        }
+    }
+}
@@ -2,19 +2,189 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
-mod ast;
+pub mod ast;
+pub mod builder;
+pub mod codegen;
+pub mod cst;
+pub mod format;
+pub mod graph;
+pub mod inputs;
+mod json;
+mod lexing;
+pub mod loading;
+pub mod lsp;
+pub mod output;
 mod parsing;
+pub mod plugin;
+pub mod query;
 mod rendering;
+pub mod serve;
+pub mod shared;
+pub mod snapshot;
+pub mod sourcemap;
+pub mod ssg;
+mod streaming;
+pub mod unused;
+pub mod visit;
+pub mod watch;
 
-//#[cfg(test)]
-//mod tests;
+#[cfg(test)]
+mod tests;
+
+pub use parsing::{ParsingError, ParsingErrorKind, Span};
+pub use rendering::RenderingError;
 
 use ast::{elements::Element, Dom};
-use std::collections::HashMap;
+use graph::ComponentGraph;
+use loading::{ComponentSource, LoadingError, Naming};
+use output::{Origins, RenderOptions};
+use plugin::{Plugin, Plugins};
+use shared::SharedVelInstance;
+use snapshot::Snapshot;
+use sourcemap::SourceMap;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    io::Read,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+use unused::UnusedReport;
 
 /// This'd make a bad partner :/
-pub(crate) type LazyDom = ParseStatus<String, Dom>;
-pub(crate) type ComponentsCache = HashMap<String, LazyDom>;
+pub(crate) type LazyDom = ParseStatus<Arc<str>, Dom>;
+
+/// Every component an instance knows about. Any component it hasn't been given is looked up in
+/// its [`ComponentSource`], if it has one.
+#[derive(Default)]
+pub(crate) struct ComponentsCache {
+    entries: HashMap<String, LazyDom>,
+    /// The template each entry was made from, so parsed entries can be reset
+    templates: HashMap<String, Arc<str>>,
+    /// The file each component was read from, relative to the directory it was read from
+    paths: HashMap<String, PathBuf>,
+    source: Option<Box<dyn ComponentSource>>,
+}
+
+impl ComponentsCache {
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut LazyDom> {
+        if !self.entries.contains_key(name) {
+            let page = self.source.as_ref()?.load(name)?;
+            self.insert(name.to_string(), LazyDom::Unparsed(page.into()));
+        }
+
+        self.entries.get_mut(name)
+    }
+
+    pub fn insert(&mut self, name: String, dom: LazyDom) -> Option<LazyDom> {
+        if let LazyDom::Unparsed(page) = &dom {
+            self.templates.insert(name.clone(), page.clone());
+        }
+
+        self.entries.insert(name, dom)
+    }
+
+    /// Gets the name of every component, including the ones its source can list but that haven't
+    /// been loaded yet
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.entries.keys().cloned().collect();
+
+        if let Some(source) = &self.source {
+            names.extend(source.names());
+        }
+
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    /// Parses the component if it hasn't been already and gets its Dom
+    pub fn parsed(&mut self, component: &str) -> Result<&Dom, ParsingError> {
+        if !matches!(self.get_mut(component), Some(LazyDom::Parsed(_))) {
+            parsing::parse(component, self)?;
+        }
+
+        match self.entries.get(component) {
+            Some(LazyDom::Parsed(dom)) => Ok(dom),
+            _ => unreachable!("parse always stores the component it was asked for"),
+        }
+    }
+
+    /// Parses the component like [`Self::parsed`], alongside where every template came from for
+    /// debug markers
+    pub fn parsed_with_origins(
+        &mut self,
+        component: &str,
+        options: &RenderOptions,
+    ) -> Result<(&Dom, Origins), ParsingError> {
+        self.parsed(component)?;
+        let origins = self.origins(options);
+
+        Ok((self.parsed(component)?, origins))
+    }
+
+    /// Gets where every template came from, which is only needed for debug markers
+    pub fn origins(&self, options: &RenderOptions) -> Origins {
+        if !options.debug {
+            return Origins::default();
+        }
+
+        Origins::new(self.templates.clone(), &self.paths, self.source.as_deref())
+    }
+
+    /// Resets the given components, and every parsed component that inlined them, back to their
+    /// templates. Returns the names of everything that was reset.
+    pub fn invalidate(&mut self, changed: &HashSet<String>) -> Vec<String> {
+        let mut invalidated = vec![];
+
+        for (name, entry) in self.entries.iter_mut() {
+            let stale = match entry {
+                LazyDom::Parsed(dom) => changed.contains(name) || dom.uses_any(changed),
+                LazyDom::Unparsed(_) => changed.contains(name),
+            };
+
+            if stale {
+                if let Some(page) = self.templates.get(name) {
+                    *entry = LazyDom::Unparsed(page.clone());
+                    invalidated.push(name.clone());
+                }
+            }
+        }
+
+        invalidated.sort_unstable();
+        invalidated
+    }
+}
+
+impl Extend<(String, LazyDom)> for ComponentsCache {
+    fn extend<T: IntoIterator<Item = (String, LazyDom)>>(&mut self, iter: T) {
+        for (name, dom) in iter {
+            self.insert(name, dom);
+        }
+    }
+}
+
+impl FromIterator<(String, LazyDom)> for ComponentsCache {
+    fn from_iter<T: IntoIterator<Item = (String, LazyDom)>>(iter: T) -> Self {
+        let mut components = Self::default();
+        components.extend(iter);
+        components
+    }
+}
+
+impl fmt::Debug for ComponentsCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ComponentsCache")
+            .field("entries", &self.entries)
+            .field("source", &self.source.is_some())
+            .finish()
+    }
+}
 
 #[derive(Debug, Clone)]
 pub(crate) enum ParseStatus<T, U> {
@@ -22,9 +192,15 @@ pub(crate) enum ParseStatus<T, U> {
     Parsed(U),
 }
 
+/// The component and the inputs it uses, sorted, that some cached output was rendered with
+type RenderCacheKey = (String, Vec<(String, String)>);
+
 #[derive(Debug)]
 pub struct VelInstance {
     components: ComponentsCache,
+    render_cache: HashMap<RenderCacheKey, String>,
+    plugins: Plugins,
+    options: RenderOptions,
 }
 
 impl VelInstance {
@@ -33,17 +209,126 @@ impl VelInstance {
             components: ComponentsCache::from_iter(
                 components
                     .into_iter()
-                    .map(|(key, value)| (key, LazyDom::Unparsed(value))),
+                    .map(|(key, value)| (key, LazyDom::Unparsed(value.into()))),
             ),
+            render_cache: HashMap::new(),
+            plugins: Plugins::default(),
+            options: RenderOptions::default(),
         }
     }
 
+    /// Adds components, replacing any that share a name. See [`Self::reload`].
     pub fn extend(&mut self, components: HashMap<String, String>) -> &mut Self {
-        self.components.extend(
-            components
-                .into_iter()
-                .map(|(key, value)| (key, LazyDom::Unparsed(value))),
-        );
+        self.reload(components);
+        self
+    }
+
+    /// Swaps in new templates for any components that have changed. They, and every component
+    /// that used them, are reset to be parsed again the next time they're needed and any cached
+    /// render output is dropped. Returns the names of every component that was reset.
+    pub fn reload(&mut self, components: HashMap<String, String>) -> Vec<String> {
+        let mut changed = HashSet::new();
+
+        for (name, template) in components {
+            if self.components.templates.get(&name).map(|page| &**page) != Some(template.as_str()) {
+                self.components
+                    .insert(name.clone(), LazyDom::Unparsed(template.into()));
+                changed.insert(name);
+            }
+        }
+
+        if changed.is_empty() {
+            return vec![];
+        }
+
+        self.render_cache.clear();
+        self.components.invalidate(&changed)
+    }
+
+    /// Fetches every component the instance knows about from its [`ComponentSource`] again,
+    /// reloading any that changed. See [`Self::reload`]. Nothing is reloaded if the source
+    /// can't look for them again.
+    pub fn reload_from_source(&mut self) -> Result<Vec<String>, LoadingError> {
+        let Some(source) = &self.components.source else {
+            return Ok(vec![]);
+        };
+        source.refresh()?;
+
+        let components = self
+            .components
+            .templates
+            .keys()
+            .filter_map(|name| Some((name.clone(), source.load(name)?)))
+            .collect();
+
+        Ok(self.reload(components))
+    }
+
+    /// Reads every `.vel` file under `root` again, reloading any that changed. See [`Self::reload`].
+    pub fn reload_dir(
+        &mut self,
+        root: impl AsRef<Path>,
+        naming: Naming,
+    ) -> Result<Vec<String>, LoadingError> {
+        let components = self.read_dir(root.as_ref(), naming)?;
+        Ok(self.reload(components))
+    }
+
+    /// Creates an instance from every `.vel` file under `root`. See [`loading::load_dir`].
+    pub fn from_dir(root: impl AsRef<Path>, naming: Naming) -> Result<Self, LoadingError> {
+        let mut instance = Self::new(HashMap::new());
+        instance.extend_from_dir(root, naming)?;
+        Ok(instance)
+    }
+
+    /// Adds every `.vel` file under `root`, replacing any components that share a name.
+    pub fn extend_from_dir(
+        &mut self,
+        root: impl AsRef<Path>,
+        naming: Naming,
+    ) -> Result<&mut Self, LoadingError> {
+        let components = self.read_dir(root.as_ref(), naming)?;
+        Ok(self.extend(components))
+    }
+
+    /// Reads every `.vel` file under `root` like [`loading::load_dir`], and remembers which file
+    /// each component came from for debug markers
+    fn read_dir(
+        &mut self,
+        root: &Path,
+        naming: Naming,
+    ) -> Result<HashMap<String, String>, LoadingError> {
+        let mut components = HashMap::new();
+
+        for (name, path) in loading::scan_dir(root, naming)? {
+            components.insert(name.clone(), fs::read_to_string(&path)?);
+
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+            self.components.paths.insert(name, relative);
+        }
+
+        Ok(components)
+    }
+
+    /// Sets where components are fetched from when they're used without having been registered.
+    /// Registered components always take priority over the source.
+    pub fn set_source(&mut self, source: impl ComponentSource + 'static) -> &mut Self {
+        self.components.source = Some(Box::new(source));
+        self
+    }
+
+    /// Adds a plugin to run on everything rendered from now on, after the ones already added.
+    pub fn add_plugin(&mut self, plugin: impl Plugin + 'static) -> &mut Self {
+        self.plugins.push(plugin);
+        // Anything already cached was rendered without it
+        self.render_cache.clear();
+        self
+    }
+
+    /// Sets how everything rendered from now on is written out, like whether it's minified.
+    pub fn set_render_options(&mut self, options: RenderOptions) -> &mut Self {
+        self.options = options;
+        self.render_cache.clear();
         self
     }
 
@@ -52,34 +337,307 @@ impl VelInstance {
         component: String,
         inputs: HashMap<String, String>,
         rendering_callback: F,
-        //) -> Result<String, RenderingError>>
-    ) -> Result<String, ()>
+    ) -> Result<String, RenderingError>
     where
         F: Fn(Element) -> Option<Element> + std::marker::Copy,
     {
-        let mut test: HashMap<(Vec<(&str, &str)>, &String, *const F), &str> = HashMap::new();
+        let (dom, origins) = self
+            .components
+            .parsed_with_origins(&component, &self.options)
+            .map_err(RenderingError::Parsing)?;
+
+        rendering::render(
+            dom,
+            &inputs,
+            rendering_callback,
+            &self.plugins,
+            &self.options,
+            origins,
+        )
+    }
 
-        let mut inputs_vec: Vec<_> = inputs
+    /// Renders a component without a callback, reusing the output from the last time it was
+    /// rendered with the same inputs.
+    pub fn render_cached(
+        &mut self,
+        component: String,
+        inputs: HashMap<String, String>,
+    ) -> Result<String, RenderingError> {
+        let (dom, origins) = self
+            .components
+            .parsed_with_origins(&component, &self.options)
+            .map_err(RenderingError::Parsing)?;
+
+        // Since we parse the whole thing into a DOM anyway, we know which inputs are required
+        // and can filter based off that, reducing the amount of redundant variations in the cache
+        let required = dom.inputs();
+        let mut inputs_vec: Vec<(String, String)> = inputs
             .iter()
-            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .filter(|(key, _)| required.contains(key.as_str()))
+            .map(|(key, value)| (key.clone(), value.clone()))
             .collect();
         inputs_vec.sort_unstable();
-        dbg!(&inputs_vec);
 
-        // NOTE: When making this fr, since we parse the whole thing into a DOM anyway, we can
-        // figure out which inputs are required and filter based of that, reducing the amount of
-        // redundant variations in the cache
-        test.insert(
-            (inputs_vec, &component, &rendering_callback as *const F),
-            "hello world",
-        );
+        let key = (component, inputs_vec);
 
-        parsing::parse(&component, &mut self.components)?;
-        //rendering::render(&dom, Cow::Borrowed(&inputs), &mut self.cache);
-        Ok(String::new())
+        if let Some(output) = self.render_cache.get(&key) {
+            return Ok(output.clone());
+        }
+
+        let output = rendering::render(dom, &inputs, Some, &self.plugins, &self.options, origins)?;
+        self.render_cache.insert(key, output.clone());
+
+        Ok(output)
+    }
+
+    /// Renders a component straight from its template, and the templates of the components it
+    /// uses, without parsing them into a Dom first. Components that have only ever been given
+    /// already parsed can't be rendered this way, and neither can anything once plugins have
+    /// been added, as they only run on a Dom.
+    pub fn render_streaming(
+        &mut self,
+        component: String,
+        inputs: HashMap<String, String>,
+    ) -> Result<String, RenderingError> {
+        // Leaving them out would quietly render something other than what `render` does
+        if !self.plugins.is_empty() {
+            return Err(RenderingError::PluginsUnsupported);
+        }
+        self.fetch_templates(&component);
+
+        streaming::render(
+            &self.components.templates,
+            &component,
+            &inputs,
+            &self.options,
+            self.components.origins(&self.options),
+        )
+    }
+
+    /// Renders a component like [`Self::render_streaming`], and makes a [`SourceMap`] of which
+    /// part of which template each part of the output came from. Like it, this fails once plugins
+    /// have been added.
+    pub fn render_with_source_map(
+        &mut self,
+        component: String,
+        inputs: HashMap<String, String>,
+    ) -> Result<(String, SourceMap), RenderingError> {
+        if !self.plugins.is_empty() {
+            return Err(RenderingError::PluginsUnsupported);
+        }
+        self.fetch_templates(&component);
+
+        streaming::render_mapped(
+            &self.components.templates,
+            &component,
+            &inputs,
+            &self.options,
+            self.components.origins(&self.options),
+        )
+    }
+
+    /// Makes sure every template rendering `component` might use has been fetched from the
+    /// source
+    fn fetch_templates(&mut self, component: &str) {
+        let mut queue = vec![component.to_string()];
+        let mut seen = HashSet::new();
+
+        while let Some(name) = queue.pop() {
+            if seen.insert(name.clone()) && self.components.get_mut(&name).is_some() {
+                if let Some(template) = self.components.templates.get(&name) {
+                    queue.extend(parsing::referenced_components(template));
+                }
+            }
+        }
+    }
+
+    /// Parses every component and works out which components each of them uses.
+    pub fn graph(&mut self) -> Result<ComponentGraph, ParsingError> {
+        let mut graph = ComponentGraph::default();
+
+        for name in self.components.names() {
+            graph.insert(&name, self.components.parsed(&name)?.components());
+        }
+
+        Ok(graph)
+    }
+
+    /// Finds every component that can't be reached from `roots`, such as your pages, alongside
+    /// any slots that are declared but never filled or filled but never declared.
+    pub fn unused(&mut self, roots: &[&str]) -> Result<UnusedReport, ParsingError> {
+        let graph = self.graph()?;
+        unused::report(&mut self.components, &graph, roots)
+    }
+
+    /// Parses every component up front, rather than as each one is first needed, so that every
+    /// broken template is found at once instead of one at a time.
+    ///
+    /// `threads` is how many threads to parse across, where 1 parses on the current thread and 0
+    /// uses as many as are available.
+    pub fn compile_all(&mut self, threads: usize) -> Result<(), Vec<ParsingError>> {
+        let names = self.components.names();
+
+        let mut errors: Vec<ParsingError> = if threads == 1 {
+            names
+                .iter()
+                .filter_map(|name| self.components.parsed(name).err())
+                .collect()
+        } else {
+            let threads = match threads {
+                0 => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+                threads => threads,
+            };
+
+            let shared = SharedVelInstance::from_cache(std::mem::take(&mut self.components));
+            let next = AtomicUsize::new(0);
+
+            let results: Vec<thread::Result<Vec<ParsingError>>> = thread::scope(|scope| {
+                let workers: Vec<_> = (0..threads)
+                    .map(|_| {
+                        scope.spawn(|| {
+                            std::iter::from_fn(|| names.get(next.fetch_add(1, Ordering::Relaxed)))
+                                .filter_map(|name| shared.dom(name).err())
+                                .collect::<Vec<_>>()
+                        })
+                    })
+                    .collect();
+
+                workers.into_iter().map(|worker| worker.join()).collect()
+            });
+
+            // The cache goes back before any panic is passed on, so the instance isn't left empty
+            self.components = shared.into_cache();
+
+            let mut errors = vec![];
+            for result in results {
+                match result {
+                    Ok(worker_errors) => errors.extend(worker_errors),
+                    Err(panic) => std::panic::resume_unwind(panic),
+                }
+            }
+            errors
+        };
+
+        if errors.is_empty() {
+            return Ok(());
+        }
+
+        // A broken component also breaks every component that uses it, so the same error turns up
+        // once for each of them
+        errors.sort_unstable();
+        errors.dedup();
+
+        Err(errors)
+    }
+
+    /// Takes every parsed component, alongside the hash of its template, so they can be saved and
+    /// loaded by a later instance without parsing them again.
+    pub fn snapshot(&self) -> Snapshot {
+        let components = self
+            .components
+            .entries
+            .iter()
+            .filter_map(|(name, entry)| match entry {
+                LazyDom::Parsed(dom) => {
+                    let template = self.components.templates.get(name)?;
+                    Some((
+                        name.clone(),
+                        (snapshot::template_hash(template), dom.clone()),
+                    ))
+                }
+                LazyDom::Unparsed(_) => None,
+            })
+            .collect();
+
+        Snapshot { components }
+    }
+
+    /// Uses the parsed components in `snapshot` in place of parsing them. Components whose
+    /// template has changed since the snapshot was taken, or that the instance doesn't have a
+    /// template for, are left to be parsed again, along with every component that used them.
+    /// Returns the names of the components that were left out.
+    pub fn load_snapshot(&mut self, snapshot: Snapshot) -> Vec<String> {
+        let mut stale = HashSet::new();
+
+        for (name, (hash, dom)) in snapshot.components {
+            // Fetching it through the cache pulls its template from the source if we need to
+            let template = match self.components.get_mut(&name) {
+                Some(_) => self.components.templates.get(&name),
+                None => None,
+            };
+
+            match template {
+                Some(template) if snapshot::template_hash(template) == hash => {
+                    self.components.insert(name, LazyDom::Parsed(dom));
+                }
+                _ => {
+                    stale.insert(name);
+                }
+            }
+        }
+
+        self.render_cache.clear();
+
+        if stale.is_empty() {
+            return vec![];
+        }
+
+        // Anything that inlined a stale component is just as stale, even if its own template
+        // hasn't changed
+        self.components.invalidate(&stale);
+
+        let mut stale: Vec<String> = stale.into_iter().collect();
+        stale.sort_unstable();
+        stale
+    }
+
+    /// Parses a component from `reader` as it's read, so big templates never have to be held in
+    /// memory all at once. Any component with the same name is replaced, and every component
+    /// that used it is reset to be parsed again.
+    ///
+    /// As the template is never held onto, the component can't be reset or reloaded, nor saved
+    /// in a [`Snapshot`], but it can still be used by other components like any other.
+    pub fn parse_reader(
+        &mut self,
+        component: String,
+        reader: impl Read,
+    ) -> Result<(), LoadingError> {
+        parsing::parse_reader(&component, reader, &mut self.components)?;
+
+        // The template it had before isn't what it's made from anymore
+        self.components.templates.remove(&component);
+        self.components.invalidate(&HashSet::from([component]));
+        self.render_cache.clear();
+
+        Ok(())
+    }
+
+    /// Adds a component that's already been parsed or [built](builder), replacing any other
+    /// component with the same name. Every component it uses is inlined into it, and every
+    /// component that used the one it replaced is reset to be parsed again.
+    ///
+    /// As it has no template, it can't be reset or reloaded, nor saved in a [`Snapshot`]. It keeps
+    /// the components it used as they were when it was registered.
+    pub fn register(&mut self, component: String, mut dom: Dom) -> Result<(), ParsingError> {
+        builder::inline_calls(&component, &mut dom, &mut self.components)?;
+        Arc::make_mut(&mut dom.tree).name = component.clone();
+
+        self.components
+            .insert(component.clone(), LazyDom::Parsed(dom));
+        self.components.templates.remove(&component);
+        self.components.invalidate(&HashSet::from([component]));
+        self.render_cache.clear();
+
+        Ok(())
+    }
+
+    /// Parses the component if it hasn't been already and gets its Dom, such as to
+    /// [`select`](Dom::select) elements from it.
+    pub fn dom(&mut self, component: &str) -> Result<&Dom, ParsingError> {
+        self.components.parsed(component)
     }
 
-    pub fn parse(&mut self, component: String) -> Result<(), ()> {
+    pub fn parse(&mut self, component: String) -> Result<(), ParsingError> {
         parsing::parse(&component, &mut self.components)?;
         Ok(())
     }
@@ -0,0 +1,387 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Loading components from a directory of `.vel` files.
+
+use crate::ParsingError;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+/// The file extension a component must have for it to be picked up by the loader.
+pub const COMPONENT_EXTENSION: &str = "vel";
+
+/// How a component file is named once it has been loaded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Naming {
+    /// `ui/Button.vel` becomes `Button`
+    #[default]
+    FileStem,
+    /// `ui/Button.vel` becomes `ui.Button`
+    RelativePath,
+}
+
+impl Naming {
+    /// Gets the component name for a path relative to the root of the components directory.
+    /// Returns [`None`] if the path isn't a component file.
+    pub fn name_of(&self, relative_path: &Path) -> Option<String> {
+        if relative_path.extension()? != COMPONENT_EXTENSION {
+            return None;
+        }
+
+        let stem = relative_path.file_stem()?.to_str()?;
+
+        match self {
+            Naming::FileStem => Some(stem.to_string()),
+            Naming::RelativePath => {
+                let mut segments = relative_path
+                    .parent()
+                    .into_iter()
+                    .flat_map(Path::components)
+                    .map(|segment| segment.as_os_str().to_str())
+                    .collect::<Option<Vec<&str>>>()?;
+                segments.push(stem);
+
+                Some(segments.join("."))
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadingError {
+    Io(io::Error),
+    /// A component that was parsed as it was read turned out to be broken
+    Parsing(ParsingError),
+    /// Every name that more than one file resolved to, alongside all of the files that claimed it.
+    DuplicateNames(Vec<(String, Vec<PathBuf>)>),
+}
+
+impl fmt::Display for LoadingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadingError::Io(error) => {
+                write!(f, "failed to read components: {error}")
+            }
+            LoadingError::Parsing(error) => write!(f, "{error}"),
+            LoadingError::DuplicateNames(duplicates) => {
+                write!(f, "multiple files resolve to the same component name:")?;
+                for (name, paths) in duplicates {
+                    write!(f, "\n  {name}:")?;
+                    for path in paths {
+                        write!(f, " {}", path.display())?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadingError::Io(error) => Some(error),
+            LoadingError::Parsing(error) => Some(error),
+            LoadingError::DuplicateNames(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadingError {
+    fn from(error: io::Error) -> Self {
+        LoadingError::Io(error)
+    }
+}
+
+impl From<ParsingError> for LoadingError {
+    fn from(error: ParsingError) -> Self {
+        LoadingError::Parsing(error)
+    }
+}
+
+/// Finds every component file under `root` and maps its name to its path without reading it.
+/// Symlinked directories aren't followed, so a link back up the tree can't loop forever.
+pub fn scan_dir(
+    root: impl AsRef<Path>,
+    naming: Naming,
+) -> Result<HashMap<String, PathBuf>, LoadingError> {
+    let root = root.as_ref();
+
+    // A BTreeMap keeps the duplicate report in a stable order between runs
+    let mut found: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    let mut directories = vec![root.to_path_buf()];
+
+    while let Some(directory) = directories.pop() {
+        for entry in fs::read_dir(&directory)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            // Unlike `Path::is_dir`, this doesn't follow symlinks
+            if entry.file_type()?.is_dir() {
+                directories.push(path);
+                continue;
+            }
+
+            let relative_path = path
+                .strip_prefix(root)
+                .expect("read_dir only yields paths inside of the directory it read");
+
+            if let Some(name) = naming.name_of(relative_path) {
+                found.entry(name).or_default().push(path);
+            }
+        }
+    }
+
+    let duplicates: Vec<(String, Vec<PathBuf>)> = found
+        .iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(name, paths)| {
+            let mut paths = paths.clone();
+            paths.sort_unstable();
+            (name.clone(), paths)
+        })
+        .collect();
+
+    if !duplicates.is_empty() {
+        return Err(LoadingError::DuplicateNames(duplicates));
+    }
+
+    Ok(found
+        .into_iter()
+        .filter_map(|(name, mut paths)| Some((name, paths.pop()?)))
+        .collect())
+}
+
+/// Reads every component file under `root`, ready to be handed to [`crate::VelInstance`].
+pub fn load_dir(
+    root: impl AsRef<Path>,
+    naming: Naming,
+) -> Result<HashMap<String, String>, LoadingError> {
+    scan_dir(root, naming)?
+        .into_iter()
+        .map(|(name, path)| Ok((name, fs::read_to_string(path)?)))
+        .collect()
+}
+
+/// Somewhere templates can be fetched from by component name, such as a directory, a database or
+/// the binary itself. Consulted whenever a component is used that hasn't been registered.
+pub trait ComponentSource: Send + Sync {
+    /// Fetches the template of the component called `name`, or [`None`] if this source doesn't
+    /// have it.
+    fn load(&self, name: &str) -> Option<String>;
+
+    /// Lists every component this source can load. Sources that can't be listed, or can't
+    /// cheaply be listed, may leave this empty.
+    fn names(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// Forgets anything the source remembers about where its templates are, ready for them to
+    /// be loaded again. Called before an instance reloads from its source. Sources that can't
+    /// look again keep what they remembered and say why.
+    fn refresh(&self) -> Result<(), LoadingError> {
+        Ok(())
+    }
+
+    /// The file the component called `name` is loaded from, for pointing back at it when
+    /// debugging. Sources that don't load from files leave this as [`None`].
+    fn path(&self, _name: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// Templates held in memory, such as ones embedded into the binary with [`include_str!`].
+#[derive(Debug, Clone, Default)]
+pub struct MemorySource {
+    components: HashMap<String, String>,
+}
+
+impl MemorySource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, template: impl Into<String>) -> &mut Self {
+        self.components.insert(name.into(), template.into());
+        self
+    }
+}
+
+impl From<HashMap<String, String>> for MemorySource {
+    fn from(components: HashMap<String, String>) -> Self {
+        Self { components }
+    }
+}
+
+impl<const N: usize> From<[(&str, &str); N]> for MemorySource {
+    fn from(components: [(&str, &str); N]) -> Self {
+        Self {
+            components: components
+                .into_iter()
+                .map(|(name, template)| (name.to_string(), template.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl ComponentSource for MemorySource {
+    fn load(&self, name: &str) -> Option<String> {
+        self.components.get(name).cloned()
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.components.keys().cloned().collect()
+    }
+}
+
+/// Templates read from a directory of `.vel` files each time they're needed.
+#[derive(Debug, Clone)]
+pub struct DirectorySource {
+    root: PathBuf,
+    naming: Naming,
+    /// Where each component is when they're named by file stem, which can't be worked out from
+    /// the name alone. It's scanned again whenever a new name is looked up or a file has moved.
+    stems: Arc<RwLock<StemIndex>>,
+}
+
+#[derive(Debug, Default)]
+struct StemIndex {
+    paths: HashMap<String, PathBuf>,
+    /// Names that weren't found the last time the directory was scanned, so looking them up
+    /// again doesn't scan it every time. They're looked for again once it's refreshed.
+    missing: HashSet<String>,
+}
+
+impl DirectorySource {
+    pub fn new(root: impl Into<PathBuf>, naming: Naming) -> Self {
+        Self {
+            root: root.into(),
+            naming,
+            stems: Arc::default(),
+        }
+    }
+
+    /// Finds the file a component lives in, without leaving the root directory.
+    pub fn path_of(&self, name: &str) -> Option<PathBuf> {
+        match self.naming {
+            Naming::FileStem => {
+                let index = self.stems.read().unwrap();
+                match index.paths.get(name) {
+                    Some(path) if path.is_file() => return Some(path.clone()),
+                    None if index.missing.contains(name) => return None,
+                    _ => drop(index),
+                }
+
+                // A directory that can't be scanned right now is left as it was last time
+                let _ = self.refresh();
+
+                let mut index = self.stems.write().unwrap();
+                let path = index.paths.get(name).cloned();
+                if path.is_none() {
+                    index.missing.insert(name.to_string());
+                }
+                path
+            }
+            Naming::RelativePath => {
+                let mut path = self.root.clone();
+
+                for segment in name.split('.') {
+                    if segment.is_empty() || segment.contains(['/', '\\']) {
+                        return None;
+                    }
+                    path.push(segment);
+                }
+
+                path.set_extension(COMPONENT_EXTENSION);
+                Some(path)
+            }
+        }
+    }
+}
+
+impl ComponentSource for DirectorySource {
+    fn load(&self, name: &str) -> Option<String> {
+        fs::read_to_string(self.path_of(name)?).ok()
+    }
+
+    fn names(&self) -> Vec<String> {
+        scan_dir(&self.root, self.naming)
+            .map(|components| components.into_keys().collect())
+            .unwrap_or_default()
+    }
+
+    /// Relative to the root directory, like `ui/Card.vel`
+    fn path(&self, name: &str) -> Option<PathBuf> {
+        let path = self.path_of(name)?;
+        Some(
+            path.strip_prefix(&self.root)
+                .map_or(path.clone(), Path::to_path_buf),
+        )
+    }
+
+    fn refresh(&self) -> Result<(), LoadingError> {
+        if self.naming == Naming::FileStem {
+            *self.stems.write().unwrap() = StemIndex {
+                paths: scan_dir(&self.root, self.naming)?,
+                missing: HashSet::new(),
+            };
+        }
+
+        Ok(())
+    }
+}
+
+/// Tries each source in the order they were added, using the first one that has the component.
+#[derive(Default)]
+pub struct ChainedSource {
+    sources: Vec<Box<dyn ComponentSource>>,
+}
+
+impl ChainedSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a source to fall back to when none of the previous ones have a component.
+    pub fn or(mut self, source: impl ComponentSource + 'static) -> Self {
+        self.sources.push(Box::new(source));
+        self
+    }
+}
+
+impl ComponentSource for ChainedSource {
+    fn load(&self, name: &str) -> Option<String> {
+        self.sources.iter().find_map(|source| source.load(name))
+    }
+
+    /// Refreshes every source, even after one fails, and says why the first one that did
+    fn refresh(&self) -> Result<(), LoadingError> {
+        self.sources
+            .iter()
+            .map(|source| source.refresh())
+            .fold(Ok(()), Result::and)
+    }
+
+    fn path(&self, name: &str) -> Option<PathBuf> {
+        self.sources
+            .iter()
+            .find(|source| source.load(name).is_some())?
+            .path(name)
+    }
+
+    fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .sources
+            .iter()
+            .flat_map(|source| source.names())
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+}
@@ -0,0 +1,610 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! A language server for templates, which editors talk to over stdin and stdout with the
+//! `vel-lsp` binary.
+//!
+//! It loads every component under the workspace folder, and keeps the templates that are open in
+//! the editor in step with what's been typed. On top of that it gives:
+//! - diagnostics for templates that fail to parse
+//! - go to definition from a component's tag to its file
+//! - completion for component names after `<`, variables after `{`, and after `slot=` the slots
+//!   of the component the element is in
+//! - hover for a component's tag, showing the inputs it needs
+//!
+//! Components are named by their file name unless the client sets `relativeNames` in its
+//! initialization options. Only full document syncing is supported.
+
+use crate::{
+    ast::elements::AttributeValues,
+    json::{self, Value},
+    lexing::{Lexer, Token},
+    loading::{self, Naming},
+    parsing::{is_component_name, parse_attribute_value, VOID_ELEMENTS},
+    serve::decode,
+    Span, VelInstance,
+};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// JSON-RPC's code for a method the server doesn't know
+const METHOD_NOT_FOUND: f64 = -32601.0;
+
+/// LSP's completion item kinds
+const KIND_VARIABLE: f64 = 6.0;
+const KIND_CLASS: f64 = 7.0;
+const KIND_PROPERTY: f64 = 10.0;
+
+/// Serves one client until it exits or `input` runs out.
+pub fn run(input: impl Read, output: impl Write) -> io::Result<()> {
+    let mut input = BufReader::new(input);
+    let mut server = Server {
+        output,
+        root: None,
+        naming: Naming::FileStem,
+        instance: VelInstance::new(HashMap::new()),
+        paths: HashMap::new(),
+        documents: HashMap::new(),
+    };
+
+    while let Some(message) = read_message(&mut input)? {
+        // Responses to requests we never send can be ignored
+        let Some(method) = message.get(&["method"]).and_then(Value::as_str) else {
+            continue;
+        };
+
+        if method == "exit" {
+            return Ok(());
+        }
+
+        let params = message.get(&["params"]).unwrap_or(&Value::Null);
+        let result = server.handle(method, params)?;
+
+        if let Some(id) = message.get(&["id"]) {
+            let outcome = match result {
+                Some(result) => ("result", result),
+                None => (
+                    "error",
+                    Value::from([
+                        ("code", Value::Number(METHOD_NOT_FOUND.into())),
+                        ("message", format!("`{method}` isn't supported").into()),
+                    ]),
+                ),
+            };
+
+            server.send(Value::from([
+                ("jsonrpc", "2.0".into()),
+                ("id", id.clone()),
+                outcome,
+            ]))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a message, skipping any that aren't JSON. Returns [`None`] once `input` runs out.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    loop {
+        let mut length = None;
+
+        loop {
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse::<usize>().ok();
+                }
+            }
+        }
+
+        let Some(length) = length else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "a message didn't have a Content-Length",
+            ));
+        };
+
+        let mut body = vec![0; length];
+        input.read_exact(&mut body)?;
+
+        if let Ok(message) = json::parse(&String::from_utf8_lossy(&body)) {
+            return Ok(Some(message));
+        }
+    }
+}
+
+/// A template that's open in the editor
+struct Document {
+    component: String,
+    text: String,
+}
+
+struct Server<W> {
+    output: W,
+    /// The workspace folder, which components are loaded from
+    root: Option<PathBuf>,
+    naming: Naming,
+    instance: VelInstance,
+    /// Where every component in the workspace is
+    paths: HashMap<String, PathBuf>,
+    /// Open templates by their URI
+    documents: HashMap<String, Document>,
+}
+
+impl<W: Write> Server<W> {
+    fn send(&mut self, message: Value) -> io::Result<()> {
+        let body = message.to_string();
+        write!(self.output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+        self.output.flush()
+    }
+
+    /// Handles a request or notification, returning the result for requests or [`None`] when the
+    /// method isn't supported
+    fn handle(&mut self, method: &str, params: &Value) -> io::Result<Option<Value>> {
+        let uri = params
+            .get(&["textDocument", "uri"])
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+
+        let result = match method {
+            "initialize" => self.initialize(params),
+            "initialized" | "shutdown" => Value::Null,
+            "textDocument/didOpen" => {
+                let text = params
+                    .get(&["textDocument", "text"])
+                    .and_then(Value::as_str);
+                self.open(uri, text.unwrap_or_default().to_string())?;
+                Value::Null
+            }
+            "textDocument/didChange" => {
+                // With full syncing, the last change has the whole document
+                let text = match params.get(&["contentChanges"]) {
+                    Some(Value::Array(changes)) => changes
+                        .last()
+                        .and_then(|change| change.get(&["text"]))
+                        .and_then(Value::as_str),
+                    _ => None,
+                };
+
+                if let Some(text) = text {
+                    self.open(uri, text.to_string())?;
+                }
+                Value::Null
+            }
+            "textDocument/didClose" => {
+                self.close(&uri)?;
+                Value::Null
+            }
+            "textDocument/didSave" | "workspace/didChangeWatchedFiles" => {
+                self.load();
+                self.publish_all()?;
+                Value::Null
+            }
+            "textDocument/definition" => self.definition(&uri, params),
+            "textDocument/hover" => self.hover(&uri, params),
+            "textDocument/completion" => self.completion(&uri, params),
+            _ => return Ok(None),
+        };
+
+        Ok(Some(result))
+    }
+
+    fn initialize(&mut self, params: &Value) -> Value {
+        let root = params
+            .get(&["rootUri"])
+            .and_then(Value::as_str)
+            .and_then(uri_to_path)
+            .or_else(|| params.get(&["rootPath"])?.as_str().map(PathBuf::from));
+
+        if params.get(&["initializationOptions", "relativeNames"]) == Some(&Value::Bool(true)) {
+            self.naming = Naming::RelativePath;
+        }
+
+        self.root = root;
+        self.load();
+
+        let triggers = ["<", "{", "=", "\"", "'"].map(Value::from).to_vec();
+        Value::from([
+            (
+                "capabilities",
+                Value::from([
+                    // Full syncing
+                    ("textDocumentSync", Value::Number(1.0.into())),
+                    ("definitionProvider", Value::Bool(true)),
+                    ("hoverProvider", Value::Bool(true)),
+                    (
+                        "completionProvider",
+                        Value::from([("triggerCharacters", Value::Array(triggers))]),
+                    ),
+                ]),
+            ),
+            ("serverInfo", Value::from([("name", "vel-lsp".into())])),
+        ])
+    }
+
+    /// Reads every component in the workspace again, keeping the open templates as they are in the
+    /// editor
+    fn load(&mut self) {
+        if let Some(root) = &self.root {
+            if let Ok(paths) = loading::scan_dir(root, self.naming) {
+                self.paths = paths;
+            }
+            if let Ok(components) = loading::load_dir(root, self.naming) {
+                self.instance.reload(components);
+            }
+        }
+
+        let open = self
+            .documents
+            .values()
+            .map(|document| (document.component.clone(), document.text.clone()))
+            .collect();
+        self.instance.reload(open);
+    }
+
+    /// The component a template is, going by where it is in the workspace
+    fn component_of(&self, uri: &str) -> String {
+        let path = uri_to_path(uri).unwrap_or_else(|| PathBuf::from(uri));
+
+        self.root
+            .as_ref()
+            .and_then(|root| path.strip_prefix(root).ok())
+            .and_then(|relative| self.naming.name_of(relative))
+            .or_else(|| Some(path.file_stem()?.to_string_lossy().into_owned()))
+            .unwrap_or_default()
+    }
+
+    fn open(&mut self, uri: String, text: String) -> io::Result<()> {
+        let component = self.component_of(&uri);
+        self.instance
+            .reload(HashMap::from([(component.clone(), text.clone())]));
+        self.documents.insert(uri, Document { component, text });
+
+        // Other open templates can break or be fixed by a change to one they use
+        self.publish_all()
+    }
+
+    fn close(&mut self, uri: &str) -> io::Result<()> {
+        let Some(document) = self.documents.remove(uri) else {
+            return Ok(());
+        };
+
+        // What's on disk is all there is once it's closed
+        let saved = self
+            .paths
+            .get(&document.component)
+            .and_then(|path| fs::read_to_string(path).ok());
+        if let Some(text) = saved {
+            self.instance
+                .reload(HashMap::from([(document.component, text)]));
+        }
+
+        self.publish(uri, vec![])?;
+        self.publish_all()
+    }
+
+    fn publish_all(&mut self) -> io::Result<()> {
+        let mut uris: Vec<String> = self.documents.keys().cloned().collect();
+        uris.sort_unstable();
+
+        for uri in uris {
+            let diagnostics = self.diagnostics(&uri);
+            self.publish(&uri, diagnostics)?;
+        }
+
+        Ok(())
+    }
+
+    fn publish(&mut self, uri: &str, diagnostics: Vec<Value>) -> io::Result<()> {
+        self.send(Value::from([
+            ("jsonrpc", "2.0".into()),
+            ("method", "textDocument/publishDiagnostics".into()),
+            (
+                "params",
+                Value::from([
+                    ("uri", uri.into()),
+                    ("diagnostics", Value::Array(diagnostics)),
+                ]),
+            ),
+        ]))
+    }
+
+    fn diagnostics(&mut self, uri: &str) -> Vec<Value> {
+        let Some(document) = self.documents.get(uri) else {
+            return vec![];
+        };
+
+        let Some(error) = self.instance.dom(&document.component).err() else {
+            return vec![];
+        };
+
+        // Errors in the components it uses have spans into those, so they go at the start
+        let span = match error.component == document.component {
+            true => error.span,
+            false => Span::default(),
+        };
+
+        vec![Value::from([
+            ("range", range(&document.text, span)),
+            // An error
+            ("severity", Value::Number(1.0.into())),
+            ("source", "vel".into()),
+            ("message", error.to_string().into()),
+        ])]
+    }
+
+    /// Finds the document and the byte offset a request is about
+    fn locate<'a>(&'a self, uri: &str, params: &Value) -> Option<(&'a Document, usize)> {
+        let document = self.documents.get(uri)?;
+        let offset = offset(&document.text, params.get(&["position"])?)?;
+        Some((document, offset))
+    }
+
+    fn definition(&self, uri: &str, params: &Value) -> Value {
+        let path = self
+            .locate(uri, params)
+            .and_then(|(document, offset)| tag_name_at(&document.text, offset))
+            .and_then(|name| self.paths.get(name));
+
+        match path {
+            Some(path) => Value::from([
+                ("uri", path_to_uri(path).into()),
+                ("range", range("", Span::default())),
+            ]),
+            None => Value::Null,
+        }
+    }
+
+    fn hover(&mut self, uri: &str, params: &Value) -> Value {
+        let Some(name) = self
+            .locate(uri, params)
+            .and_then(|(document, offset)| tag_name_at(&document.text, offset))
+            .filter(|name| is_component_name(name))
+            .map(str::to_string)
+        else {
+            return Value::Null;
+        };
+
+        let contents = match self.instance.dom(&name) {
+            Ok(dom) => match dom.inputs() {
+                inputs if inputs.is_empty() => format!("**{name}** takes no inputs"),
+                inputs => {
+                    let inputs: Vec<String> =
+                        inputs.iter().map(|input| format!("`{input}`")).collect();
+                    format!("**{name}** takes {}", inputs.join(", "))
+                }
+            },
+            Err(error) => format!("**{name}** doesn't parse: {error}"),
+        };
+
+        Value::from([(
+            "contents",
+            Value::from([("kind", "markdown".into()), ("value", contents.into())]),
+        )])
+    }
+
+    fn completion(&mut self, uri: &str, params: &Value) -> Value {
+        let Some((text, offset)) = self
+            .locate(uri, params)
+            .map(|(document, offset)| (document.text.clone(), offset))
+        else {
+            return Value::Array(vec![]);
+        };
+
+        let (labels, kind) = match completing(&text, offset) {
+            Some(Completing::Component) => (self.instance.components.names(), KIND_CLASS),
+            Some(Completing::Variable) => (variables(&text), KIND_VARIABLE),
+            Some(Completing::Slot { tag_start }) => {
+                let parent = parent_component(&text, tag_start);
+                let slots = match parent.map(|parent| self.instance.dom(parent)) {
+                    Some(Ok(dom)) => dom
+                        .slots()
+                        .into_iter()
+                        .filter_map(|name| match name {
+                            // Slots named with a variable can't be known ahead of time
+                            [AttributeValues::Text(text)] => Some(text.value.to_string()),
+                            _ => None,
+                        })
+                        .collect(),
+                    _ => vec![],
+                };
+                (slots, KIND_PROPERTY)
+            }
+            None => (vec![], KIND_CLASS),
+        };
+
+        Value::Array(
+            labels
+                .into_iter()
+                .map(|label| {
+                    Value::from([
+                        ("label", label.into()),
+                        ("kind", Value::Number(kind.into())),
+                    ])
+                })
+                .collect(),
+        )
+    }
+}
+
+/// What's being typed at the cursor
+#[derive(Debug, PartialEq, Eq)]
+enum Completing {
+    /// A tag's name, after `<` or `</`
+    Component,
+    /// After a `{`
+    Variable,
+    /// The value of a `slot` attribute in the tag starting at `tag_start`
+    Slot { tag_start: usize },
+}
+
+fn is_name(char: char) -> bool {
+    char.is_alphanumeric() || matches!(char, '.' | '-' | '_')
+}
+
+/// Where the name that ends at `offset` starts
+fn name_start(source: &str, offset: usize) -> usize {
+    source[..offset]
+        .char_indices()
+        .rev()
+        .find(|(_, char)| !is_name(*char))
+        .map_or(0, |(index, char)| index + char.len_utf8())
+}
+
+fn completing(source: &str, offset: usize) -> Option<Completing> {
+    let before = &source[..name_start(source, offset)];
+
+    if before.ends_with('<') {
+        return Some(Completing::Component);
+    }
+    if before.ends_with('{') && !before.ends_with("\\{") {
+        return Some(Completing::Variable);
+    }
+
+    let before = before.strip_suffix(['"', '\'']).unwrap_or(before);
+    if !before.ends_with("slot=") {
+        return None;
+    }
+
+    let tag_start = before.rfind('<')?;
+    match before[tag_start..].contains('>') {
+        true => None,
+        false => Some(Completing::Slot { tag_start }),
+    }
+}
+
+/// Gets the name of the tag `offset` is in the name of, like `Card` in `<Card>` or `</Card>`
+fn tag_name_at(source: &str, offset: usize) -> Option<&str> {
+    let start = name_start(source, offset);
+    let end = source[offset..]
+        .find(|char| !is_name(char))
+        .map_or(source.len(), |length| offset + length);
+
+    let before = &source[..start];
+    let is_tag = before.ends_with('<') || before.ends_with("</");
+    Some(&source[start..end]).filter(|name| is_tag && !name.is_empty())
+}
+
+/// Finds the element that something starting at `offset` would be a child of, when it's a
+/// component
+fn parent_component(source: &str, offset: usize) -> Option<&str> {
+    let mut lexer = Lexer::new(&source[..offset]);
+    let mut open = vec![];
+
+    loop {
+        match lexer.next_spanned().0 {
+            Token::Open { name, .. } if !VOID_ELEMENTS.contains(&name) => open.push(name),
+            Token::Close(name) => {
+                if let Some(index) = open.iter().rposition(|open| *open == name) {
+                    open.truncate(index);
+                }
+            }
+            Token::End => break,
+            _ => (),
+        }
+    }
+
+    open.pop().filter(|name| is_component_name(name))
+}
+
+/// Every variable a template uses, in text or in attributes, even when it doesn't parse
+fn variables(source: &str) -> Vec<String> {
+    let mut lexer = Lexer::new(source);
+    let mut variables = BTreeSet::new();
+
+    loop {
+        match lexer.next_spanned().0 {
+            Token::Variable(name) => _ = variables.insert(name.to_string()),
+            Token::Open { attributes, .. } => {
+                for (_, value) in attributes {
+                    for value in parse_attribute_value(value) {
+                        if let AttributeValues::Variable(variable) = value {
+                            variables.insert(variable.name.into());
+                        }
+                    }
+                }
+            }
+            Token::End => break,
+            _ => (),
+        }
+    }
+
+    variables.remove("");
+    variables.into_iter().collect()
+}
+
+/// Turns a byte offset into a position, whose lines count from 0 and whose characters count
+/// UTF-16 code units, which is what clients use unless they're told otherwise
+fn position(source: &str, offset: usize) -> Value {
+    let before = &source[..offset.min(source.len())];
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+
+    Value::from([
+        ("line", Value::Number(before.matches('\n').count().into())),
+        ("character", Value::Number(character.into())),
+    ])
+}
+
+fn range(source: &str, span: Span) -> Value {
+    Value::from([
+        ("start", position(source, span.start)),
+        ("end", position(source, span.end)),
+    ])
+}
+
+/// Turns a position back into a byte offset, clamping it to the line it's on
+fn offset(source: &str, position: &Value) -> Option<usize> {
+    let line = position.get(&["line"])?.as_number()? as usize;
+    let character = position.get(&["character"])?.as_number()? as usize;
+
+    let line_start = match line {
+        0 => 0,
+        _ => source.match_indices('\n').nth(line - 1)?.0 + 1,
+    };
+
+    let mut units = 0;
+    for (index, char) in source[line_start..].char_indices() {
+        if units >= character || char == '\n' {
+            return Some(line_start + index);
+        }
+        units += char.len_utf16();
+    }
+
+    Some(source.len())
+}
+
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    uri.strip_prefix("file://")
+        .map(|path| PathBuf::from(decode(path)))
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+
+    uri
+}
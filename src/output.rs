@@ -0,0 +1,652 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! How rendered HTML is written out, which every way of rendering shares.
+//!
+//! Options are applied while rendering rather than after it, so they work the same when
+//! rendering straight from templates with [`crate::VelInstance::render_streaming`]. Code
+//! generated by [`crate::codegen`] always renders the HTML as it's written.
+
+use crate::{
+    ast::elements::AttributeValues,
+    lexing::{Lexer, Token},
+    loading::ComponentSource,
+    rendering::escape_into,
+    sourcemap::line_starts,
+};
+use std::{collections::HashMap, ops::Range, path::PathBuf, sync::Arc};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderOptions {
+    pub style: OutputStyle,
+    /// Wraps what every component and slot renders in comments like
+    /// `<!-- <Card> from ui/Card.vel:12, called at Page.vel:3 -->` and `<!-- </Card> -->`, so the
+    /// markup in browser devtools can be traced back to the template it came from. Components
+    /// that weren't loaded from a file are named instead, and ones built without a template
+    /// leave the line out.
+    ///
+    /// The component being rendered is wrapped too, after any `<!DOCTYPE>`, since a comment
+    /// before it changes how browsers render the page. Nothing inside raw text elements like
+    /// `<script>` is wrapped, where a comment would change what they do.
+    pub debug: bool,
+}
+
+/// How the rendered HTML is laid out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputStyle {
+    /// Exactly as the templates have it
+    #[default]
+    AsWritten,
+    /// As small as it can be while rendering the same:
+    /// - whitespace in text is collapsed to a single space, and dropped next to block elements
+    /// - attribute values are only quoted when they have to be
+    /// - boolean attributes with a value, like `disabled="disabled"`, lose it
+    /// - closing tags the HTML spec lets you leave out, like `</li>` before another `<li>`, are
+    ///   left out
+    ///
+    /// Whitespace in `<pre>`, `<textarea>`, `<script>` and `<style>` is kept as it is. Comments
+    /// never make it into the output anyway.
+    Minified,
+    /// Easy to read, for debugging. Block elements go on their own lines, indented by how many
+    /// elements they're inside of, and so does whatever comes straight after them. Whitespace
+    /// next to block elements is replaced, but inline elements, text and the raw text elements
+    /// listed under [`Self::Minified`] are left alone.
+    Pretty,
+}
+
+/// Elements that whitespace around never changes the look of
+pub(crate) const BLOCK_ELEMENTS: [&str; 44] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "caption",
+    "dd",
+    "details",
+    "dialog",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hgroup",
+    "hr",
+    "html",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "title",
+    "tr",
+    "ul",
+];
+
+/// How many spaces each level is indented by when pretty printing
+const PRETTY_INDENT: usize = 2;
+
+/// Elements whose whitespace matters, or that aren't HTML inside
+const RAW_ELEMENTS: [&str; 4] = ["pre", "textarea", "script", "style"];
+
+const BOOLEAN_ATTRIBUTES: [&str; 24] = [
+    "allowfullscreen",
+    "async",
+    "autofocus",
+    "autoplay",
+    "checked",
+    "controls",
+    "default",
+    "defer",
+    "disabled",
+    "formnovalidate",
+    "hidden",
+    "inert",
+    "ismap",
+    "itemscope",
+    "loop",
+    "multiple",
+    "muted",
+    "nomodule",
+    "novalidate",
+    "open",
+    "playsinline",
+    "readonly",
+    "required",
+    "reversed",
+];
+
+/// Elements whose closing tag can be left out when the right thing follows it
+const OPTIONAL_CLOSES: [&str; 14] = [
+    "li", "dt", "dd", "p", "rt", "rp", "optgroup", "option", "thead", "tbody", "tfoot", "tr", "td",
+    "th",
+];
+
+/// What comes after an element whose closing tag was held back
+enum Next<'a> {
+    Open(&'a str),
+    /// The element it's inside of closing
+    ParentClose(&'a str),
+}
+
+/// Whether `element`'s closing tag can be left out, going by the HTML spec's rules for optional
+/// tags
+fn close_is_optional(element: &str, next: Next) -> bool {
+    const CLOSES_P: [&str; 30] = [
+        "address",
+        "article",
+        "aside",
+        "blockquote",
+        "details",
+        "div",
+        "dl",
+        "fieldset",
+        "figcaption",
+        "figure",
+        "footer",
+        "form",
+        "h1",
+        "h2",
+        "h3",
+        "h4",
+        "h5",
+        "h6",
+        "header",
+        "hgroup",
+        "hr",
+        "main",
+        "menu",
+        "nav",
+        "ol",
+        "p",
+        "pre",
+        "section",
+        "table",
+        "ul",
+    ];
+
+    match (element, next) {
+        ("li", Next::Open(next)) => next == "li",
+        ("dt", Next::Open(next)) => matches!(next, "dt" | "dd"),
+        ("dd", Next::Open(next)) => matches!(next, "dt" | "dd"),
+        ("p", Next::Open(next)) => CLOSES_P.contains(&next),
+        ("rt" | "rp", Next::Open(next)) => matches!(next, "rt" | "rp"),
+        ("optgroup", Next::Open(next)) => next == "optgroup",
+        ("option", Next::Open(next)) => matches!(next, "option" | "optgroup"),
+        ("thead", Next::Open(next)) => matches!(next, "tbody" | "tfoot"),
+        ("tbody", Next::Open(next)) => matches!(next, "tbody" | "tfoot"),
+        ("tr", Next::Open(next)) => next == "tr",
+        ("td" | "th", Next::Open(next)) => matches!(next, "td" | "th"),
+        // A `p` has to be closed at the end of elements that can hold text but aren't blocks
+        ("p", Next::ParentClose(parent)) => !matches!(
+            parent,
+            "a" | "audio" | "del" | "ins" | "map" | "noscript" | "video"
+        ),
+        ("dt" | "thead", Next::ParentClose(_)) => false,
+        (_, Next::ParentClose(_)) => true,
+        (_, Next::Open(_)) => false,
+    }
+}
+
+/// Where each component's template came from, which debug markers point back at. It's only
+/// filled in when debugging.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Origins {
+    templates: HashMap<String, Arc<str>>,
+    /// The file each component was loaded from, when it was loaded from one
+    paths: HashMap<String, PathBuf>,
+}
+
+impl Origins {
+    /// Finds the file of each template in `paths`, or failing that asks `source`
+    pub fn new(
+        templates: HashMap<String, Arc<str>>,
+        paths: &HashMap<String, PathBuf>,
+        source: Option<&dyn ComponentSource>,
+    ) -> Self {
+        let paths = templates
+            .keys()
+            .filter_map(|name| {
+                let path = match paths.get(name) {
+                    Some(path) => path.clone(),
+                    None => source?.path(name)?,
+                };
+                Some((name.clone(), path))
+            })
+            .collect();
+
+        Self { templates, paths }
+    }
+}
+
+/// Where the first thing `template` renders starts, past any whitespace and comments
+fn markup_start(template: &str) -> usize {
+    let mut lexer = Lexer::new(template);
+
+    loop {
+        match lexer.next_spanned() {
+            (Token::Text(raw), _) if raw.trim().is_empty() => (),
+            (Token::Comment(_), _) => (),
+            (_, span) => return span.start,
+        }
+    }
+}
+
+/// Writes out HTML as it's rendered, laying it out how the [`RenderOptions`] ask for
+pub(crate) struct HtmlWriter {
+    output: String,
+    style: OutputStyle,
+    debug: bool,
+    /// How many elements the writer is inside of
+    depth: usize,
+    /// How many raw text elements the writer is inside of
+    raw: usize,
+    /// Whitespace that's only written if something other than a block element follows it
+    space: String,
+    /// A closing tag that's only written if what follows it needs it
+    close: Option<String>,
+    /// Whether the last thing written was a block element's tag, which whitespace after is
+    /// dropped
+    after_block: bool,
+    /// Where each thing being mapped for a source map started in the output, or [`None`] when
+    /// nothing's been written for it yet
+    starts: Vec<Option<usize>>,
+    origins: Origins,
+    /// Where each line starts in the templates markers have pointed at so far
+    lines: HashMap<String, Vec<usize>>,
+    /// The marker for the component being rendered, which waits for anything that has to come
+    /// first, like a `<!DOCTYPE>`
+    pending: Option<String>,
+}
+
+impl HtmlWriter {
+    pub fn new(options: &RenderOptions) -> Self {
+        Self {
+            output: String::new(),
+            style: options.style,
+            debug: options.debug,
+            depth: 0,
+            raw: 0,
+            space: String::new(),
+            close: None,
+            after_block: false,
+            starts: vec![],
+            origins: Origins::default(),
+            lines: HashMap::new(),
+            pending: None,
+        }
+    }
+
+    /// Gives debug markers the templates and files they point back at
+    pub fn with_origins(mut self, origins: Origins) -> Self {
+        self.origins = origins;
+        self
+    }
+
+    fn minified(&self) -> bool {
+        self.style == OutputStyle::Minified
+    }
+
+    /// Whether whitespace is being written as it is
+    fn verbatim(&self) -> bool {
+        self.style == OutputStyle::AsWritten || self.raw > 0
+    }
+
+    /// Writes the closing tag that was held back, unless `next` means it isn't needed
+    fn settle_close(&mut self, next: Option<Next>) {
+        if let Some(close) = self.close.take() {
+            if !next.is_some_and(|next| close_is_optional(&close, next)) {
+                self.output.push_str("</");
+                self.output.push_str(&close);
+                self.output.push('>');
+            }
+        }
+    }
+
+    /// Writes the whitespace that was held back, unless a block element is next
+    fn settle_space(&mut self, block: bool) {
+        if !block {
+            self.output.push_str(&self.space);
+        }
+        self.space.clear();
+    }
+
+    /// Gets ready to write inline content, like text or an inline element's tag
+    fn inline(&mut self) {
+        self.settle_close(None);
+        self.settle_space(false);
+
+        // Inline content after a block element starts a line of its own when pretty printing
+        if self.style == OutputStyle::Pretty && self.after_block && self.raw == 0 {
+            self.line();
+        }
+        self.after_block = false;
+        self.settle_pending();
+        self.starting();
+    }
+
+    /// Writes the marker for the component being rendered, if it's still waiting
+    fn settle_pending(&mut self) {
+        if let Some(marker) = self.pending.take() {
+            self.comment(&marker);
+        }
+    }
+
+    /// Records that what's written next is where anything being mapped that's still empty starts
+    fn starting(&mut self) {
+        for start in self.starts.iter_mut().rev() {
+            match start {
+                Some(_) => break,
+                None => *start = Some(self.output.len()),
+            }
+        }
+    }
+
+    /// Starts mapping what's written, which has to be finished with [`Self::end`]. Mappings can
+    /// be nested.
+    pub fn begin(&mut self) {
+        self.starts.push(None);
+    }
+
+    /// Finishes the last mapping [`Self::begin`] started, returning the part of the output that
+    /// was written for it, or [`None`] when nothing was
+    pub fn end(&mut self) -> Option<Range<usize>> {
+        let start = self.starts.pop()??;
+        Some(start..self.output.len())
+    }
+
+    /// Starts a new line at the current depth, unless nothing's been written yet
+    fn line(&mut self) {
+        if !self.output.is_empty() {
+            self.output.push('\n');
+            self.output
+                .extend(std::iter::repeat_n(' ', self.depth * PRETTY_INDENT));
+        }
+    }
+
+    /// Starts an opening tag, which needs [`Self::end_open_tag`] after its attributes
+    pub fn open_tag(&mut self, name: &str) {
+        let block = BLOCK_ELEMENTS.contains(&name);
+        // Nothing can come before a doctype, not even a marker
+        let pending = match name.eq_ignore_ascii_case("!doctype") {
+            true => self.pending.take(),
+            false => None,
+        };
+
+        if !block || self.verbatim() {
+            // Whitespace in between means the held back element isn't followed straight by this
+            let next = self.space.is_empty().then_some(Next::Open(name));
+            self.settle_close(next);
+            self.inline();
+        } else {
+            self.settle_close(Some(Next::Open(name)));
+            self.settle_space(true);
+            self.settle_pending();
+            if self.style == OutputStyle::Pretty {
+                self.line();
+            }
+            self.starting();
+        }
+
+        self.output.push('<');
+        self.output.push_str(name);
+        self.pending = self.pending.take().or(pending);
+    }
+
+    pub fn attribute(&mut self, key: &str, value: Option<&str>) {
+        self.output.push(' ');
+        self.starting();
+        self.output.push_str(key);
+
+        let Some(value) = value else {
+            return;
+        };
+
+        if !self.minified() {
+            self.output.push_str("=\"");
+            escape_into(&mut self.output, value);
+            self.output.push('"');
+            return;
+        }
+
+        if BOOLEAN_ATTRIBUTES.contains(&key) && (value.is_empty() || value == key) {
+            return;
+        }
+
+        // Escaping leaves nothing that'd end the value early but whitespace, `=` and backticks
+        let quoted = value.is_empty()
+            || value
+                .chars()
+                .any(|char| char.is_ascii_whitespace() || matches!(char, '=' | '`'));
+
+        self.output.push('=');
+        if quoted {
+            self.output.push('"');
+        }
+        escape_into(&mut self.output, value);
+        if quoted {
+            self.output.push('"');
+        }
+    }
+
+    /// Finishes an opening tag. Void elements are never closed.
+    pub fn end_open_tag(&mut self, name: &str, void: bool) {
+        self.output.push('>');
+        self.after_block = BLOCK_ELEMENTS.contains(&name);
+
+        if !void {
+            self.depth += 1;
+
+            if RAW_ELEMENTS.contains(&name) {
+                self.raw += 1;
+            }
+        }
+    }
+
+    pub fn close_tag(&mut self, name: &str) {
+        let block = BLOCK_ELEMENTS.contains(&name);
+        self.depth = self.depth.saturating_sub(1);
+
+        if !block || self.verbatim() {
+            let next = self.space.is_empty().then_some(Next::ParentClose(name));
+            self.settle_close(next);
+            self.inline();
+        } else {
+            self.settle_close(Some(Next::ParentClose(name)));
+            self.settle_space(true);
+            if self.style == OutputStyle::Pretty {
+                self.line();
+            }
+            self.starting();
+        }
+
+        if RAW_ELEMENTS.contains(&name) {
+            self.raw = self.raw.saturating_sub(1);
+        }
+
+        // Nothing after the end of the output is known, so the last one is always written
+        match self.minified() && OPTIONAL_CLOSES.contains(&name) {
+            true => self.close = Some(name.to_string()),
+            false => {
+                self.output.push_str("</");
+                self.output.push_str(name);
+                self.output.push('>');
+            }
+        }
+
+        self.after_block = block;
+    }
+
+    /// Writes text that's already HTML, like the text in a template
+    pub fn text(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+
+        if self.verbatim() {
+            self.inline();
+            self.output.push_str(text);
+            return;
+        }
+
+        for char in text.chars() {
+            if !char.is_whitespace() {
+                self.inline();
+                self.output.push(char);
+                continue;
+            }
+
+            match (self.after_block, self.style) {
+                (true, _) => (),
+                (false, OutputStyle::Minified) => {
+                    if self.space.is_empty() {
+                        self.space.push(' ');
+                    }
+                }
+                (false, _) => self.space.push(char),
+            }
+        }
+    }
+
+    /// Writes text that isn't HTML, escaping it
+    pub fn escaped(&mut self, text: &str) {
+        if self.verbatim() {
+            self.inline();
+            escape_into(&mut self.output, text);
+            return;
+        }
+
+        let mut escaped = String::with_capacity(text.len());
+        escape_into(&mut escaped, text);
+        self.text(&escaped);
+    }
+
+    /// Marks where what the component being rendered renders starts, when debugging. The marker
+    /// waits for anything that has to come first, like a `<!DOCTYPE>`.
+    pub fn open_root(&mut self, name: &str) {
+        if self.debug {
+            let start = self.markup_start(name);
+            self.pending = Some(format!("<{name}> from {}", self.locate(name, start)));
+        }
+    }
+
+    /// Marks where what a component renders starts, when debugging, alongside where in which
+    /// component's template it was called when that's known
+    pub fn open_component(&mut self, name: &str, call: Option<(&str, usize)>) {
+        self.marker(|writer| {
+            let start = writer.markup_start(name);
+            let mut marker = format!("<{name}> from {}", writer.locate(name, start));
+
+            if let Some((caller, offset)) = call {
+                marker.push_str(", called at ");
+                marker.push_str(&writer.locate(caller, Some(offset)));
+            }
+            marker
+        });
+    }
+
+    pub fn close_component(&mut self, name: &str) {
+        self.marker(|_| format!("</{name}>"));
+    }
+
+    /// Marks where what a slot of `component` renders starts, when debugging, alongside where
+    /// it's declared when that's known. The slot's name is shown as it's written, with any
+    /// variables still in braces.
+    pub fn open_slot(&mut self, component: &str, name: &[AttributeValues], offset: Option<usize>) {
+        self.marker(|writer| {
+            let mut marker = String::from("<Slot name=\"");
+            for part in name {
+                match part {
+                    AttributeValues::Text(text) => marker.push_str(&text.value),
+                    AttributeValues::Variable(variable) => {
+                        marker.push('{');
+                        marker.push_str(&variable.name);
+                        marker.push('}');
+                    }
+                }
+            }
+            marker.push_str("\"> of <");
+            marker.push_str(component);
+            marker.push_str("> from ");
+            marker.push_str(&writer.locate(component, offset));
+            marker
+        });
+    }
+
+    pub fn close_slot(&mut self, component: &str) {
+        self.marker(|_| format!("</Slot> of <{component}>"));
+    }
+
+    /// Where the first thing `component` renders starts in its template, if it has one
+    fn markup_start(&self, component: &str) -> Option<usize> {
+        self.origins
+            .templates
+            .get(component)
+            .map(|template| markup_start(template))
+    }
+
+    /// Says where `offset` of `component`'s template is, like `ui/Card.vel:3`. It falls back to
+    /// the component's name without a file, and leaves the line out without a template.
+    fn locate(&mut self, component: &str, offset: Option<usize>) -> String {
+        let mut location = match self.origins.paths.get(component) {
+            Some(path) => path.display().to_string(),
+            None => component.to_string(),
+        };
+
+        if let (Some(template), Some(offset)) = (self.origins.templates.get(component), offset) {
+            let starts = self
+                .lines
+                .entry(component.to_string())
+                .or_insert_with(|| line_starts(template));
+            location.push(':');
+            location.push_str(&starts.partition_point(|start| *start <= offset).to_string());
+        }
+
+        location
+    }
+
+    /// Writes a comment for debugging, which is only made when it's needed
+    fn marker(&mut self, text: impl FnOnce(&mut Self) -> String) {
+        if !self.debug || self.raw > 0 {
+            return;
+        }
+
+        let text = text(self);
+        self.inline();
+        self.comment(&text);
+    }
+
+    fn comment(&mut self, text: &str) {
+        self.output.push_str("<!-- ");
+        // Slot names can have anything in them, including what would end the comment early
+        self.output.push_str(&text.replace("--", "- -"));
+        self.output.push_str(" -->");
+    }
+
+    pub fn finish(mut self) -> String {
+        self.settle_pending();
+        self.settle_close(None);
+        self.settle_space(false);
+        self.output
+    }
+}
@@ -4,35 +4,48 @@
 
 use crate::{
     ast::{elements::*, Dom},
+    lexing::{offset_of, unescape_into, Lexer, RawAttribute, ReadLexer, Token},
+    loading::LoadingError,
     ComponentsCache, LazyDom,
 };
-use frames::PageFrame;
+use frames::{Page, PageFrame};
 use std::{
-    collections::HashMap,
-    ops::Deref,
-    sync::{Arc, Mutex},
-    vec::IntoIter,
+    cell::{Cell, RefCell},
+    collections::{BTreeSet, HashMap},
+    fmt,
+    io::Read,
+    rc::Rc,
+    sync::Arc,
 };
 
 /// A lazily evaluated static of all HTML5 void elements as of 2025-04-17
-const VOID_ELEMENTS: [&str; 15] = [
+pub(crate) const VOID_ELEMENTS: [&str; 15] = [
     "!DOCTYPE", "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta",
     "param", "source", "track", "wbr",
 ];
 
-// Wow babes first polyfill!!
-/// HACK: soooooooo, Rust std has an iterator called IntoChars BUTTT it's nightly so we have to
-/// handroll our own until it becomes stable as I aint forcing someone to use nightly for my
-/// shitass templating language
-type IntoChars = IntoIter<char>;
-
-trait TemporaryBSUntilIntoCharsIsNotUnstable {
-    fn into_chars(self) -> IntoChars;
+/// A range of bytes in a component's template
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
 }
 
-impl TemporaryBSUntilIntoCharsIsNotUnstable for String {
-    fn into_chars(self) -> IntoChars {
-        self.chars().collect::<Vec<char>>().into_iter()
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Gets the line and column the span starts at in `source`, both counting from 1. Columns
+    /// count chars rather than bytes.
+    pub fn line_column(&self, source: &str) -> (usize, usize) {
+        let before = &source[..self.start.min(source.len())];
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+
+        (
+            before.matches('\n').count() + 1,
+            before[line_start..].chars().count() + 1,
+        )
     }
 }
 
@@ -40,7 +53,58 @@ impl TemporaryBSUntilIntoCharsIsNotUnstable for String {
 /// error in theory but wont as it's an unreachable!() state
 mod frames {
 
-    use super::{Arc, Document, Element, IntoChars, Mpreggable, Mutex};
+    use super::{offset_of, unescape_into, Cell, Lexer, Read, ReadLexer, RefCell, Token};
+    use super::{
+        Arc, Document, Element, Mpreggable, ParsingErrorKind, Rc, Span, TemplateStr, Text,
+    };
+    use std::io;
+
+    /// A component's template, shared by every frame parsing a part of it
+    pub enum Page<'r> {
+        Template {
+            source: Arc<str>,
+            /// How far into the template parsing has got
+            position: Cell<usize>,
+        },
+        Reader(RefCell<ReadLexer<Box<dyn Read + 'r>>>),
+    }
+
+    impl Page<'_> {
+        /// Hands the next token of the template to `f`
+        pub fn next_token<T>(&self, f: impl FnOnce(Token, Span) -> T) -> io::Result<T> {
+            match self {
+                Page::Template { source, position } => {
+                    let mut lexer = Lexer::within(source, Span::new(position.get(), source.len()));
+                    let (token, span) = lexer.next_spanned();
+                    position.set(lexer.position());
+
+                    Ok(f(token, span))
+                }
+                Page::Reader(lexer) => lexer.borrow_mut().next_spanned(f),
+            }
+        }
+
+        /// The whole template, when it's all in memory
+        fn source(&self) -> Option<&Arc<str>> {
+            match self {
+                Page::Template { source, .. } => Some(source),
+                Page::Reader(_) => None,
+            }
+        }
+
+        /// Keeps `part`, which has to have been lexed from the page, as a range of the template
+        /// when it's in memory. Templates being read only hold onto what's being lexed, so their
+        /// parts have to be copied.
+        pub fn slice(&self, part: &str) -> TemplateStr {
+            match self.source() {
+                Some(source) => {
+                    let start = offset_of(source, part);
+                    TemplateStr::new(source, Span::new(start, start + part.len()))
+                }
+                None => part.into(),
+            }
+        }
+    }
 
     #[derive(PartialEq, Eq)]
     enum PushTarget {
@@ -49,42 +113,118 @@ mod frames {
         Slot,
     }
 
-    pub struct PageFrame {
+    pub struct PageFrame<'r> {
         mpregee: Mpreggable,
-        pub page: Arc<Mutex<IntoChars>>,
+        /// The component whose template the page belongs to
+        pub component: Arc<str>,
+        pub page: Rc<Page<'r>>,
+        /// Where the element being parsed was opened
+        pub open: Span,
         push_target: PushTarget,
-        pub string_buffer: String,
+        /// Text that had to be copied out of the template to be joined up or unescaped
+        string_buffer: String,
+        /// Text that's still only a range of the template, as it hasn't needed copying yet
+        text_span: Option<Span>,
     }
 
-    impl PageFrame {
-        pub fn new_document(name: String, page: IntoChars) -> Self {
+    impl<'r> PageFrame<'r> {
+        pub fn new_document(name: String, page: Page<'r>) -> Self {
             PageFrame {
+                component: Arc::from(name.as_str()),
                 mpregee: Mpreggable::Document(Document::new(name)),
-                page: Arc::new(Mutex::new(page)),
+                page: Rc::new(page),
+                open: Span::default(),
                 push_target: PushTarget::Children,
                 string_buffer: String::new(),
+                text_span: None,
             }
         }
 
-        pub fn new_slot(document: Document, page: Arc<Mutex<IntoChars>>) -> Self {
+        pub fn new_slot(
+            document: Document,
+            component: Arc<str>,
+            page: Rc<Page<'r>>,
+            open: Span,
+        ) -> Self {
             PageFrame {
                 mpregee: Mpreggable::Document(document),
+                component,
                 page,
+                open,
                 push_target: PushTarget::Slot,
                 string_buffer: String::new(),
+                text_span: None,
             }
         }
 
-        pub fn new_child(element: Mpreggable, page: Arc<Mutex<IntoChars>>) -> Self {
+        pub fn new_child(
+            element: Mpreggable,
+            component: Arc<str>,
+            page: Rc<Page<'r>>,
+            open: Span,
+        ) -> Self {
             PageFrame {
                 mpregee: element,
+                component,
                 page,
+                open,
                 push_target: PushTarget::Children,
                 string_buffer: String::new(),
+                text_span: None,
+            }
+        }
+
+        /// Returns whether this frame is the body of the component called `name`
+        pub fn is_component(&self, name: &str) -> bool {
+            self.push_target == PushTarget::Children
+                && matches!(&self.mpregee, Mpreggable::Document(document) if document.name == name)
+        }
+
+        /// Adds a run of text from the page to the text being collected. A run that has nothing
+        /// next to it and no escapes in it is kept as a range of the template, without copying.
+        pub fn push_text(&mut self, raw: &str) {
+            let page = self.page.clone();
+
+            match page.source() {
+                Some(source)
+                    if self.string_buffer.is_empty()
+                        && self.text_span.is_none()
+                        && !raw.contains('\\') =>
+                {
+                    let start = offset_of(source, raw);
+                    self.text_span = Some(Span::new(start, start + raw.len()));
+                }
+                source => {
+                    if let (Some(span), Some(source)) = (self.text_span.take(), source) {
+                        self.string_buffer.push_str(&source[span.start..span.end]);
+                    }
+                    unescape_into(&mut self.string_buffer, raw);
+                }
             }
         }
 
+        /// Turns any text collected so far into a Text element, so it stays in order with the
+        /// elements around it
+        fn flush_text(&mut self) {
+            let value = match (self.text_span.take(), self.page.source()) {
+                (Some(span), Some(source)) if span.start < span.end => {
+                    TemplateStr::new(source, span)
+                }
+                _ if !self.string_buffer.is_empty() => {
+                    std::mem::take(&mut self.string_buffer).into()
+                }
+                _ => return,
+            };
+
+            self.push_target_element(Element::Text(Text { value }));
+        }
+
         pub fn push_element(&mut self, element: Element) {
+            self.flush_text();
+            self.push_target_element(element);
+        }
+
+        fn push_target_element(&mut self, element: Element) {
             match self.push_target {
                 PushTarget::Slot => {
                     if self.mpregee.try_add_slot(element).is_err() {
@@ -95,42 +235,124 @@ mod frames {
             }
         }
 
-        pub fn try_take_my_kids(&mut self, document: Document) -> Result<(), ()> {
-            if self.push_target == PushTarget::Slot {
-                if let Mpreggable::Document(slot_document) = &mut self.mpregee {
-                    slot_document.children = document.children.clone();
-                    return Ok(());
+        pub fn take_my_kids(&mut self, document: Document) {
+            match &mut self.mpregee {
+                Mpreggable::Document(slot_document) if self.push_target == PushTarget::Slot => {
+                    slot_document.children = document.children;
                 }
+                _ => unreachable!("Component bodies are only ever parsed straight after their call site's frame. Please check the logic in parse TwT."),
+            }
+        }
+
+        /// Closes a component body once its page has run out, as component templates aren't
+        /// wrapped in their own tag
+        pub fn try_finish(mut self) -> Result<Document, ParsingErrorKind> {
+            self.flush_text();
+
+            match self.mpregee {
+                Mpreggable::Document(document) if self.push_target == PushTarget::Children => {
+                    Ok(document)
+                }
+                // Anything else reaching the end of the page was never closed
+                mpregee => Err(ParsingErrorKind::UnclosedElement(
+                    mpregee.get_name().to_string(),
+                )),
             }
-            Err(())
         }
 
         pub fn try_close(
-            self,
+            mut self,
             name: String,
-            parent_frame: Option<&mut PageFrame>,
-        ) -> Result<Option<Document>, ()> {
+            parent_frame: Option<&mut PageFrame<'r>>,
+        ) -> Result<Option<Document>, ParsingErrorKind> {
+            self.flush_text();
+
             if self.mpregee.get_name() == name {
+                let unexpected = || ParsingErrorKind::UnexpectedClosingTag(name.clone());
+
                 match self.mpregee {
                     Mpreggable::Document(document) => match self.push_target {
                         PushTarget::Children => return Ok(Some(document)),
                         PushTarget::Slot => parent_frame
-                            .ok_or(())?
+                            .ok_or_else(unexpected)?
                             .push_element(Element::Document(document)),
                     },
-                    Mpreggable::Slot(_) | Mpreggable::Node(_) => {
-                        parent_frame.ok_or(())?.push_element(self.mpregee.into())
-                    }
+                    Mpreggable::Slot(_) | Mpreggable::Node(_) => parent_frame
+                        .ok_or_else(unexpected)?
+                        .push_element(self.mpregee.into()),
                 }
                 Ok(None)
+            } else if matches!(self.mpregee, Mpreggable::Document(_))
+                && self.push_target == PushTarget::Children
+            {
+                // Nothing in the component's body is open
+                Err(ParsingErrorKind::UnexpectedClosingTag(name))
             } else {
-                // Names dont match
-                Err(())
+                Err(ParsingErrorKind::MismatchedClosingTag {
+                    expected: self.mpregee.get_name().to_string(),
+                    found: name,
+                })
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ParsingError {
+    /// The component whose template the error is in
+    pub component: String,
+    pub kind: ParsingErrorKind,
+    /// Where in the component's template the error is. Components that couldn't be found at all
+    /// have an empty span at the start.
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ParsingErrorKind {
+    /// A component was used that isn't registered and that the source doesn't have
+    UnknownComponent(String),
+    /// A component ended up using itself, which would never finish rendering
+    RecursiveComponent(String),
+    /// A closing tag didn't match the element that was open
+    MismatchedClosingTag { expected: String, found: String },
+    /// A closing tag was found without any element open for it to close
+    UnexpectedClosingTag(String),
+    /// The template ended while an element was still open
+    UnclosedElement(String),
+}
+
+impl ParsingError {
+    pub(crate) fn new(component: &str, kind: ParsingErrorKind, span: Span) -> Self {
+        Self {
+            component: component.to_string(),
+            kind,
+            span,
+        }
+    }
+}
+
+impl fmt::Display for ParsingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "in `{}`: ", self.component)?;
+
+        match &self.kind {
+            ParsingErrorKind::UnknownComponent(name) => write!(f, "unknown component `{name}`"),
+            ParsingErrorKind::RecursiveComponent(name) => {
+                write!(f, "`{name}` ends up using itself")
+            }
+            ParsingErrorKind::MismatchedClosingTag { expected, found } => {
+                write!(f, "expected `</{expected}>` but found `</{found}>`")
             }
+            ParsingErrorKind::UnexpectedClosingTag(name) => {
+                write!(f, "`</{name}>` doesn't close anything")
+            }
+            ParsingErrorKind::UnclosedElement(name) => write!(f, "`<{name}>` is never closed"),
         }
     }
 }
 
+impl std::error::Error for ParsingError {}
+
 /// Used to determine the current element in the frame stack.
 /// Only elements that can gain kids can be the current element.
 enum Mpreggable {
@@ -166,7 +388,7 @@ impl Mpreggable {
                         .attributes
                         .remove("slot")
                         .unwrap_or(vec![AttributeValues::Text(Text {
-                            value: "default".to_string(),
+                            value: "default".into(),
                         })])
                 }
 
@@ -174,7 +396,7 @@ impl Mpreggable {
                     node.attributes
                         .remove("slot")
                         .unwrap_or(vec![AttributeValues::Text(Text {
-                            value: "default".to_string(),
+                            value: "default".into(),
                         })])
                 }
 
@@ -182,19 +404,19 @@ impl Mpreggable {
                     slot.attributes
                         .remove("slot")
                         .unwrap_or(vec![AttributeValues::Text(Text {
-                            value: "default".to_string(),
+                            value: "default".into(),
                         })])
                 }
 
                 Element::Text(_) | Element::Variable(_) => vec![AttributeValues::Text(Text {
-                    value: "default".to_string(),
+                    value: "default".into(),
                 })],
 
                 Element::Void(void) => {
                     void.attributes
                         .remove("slot")
                         .unwrap_or(vec![AttributeValues::Text(Text {
-                            value: "default".to_string(),
+                            value: "default".into(),
                         })])
                 }
             };
@@ -231,121 +453,219 @@ impl From<Mpreggable> for Element {
 // - [X] Fix bug where you can only parse one element
 // - [ ] Variable passing (hx-params, slot)
 
-fn parse_variable(
-    char_iterator: &mut IntoChars, /*, inputs: &HashMap<String, String>*/
-) -> Variable {
-    Variable {
-        name: char_iterator.take_while(|char| char != &'}').collect(),
-    }
+/// Splits an attribute value into its text and `{variable}` parts
+pub(crate) fn parse_attribute_value(value: &str) -> Vec<AttributeValues> {
+    attribute_value(value, |part| TemplateStr::from(part))
 }
 
-// WARN: Wtf is this shit ass code
-fn parse_attributes(char_iterator: &mut IntoChars) -> Attributes {
-    HashMap::from_iter(
-        char_iterator
-            .take_while(|char| char != &'>')
-            .collect::<String>()
-            .split_whitespace()
-            .map(|sub_string| {
-                if let Some((key, value)) = sub_string.split_once('=') {
-                    let mut parsed_values = vec![];
-                    let mut string_buffer = String::new();
-
-                    while let Some(char) = value.chars().next() {
-                        match char {
-                            '{' => {
-                                if !string_buffer.is_empty() {
-                                    parsed_values.push(AttributeValues::Text(Text {
-                                        value: std::mem::take(&mut string_buffer),
-                                    }));
-                                }
-
-                                parsed_values
-                                    .push(AttributeValues::Variable(parse_variable(char_iterator)))
-                            }
-                            char => string_buffer.push(char),
-                        }
-                    }
+/// Splits an attribute value up like [`parse_attribute_value`], making each part that didn't
+/// need unescaping with `slice`
+fn attribute_value(value: &str, slice: impl Fn(&str) -> TemplateStr) -> Vec<AttributeValues> {
+    let mut parsed_values = vec![];
+    // Where the text since the last variable starts, and a copy of it once it has an escape
+    let mut text_start = 0;
+    let mut unescaped: Option<String> = None;
+    let mut index = 0;
+
+    let push_text = |parsed_values: &mut Vec<_>, raw: &str, unescaped: Option<String>| {
+        let value = match unescaped {
+            Some(unescaped) if !unescaped.is_empty() => unescaped.into(),
+            None if !raw.is_empty() => slice(raw),
+            _ => return,
+        };
+        parsed_values.push(AttributeValues::Text(Text { value }));
+    };
 
-                    if !string_buffer.is_empty() {
-                        parsed_values.push(AttributeValues::Text(Text {
-                            value: std::mem::take(&mut string_buffer),
-                        }));
-                    }
+    while let Some(char) = value[index..].chars().next() {
+        match char {
+            '{' => {
+                push_text(
+                    &mut parsed_values,
+                    &value[text_start..index],
+                    unescaped.take(),
+                );
+
+                let name_start = index + 1;
+                let name_end = value[name_start..]
+                    .find('}')
+                    .map_or(value.len(), |end| name_start + end);
+
+                parsed_values.push(AttributeValues::Variable(Variable {
+                    name: slice(&value[name_start..name_end]),
+                }));
+
+                index = (name_end + 1).min(value.len());
+                text_start = index;
+            }
+            '\\' => {
+                let unescaped =
+                    unescaped.get_or_insert_with(|| value[text_start..index].to_string());
+                let escaped = value[index + 1..].chars().next();
 
-                    (key.to_string(), parsed_values)
-                } else {
-                    (sub_string.to_string(), vec![])
+                unescaped.extend(escaped);
+                index += 1 + escaped.map_or(0, char::len_utf8);
+            }
+            char => {
+                if let Some(unescaped) = &mut unescaped {
+                    unescaped.push(char);
                 }
-            }),
-    )
+                index += char.len_utf8();
+            }
+        }
+    }
+
+    push_text(&mut parsed_values, &value[text_start..], unescaped);
+
+    parsed_values
+}
+
+fn parse_attributes(raw: Vec<RawAttribute>, page: &Page) -> Attributes {
+    raw.into_iter()
+        .map(|(key, value)| {
+            let values = attribute_value(value, |part| page.slice(part));
+            (page.slice(key), values)
+        })
+        .collect()
+}
+
+/// Components are told apart from HTML elements by being capitalised. Namespaced components, like
+/// `ui.Button`, only need their last segment to be capitalised.
+pub(crate) fn is_component_name(name: &str) -> bool {
+    name.rsplit('.')
+        .next()
+        .and_then(|segment| segment.chars().next())
+        .is_some_and(char::is_uppercase)
 }
 
 enum ParsingAction {
     ParseInnards(Mpreggable),
     Mpreg(Element),
     Close(String),
-    ExhaustedIntoChar,
+    /// The page ran out
+    End,
 }
 
-fn parse_element(char_iterator: &mut IntoChars) -> ParsingAction {
-    let mut open = true;
-
-    let name: String = char_iterator
-        .take_while(|char| {
-            if char == &'>' {
-                open = false;
-                false
-            } else {
-                char != &' '
-            }
-        })
-        .collect();
-
-    let mut attributes = Attributes::new();
-
-    if open {
-        attributes = parse_attributes(char_iterator);
-    };
+fn parse_element(
+    name: &str,
+    attributes: Vec<RawAttribute>,
+    page: &Page,
+    location: Location,
+) -> ParsingAction {
+    let location = Some(location);
+    let name = name.to_string();
+    let mut attributes = parse_attributes(attributes, page);
 
     // Determine the kind
-    match name.chars().next() {
-        Some(_) if name.as_str() == "Slot" => {
+    match name.as_str() {
+        "Slot" => {
             let name = attributes
                 .remove("name")
                 .unwrap_or(vec![AttributeValues::Text(Text {
-                    value: "default".to_string(),
+                    value: "default".into(),
                 })]);
 
             ParsingAction::ParseInnards(Mpreggable::Slot(Slot {
                 name,
                 attributes,
                 children: vec![],
+                location,
             }))
         }
-        Some(char) if char.is_uppercase() => {
+        _ if is_component_name(&name) => {
             ParsingAction::ParseInnards(Mpreggable::Document(Document {
                 name,
                 attributes,
                 slot_content: HashMap::new(),
                 children: vec![],
+                location,
             }))
         }
-        Some('/') => ParsingAction::Close(name[1..].to_string()),
-        Some(_) if VOID_ELEMENTS.contains(&name.as_str()) => {
+        _ if VOID_ELEMENTS.contains(&name.as_str()) => {
             ParsingAction::Mpreg(Element::Void(Void { name, attributes }))
         }
-        Some(_) => ParsingAction::ParseInnards(Mpreggable::Node(Node {
+        _ => ParsingAction::ParseInnards(Mpreggable::Node(Node {
             name,
             attributes,
             children: vec![],
         })),
-        None => ParsingAction::ExhaustedIntoChar,
     }
 }
 
-pub(crate) fn parse(component: &str, components: &mut ComponentsCache) -> Result<(), ()> {
-    let page = if let LazyDom::Unparsed(page) = components.get_mut(component).ok_or(())? {
+/// A component the parser came across, as handed back by a [`ComponentStore`]
+pub(crate) enum Lookup {
+    Parsed(Dom),
+    Unparsed(Arc<str>),
+}
+
+/// Where the parser gets the components it comes across from, and where it puts the ones it
+/// finishes parsing
+pub(crate) trait ComponentStore {
+    fn lookup(&mut self, name: &str) -> Option<Lookup>;
+
+    fn store(&mut self, name: String, dom: Dom);
+}
+
+impl ComponentStore for ComponentsCache {
+    fn lookup(&mut self, name: &str) -> Option<Lookup> {
+        Some(match self.get_mut(name)? {
+            LazyDom::Parsed(dom) => Lookup::Parsed(dom.clone()),
+            LazyDom::Unparsed(page) => Lookup::Unparsed(page.clone()),
+        })
+    }
+
+    fn store(&mut self, name: String, dom: Dom) {
+        self.insert(name, LazyDom::Parsed(dom));
+    }
+}
+
+/// Lists the components a template uses by scanning it for their tags, without parsing it
+pub(crate) fn referenced_components(page: &str) -> BTreeSet<String> {
+    let mut components = BTreeSet::new();
+    let mut lexer = Lexer::new(page);
+
+    loop {
+        match lexer.next_token() {
+            Token::Open { name, .. } if is_component_name(name) => {
+                components.insert(name.to_string());
+            }
+            Token::End => return components,
+            _ => (),
+        }
+    }
+}
+
+/// Stores a finished component body and hands its children over to the call site it was parsed
+/// for, if there is one. Returns true once the component we were asked to parse is done.
+fn finish_document(
+    document: Document,
+    frame_stack: &mut [PageFrame<'_>],
+    components: &mut impl ComponentStore,
+) -> bool {
+    let done = match frame_stack.last_mut() {
+        Some(frame) => {
+            frame.take_my_kids(document.clone());
+            false
+        }
+        None => true,
+    };
+
+    components.store(document.name.clone(), Dom::new(document));
+
+    done
+}
+
+/// Parses `component`, storing it and every component it uses that hadn't been parsed yet
+pub(crate) fn parse(
+    component: &str,
+    components: &mut impl ComponentStore,
+) -> Result<(), ParsingError> {
+    let page = if let Lookup::Unparsed(page) = components.lookup(component).ok_or_else(|| {
+        ParsingError::new(
+            component,
+            ParsingErrorKind::UnknownComponent(component.to_string()),
+            Span::default(),
+        )
+    })? {
         page
     } else {
         #[cfg(debug_assertions)]
@@ -356,88 +676,154 @@ pub(crate) fn parse(component: &str, components: &mut ComponentsCache) -> Result
         return Ok(());
     };
 
-    let mut frame_stack = vec![PageFrame::new_document(
-        component.to_string(),
-        std::mem::take(page).into_chars(),
-    )];
+    let page = Page::Template {
+        source: page,
+        position: Cell::new(0),
+    };
+
+    parse_page(component, page, components).map_err(|error| match error {
+        LoadingError::Parsing(error) => error,
+        _ => unreachable!("templates that are already in memory can't fail to be read"),
+    })
+}
+
+/// Parses `component` from a reader as it's read, rather than reading all of it first. The
+/// components it uses are taken from `components` like usual.
+pub(crate) fn parse_reader<'r>(
+    component: &str,
+    reader: impl Read + 'r,
+    components: &mut impl ComponentStore,
+) -> Result<(), LoadingError> {
+    let page = Page::Reader(RefCell::new(ReadLexer::new(Box::new(reader))));
+
+    parse_page(component, page, components)
+}
+
+fn parse_page(
+    component: &str,
+    page: Page,
+    components: &mut impl ComponentStore,
+) -> Result<(), LoadingError> {
+    let mut frame_stack = vec![PageFrame::new_document(component.to_string(), page)];
 
     'frame_loop: while let Some(mut frame) = frame_stack.pop() {
         let page = frame.page.clone();
-        let mut char_iterator = page.lock().expect("char_iterator mutex poisoned. I don't know what we could do here so it's best to panic as it's unrecoverable at the moment.");
-
-        'char_loop: while let Some(char) = char_iterator.next() {
-            match char {
-                '{' => frame.push_element(Element::Variable(parse_variable(&mut char_iterator))),
-                '<' => match parse_element(&mut char_iterator) {
-                    ParsingAction::Mpreg(element) => frame.push_element(element),
-                    ParsingAction::ParseInnards(element) => {
-                        let page = frame.page.clone();
-
-                        // Unlocks the char_iterator Mutex
-                        drop(char_iterator);
-
-                        frame_stack.push(frame);
-
-                        if let Mpreggable::Document(document) = element {
-                            match components.get_mut(&document.name) {
-                                Some(LazyDom::Parsed(dom)) => {
-                                    frame_stack
-                                        .push(PageFrame::new_slot(dom.tree.deref().clone(), page));
-                                }
-                                Some(LazyDom::Unparsed(dom_page)) => {
-                                    let name = document.name.clone();
-                                    frame_stack.push(PageFrame::new_slot(document, page));
-                                    frame_stack.push(PageFrame::new_document(
-                                        name,
-                                        std::mem::take(dom_page).into_chars(),
-                                    ));
-                                }
-                                None => return Err(()),
-                            }
-                        } else {
-                            frame_stack.push(PageFrame::new_child(element, page));
-                        }
 
-                        continue 'frame_loop;
+        loop {
+            let (action, span) = page.next_token(|token, span| {
+                let action = match token {
+                    Token::Text(raw) => {
+                        frame.push_text(raw);
+                        None
                     }
-                    ParsingAction::Close(name) => {
-                        let document = frame.try_close(name, frame_stack.last_mut())?;
-
-                        drop(char_iterator);
-
-                        if let Some(document) = document {
-                            match frame_stack.last_mut() {
-                                Some(frame) => {
-                                    frame.try_take_my_kids(document.clone())?;
-                                    components.insert(
-                                        document.name.clone(),
-                                        LazyDom::Parsed(Dom::new(document)),
-                                    );
-                                }
-                                None => {
-                                    components.insert(
-                                        document.name.clone(),
-                                        LazyDom::Parsed(Dom::new(document)),
-                                    );
-                                    return Ok(());
-                                }
-                            }
+                    Token::Variable(name) => {
+                        let name = frame.page.slice(name);
+                        frame.push_element(Element::Variable(Variable { name }));
+                        None
+                    }
+                    Token::Open { name, attributes } => {
+                        let location = Location {
+                            component: frame.component.clone(),
+                            span,
+                        };
+                        Some(parse_element(name, attributes, &frame.page, location))
+                    }
+                    Token::Close(name) => Some(ParsingAction::Close(name.to_string())),
+                    Token::Comment(_) => None,
+                    Token::End => Some(ParsingAction::End),
+                };
+
+                (action, span)
+            })?;
+
+            let Some(action) = action else {
+                continue;
+            };
+
+            match action {
+                ParsingAction::Mpreg(element) => frame.push_element(element),
+                ParsingAction::ParseInnards(element) => {
+                    let page = frame.page.clone();
+                    let component = frame.component.clone();
+
+                    frame_stack.push(frame);
+
+                    if let Mpreggable::Document(mut document) = element {
+                        // A component that uses itself would never finish parsing
+                        if frame_stack
+                            .iter()
+                            .any(|frame| frame.is_component(&document.name))
+                        {
+                            return Err(ParsingError::new(
+                                &component,
+                                ParsingErrorKind::RecursiveComponent(document.name),
+                                span,
+                            )
+                            .into());
                         }
 
-                        continue 'frame_loop;
-                    }
-                    ParsingAction::ExhaustedIntoChar => break 'char_loop,
-                },
-                '\\' => {
-                    if let Some(char) = char_iterator.next() {
-                        frame.string_buffer.push(char)
+                        let Some(lookup) = components.lookup(&document.name) else {
+                            return Err(ParsingError::new(
+                                &component,
+                                ParsingErrorKind::UnknownComponent(document.name),
+                                span,
+                            )
+                            .into());
+                        };
+
+                        match lookup {
+                            Lookup::Parsed(dom) => {
+                                document.children = dom.tree.children.clone();
+                                frame_stack
+                                    .push(PageFrame::new_slot(document, component, page, span));
+                            }
+                            Lookup::Unparsed(dom_page) => {
+                                let name = document.name.clone();
+                                frame_stack
+                                    .push(PageFrame::new_slot(document, component, page, span));
+                                frame_stack.push(PageFrame::new_document(
+                                    name,
+                                    Page::Template {
+                                        source: dom_page,
+                                        position: Cell::new(0),
+                                    },
+                                ));
+                            }
+                        }
                     } else {
-                        break 'char_loop;
+                        frame_stack.push(PageFrame::new_child(element, component, page, span));
                     }
+
+                    continue 'frame_loop;
+                }
+                ParsingAction::Close(name) => {
+                    let component = frame.component.clone();
+                    let document = frame
+                        .try_close(name, frame_stack.last_mut())
+                        .map_err(|kind| ParsingError::new(&component, kind, span))?;
+
+                    if let Some(document) = document {
+                        if finish_document(document, &mut frame_stack, components) {
+                            return Ok(());
+                        }
+                    }
+
+                    continue 'frame_loop;
                 }
-                other_char => frame.string_buffer.push(other_char),
+                ParsingAction::End => break,
             }
         }
+
+        // The page ran out before the frame was closed, which is only fine for component bodies
+        let component = frame.component.clone();
+        let open = frame.open;
+        let document = frame
+            .try_finish()
+            .map_err(|kind| ParsingError::new(&component, kind, open))?;
+
+        if finish_document(document, &mut frame_stack, components) {
+            return Ok(());
+        }
     }
 
     Ok(())
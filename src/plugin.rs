@@ -0,0 +1,70 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Plugins that change what's rendered, registered once on an instance rather than passed to
+//! every render.
+//!
+//! Plugins run in the order they were added, each seeing what the ones before it left behind.
+//! They only run when rendering from a Dom, so [`crate::VelInstance::render_streaming`] and
+//! [`crate::VelInstance::render_with_source_map`] fail with
+//! [`crate::RenderingError::PluginsUnsupported`] once any have been added, and code from
+//! [`crate::codegen`] skips them.
+
+use crate::ast::elements::Element;
+use std::{collections::BTreeMap, fmt, sync::Arc};
+
+/// An element's attributes once their variables have been filled in. Attributes without a value,
+/// like `<input disabled>`, are [`None`].
+pub type RenderedAttributes = BTreeMap<String, Option<String>>;
+
+/// What a plugin wants done with an element that's about to be rendered
+#[derive(Debug, Clone)]
+pub enum Transform {
+    Keep,
+    /// Renders this instead. Only the plugins after the one that replaced it see the
+    /// replacement, so plugins can't keep replacing each other's elements forever, but its
+    /// children go through every plugin as they're rendered.
+    Replace(Element),
+    /// Renders nothing, not even its children
+    Remove,
+}
+
+pub trait Plugin: Send + Sync {
+    /// Called before every element is rendered, including component calls, slots, text and
+    /// variables.
+    fn element(&self, _element: &Element) -> Transform {
+        Transform::Keep
+    }
+
+    /// Rewrites the attributes of an element called `name` just before they're written out.
+    /// Attributes can be changed, added and taken away.
+    fn attributes(&self, _name: &str, _attributes: &mut RenderedAttributes) {}
+
+    /// Changes the finished output of rendering `component`.
+    fn post_process(&self, _component: &str, _output: &mut String) {}
+}
+
+/// Every plugin an instance has, in the order they were added
+#[derive(Clone, Default)]
+pub(crate) struct Plugins(Vec<Arc<dyn Plugin>>);
+
+impl Plugins {
+    pub fn push(&mut self, plugin: impl Plugin + 'static) {
+        self.0.push(Arc::new(plugin));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Plugin> {
+        self.0.iter().map(Arc::as_ref)
+    }
+}
+
+impl fmt::Debug for Plugins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} plugins", self.0.len())
+    }
+}
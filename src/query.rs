@@ -0,0 +1,499 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Finding elements in a parsed component with CSS selectors, for tooling that picks through
+//! templates, like finding every `img:not([alt])`.
+//!
+//! The supported subset is tags and `*`, `#id`, `.class`, `[attr]`, `[attr=value]` (with `^=`,
+//! `$=` and `*=` too), `:not(...)`, the descendant and `>` child combinators and `,` lists.
+//!
+//! Only what was written in the component itself is searched, which is its own elements and the
+//! slot content it gives to the components it uses. Component calls and `<Slot>`s count as
+//! ancestors under their own names, so `Card > img` finds images given straight to a `<Card>`.
+
+use crate::ast::{
+    elements::{AttributeValues, Attributes, Element, Node, Void},
+    Dom,
+};
+use std::{fmt, iter::Peekable, str::CharIndices, str::FromStr, sync::Arc};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectorError {
+    /// There was nothing to select with, like an empty selector or one ending in a combinator
+    Empty,
+    /// A char that doesn't belong where it was, alongside its byte offset in the selector
+    Unexpected(char, usize),
+    /// The selector ended part way through something, like an `[attribute`
+    UnexpectedEnd,
+}
+
+impl fmt::Display for SelectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SelectorError::Empty => write!(f, "the selector doesn't select anything"),
+            SelectorError::Unexpected(char, position) => {
+                write!(f, "unexpected `{char}` at {position} in the selector")
+            }
+            SelectorError::UnexpectedEnd => write!(f, "the selector ended too early"),
+        }
+    }
+}
+
+impl std::error::Error for SelectorError {}
+
+/// An element that a selector matched
+#[derive(Debug, Clone, Copy)]
+pub enum Selected<'a> {
+    Node(&'a Node),
+    Void(&'a Void),
+}
+
+impl<'a> Selected<'a> {
+    pub fn name(&self) -> &'a str {
+        match self {
+            Selected::Node(node) => &node.name,
+            Selected::Void(void) => &void.name,
+        }
+    }
+
+    pub fn attributes(&self) -> &'a Attributes {
+        match self {
+            Selected::Node(node) => &node.attributes,
+            Selected::Void(void) => &void.attributes,
+        }
+    }
+}
+
+/// A parsed selector, which can be reused across components with [`Dom::select`].
+#[derive(Debug, Clone)]
+pub struct Selector {
+    /// Each selector of a `,` list
+    alternatives: Vec<Complex>,
+}
+
+impl Selector {
+    pub fn parse(selector: &str) -> Result<Self, SelectorError> {
+        SelectorParser {
+            chars: selector.char_indices().peekable(),
+        }
+        .selector()
+    }
+
+    pub(crate) fn select<'a>(&self, dom: &'a Dom) -> Vec<Selected<'a>> {
+        let mut found = vec![];
+        self.walk(&dom.tree.children, &mut vec![], &mut found);
+        found
+    }
+
+    fn matches(&self, element: &Candidate, ancestors: &[Candidate]) -> bool {
+        self.alternatives
+            .iter()
+            .any(|complex| complex.matches(element, ancestors))
+    }
+
+    fn walk<'a>(
+        &self,
+        children: &'a [Arc<Element>],
+        ancestors: &mut Vec<Candidate<'a>>,
+        found: &mut Vec<Selected<'a>>,
+    ) {
+        for child in children {
+            match child.as_ref() {
+                Element::Document(document) => {
+                    ancestors.push(Candidate {
+                        name: &document.name,
+                        attributes: &document.attributes,
+                    });
+
+                    // The children are the used component's own body, so only the slot content is ours
+                    let mut slot_content: Vec<_> = document.slot_content.iter().collect();
+                    slot_content.sort_unstable_by_key(|(slot, _)| *slot);
+
+                    for (_, content) in slot_content {
+                        self.walk(content, ancestors, found);
+                    }
+
+                    ancestors.pop();
+                }
+                Element::Node(node) => {
+                    let candidate = Candidate {
+                        name: &node.name,
+                        attributes: &node.attributes,
+                    };
+
+                    if self.matches(&candidate, ancestors) {
+                        found.push(Selected::Node(node));
+                    }
+
+                    ancestors.push(candidate);
+                    self.walk(&node.children, ancestors, found);
+                    ancestors.pop();
+                }
+                Element::Slot(slot) => {
+                    ancestors.push(Candidate {
+                        name: "Slot",
+                        attributes: &slot.attributes,
+                    });
+                    self.walk(&slot.children, ancestors, found);
+                    ancestors.pop();
+                }
+                Element::Void(void) => {
+                    let candidate = Candidate {
+                        name: &void.name,
+                        attributes: &void.attributes,
+                    };
+
+                    if self.matches(&candidate, ancestors) {
+                        found.push(Selected::Void(void));
+                    }
+                }
+                Element::Text(_) | Element::Variable(_) => (),
+            }
+        }
+    }
+}
+
+impl FromStr for Selector {
+    type Err = SelectorError;
+
+    fn from_str(selector: &str) -> Result<Self, Self::Err> {
+        Self::parse(selector)
+    }
+}
+
+/// Anything that can be matched against, whether or not it can be selected itself
+struct Candidate<'a> {
+    name: &'a str,
+    attributes: &'a Attributes,
+}
+
+impl Candidate<'_> {
+    /// Gets an attribute's value, unless it doesn't have one or a variable decides it
+    fn value(&self, key: &str) -> Option<String> {
+        self.attributes
+            .get(key)?
+            .iter()
+            .map(|value| match value {
+                AttributeValues::Text(text) => Some(text.value.as_str()),
+                AttributeValues::Variable(_) => None,
+            })
+            .collect()
+    }
+
+    /// Gets the whitespace separated words of an attribute that don't touch a variable
+    fn words(&self, key: &str) -> Vec<String> {
+        // Variables are swapped for a char that can't be written, so the words around them are
+        // dropped rather than looking like they end where the variable starts
+        let value: String = self
+            .attributes
+            .get(key)
+            .into_iter()
+            .flatten()
+            .map(|value| match value {
+                AttributeValues::Text(text) => text.value.as_str(),
+                AttributeValues::Variable(_) => "\0",
+            })
+            .collect();
+
+        value
+            .split_whitespace()
+            .filter(|word| !word.contains('\0'))
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    Descendant,
+    Child,
+}
+
+#[derive(Debug, Clone)]
+struct Complex {
+    /// The part the element itself has to match
+    subject: Compound,
+    /// The parts its ancestors have to match, nearest first, alongside how each is related to the
+    /// part after it
+    ancestors: Vec<(Combinator, Compound)>,
+}
+
+impl Complex {
+    fn matches(&self, element: &Candidate, ancestors: &[Candidate]) -> bool {
+        self.subject.matches(element) && matches_ancestors(&self.ancestors, ancestors)
+    }
+}
+
+fn matches_ancestors(parts: &[(Combinator, Compound)], ancestors: &[Candidate]) -> bool {
+    let Some(((combinator, compound), parts)) = parts.split_first() else {
+        return true;
+    };
+
+    match combinator {
+        Combinator::Child => ancestors.split_last().is_some_and(|(parent, above)| {
+            compound.matches(parent) && matches_ancestors(parts, above)
+        }),
+        Combinator::Descendant => (0..ancestors.len()).rev().any(|index| {
+            compound.matches(&ancestors[index]) && matches_ancestors(parts, &ancestors[..index])
+        }),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Compound {
+    /// [`None`] for `*`, or when no tag was given
+    tag: Option<String>,
+    filters: Vec<Filter>,
+}
+
+impl Compound {
+    fn matches(&self, element: &Candidate) -> bool {
+        self.tag.as_ref().is_none_or(|tag| tag == element.name)
+            && self.filters.iter().all(|filter| filter.matches(element))
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Filter {
+    Id(String),
+    Class(String),
+    Attribute(String, Option<(Operator, String)>),
+    Not(Compound),
+}
+
+impl Filter {
+    fn matches(&self, element: &Candidate) -> bool {
+        match self {
+            Filter::Id(id) => element.value("id").as_ref() == Some(id),
+            Filter::Class(class) => element.words("class").contains(class),
+            Filter::Attribute(key, None) => element.attributes.contains_key(key.as_str()),
+            Filter::Attribute(key, Some((operator, expected))) => {
+                element.value(key).is_some_and(|value| match operator {
+                    Operator::Equals => &value == expected,
+                    Operator::Prefix => value.starts_with(expected.as_str()),
+                    Operator::Suffix => value.ends_with(expected.as_str()),
+                    Operator::Contains => value.contains(expected.as_str()),
+                })
+            }
+            Filter::Not(compound) => !compound.matches(element),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Operator {
+    /// `=`
+    Equals,
+    /// `^=`
+    Prefix,
+    /// `$=`
+    Suffix,
+    /// `*=`
+    Contains,
+}
+
+struct SelectorParser<'s> {
+    chars: Peekable<CharIndices<'s>>,
+}
+
+impl SelectorParser<'_> {
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|(_, char)| *char)
+    }
+
+    fn skip_whitespace(&mut self) -> bool {
+        let mut skipped = false;
+        while self
+            .chars
+            .next_if(|(_, char)| char.is_whitespace())
+            .is_some()
+        {
+            skipped = true;
+        }
+        skipped
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SelectorError> {
+        match self.chars.next() {
+            Some((_, char)) if char == expected => Ok(()),
+            Some((position, char)) => Err(SelectorError::Unexpected(char, position)),
+            None => Err(SelectorError::UnexpectedEnd),
+        }
+    }
+
+    fn unexpected(&mut self) -> SelectorError {
+        match self.chars.next() {
+            Some((position, char)) => SelectorError::Unexpected(char, position),
+            None => SelectorError::UnexpectedEnd,
+        }
+    }
+
+    fn selector(mut self) -> Result<Selector, SelectorError> {
+        let mut alternatives = vec![self.complex()?];
+
+        while self.peek() == Some(',') {
+            self.chars.next();
+            alternatives.push(self.complex()?);
+        }
+
+        match self.chars.next() {
+            None => Ok(Selector { alternatives }),
+            Some((position, char)) => Err(SelectorError::Unexpected(char, position)),
+        }
+    }
+
+    fn complex(&mut self) -> Result<Complex, SelectorError> {
+        self.skip_whitespace();
+        let mut parts = vec![];
+        let mut subject = self.compound()?;
+
+        loop {
+            let spaced = self.skip_whitespace();
+
+            let combinator = match self.peek() {
+                Some('>') => {
+                    self.chars.next();
+                    self.skip_whitespace();
+                    Combinator::Child
+                }
+                Some(',') | None => break,
+                Some(_) if spaced => Combinator::Descendant,
+                Some(_) => return Err(self.unexpected()),
+            };
+
+            parts.push((
+                combinator,
+                std::mem::replace(&mut subject, self.compound()?),
+            ));
+        }
+
+        // Matching starts from the subject and works outwards
+        parts.reverse();
+
+        Ok(Complex {
+            subject,
+            ancestors: parts,
+        })
+    }
+
+    fn compound(&mut self) -> Result<Compound, SelectorError> {
+        let mut compound = Compound::default();
+        let mut empty = true;
+
+        match self.peek() {
+            Some('*') => {
+                self.chars.next();
+                empty = false;
+            }
+            Some(char) if is_identifier_char(char) => {
+                compound.tag = Some(self.identifier()?);
+                empty = false;
+            }
+            _ => (),
+        }
+
+        loop {
+            let filter = match self.peek() {
+                Some('#') => {
+                    self.chars.next();
+                    Filter::Id(self.identifier()?)
+                }
+                Some('.') => {
+                    self.chars.next();
+                    Filter::Class(self.identifier()?)
+                }
+                Some('[') => {
+                    self.chars.next();
+                    self.attribute()?
+                }
+                Some(':') => {
+                    let (position, _) = self.chars.next().expect("it was just peeked");
+                    if self.identifier()? != "not" {
+                        return Err(SelectorError::Unexpected(':', position));
+                    }
+                    self.expect('(')?;
+                    self.skip_whitespace();
+                    let inner = self.compound()?;
+                    self.skip_whitespace();
+                    self.expect(')')?;
+                    Filter::Not(inner)
+                }
+                _ => break,
+            };
+
+            compound.filters.push(filter);
+            empty = false;
+        }
+
+        match empty {
+            true => match self.peek() {
+                Some(',') | None => Err(SelectorError::Empty),
+                Some(_) => Err(self.unexpected()),
+            },
+            false => Ok(compound),
+        }
+    }
+
+    /// Parses what comes after the `[` of an attribute filter
+    fn attribute(&mut self) -> Result<Filter, SelectorError> {
+        self.skip_whitespace();
+        let key = self.identifier()?;
+        self.skip_whitespace();
+
+        let operator = match self.peek() {
+            Some(']') => {
+                self.chars.next();
+                return Ok(Filter::Attribute(key, None));
+            }
+            Some('=') => Operator::Equals,
+            Some('^') => Operator::Prefix,
+            Some('$') => Operator::Suffix,
+            Some('*') => Operator::Contains,
+            _ => return Err(self.unexpected()),
+        };
+
+        self.chars.next();
+        if !matches!(operator, Operator::Equals) {
+            self.expect('=')?;
+        }
+        self.skip_whitespace();
+
+        let value = match self.peek() {
+            Some(quote @ ('"' | '\'')) => {
+                self.chars.next();
+                let mut value = String::new();
+                loop {
+                    match self.chars.next() {
+                        Some((_, char)) if char == quote => break value,
+                        Some((_, char)) => value.push(char),
+                        None => return Err(SelectorError::UnexpectedEnd),
+                    }
+                }
+            }
+            _ => self.identifier()?,
+        };
+
+        self.skip_whitespace();
+        self.expect(']')?;
+
+        Ok(Filter::Attribute(key, Some((operator, value))))
+    }
+
+    fn identifier(&mut self) -> Result<String, SelectorError> {
+        let mut identifier = String::new();
+
+        while let Some((_, char)) = self.chars.next_if(|(_, char)| is_identifier_char(*char)) {
+            identifier.push(char);
+        }
+
+        match identifier.is_empty() {
+            true => Err(self.unexpected()),
+            false => Ok(identifier),
+        }
+    }
+}
+
+fn is_identifier_char(char: char) -> bool {
+    char.is_alphanumeric() || char == '-' || char == '_'
+}
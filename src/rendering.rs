@@ -2,22 +2,271 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
-//use crate::ast::Element;
-
-//impl Element {
-//    //pub fn serialise(self) -> Result<>
-//    pub fn serialise(&self) -> String {
-//        let text = match &self.value {
-//            ParsedState::Parsed(text) => &text,
-//            ParsedState::Unparsed => &String::new(),
-//        };
-//
-//        let attributes: String = self
-//            .attributes
-//            .iter()
-//            .map(|(attribute, value)| format!(" {attribute}=\"{value}\""))
-//            .collect();
-//
-//        format!("<{0}{attributes}>{text}</{0}>", self.name)
-//    }
-//}
+use crate::{
+    ast::{elements::*, Dom},
+    output::{HtmlWriter, Origins, RenderOptions},
+    plugin::{Plugins, RenderedAttributes, Transform},
+    ParsingError,
+};
+use std::{borrow::Cow, collections::HashMap, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderingError {
+    /// The component, or one of the components it uses, couldn't be parsed
+    Parsing(ParsingError),
+    /// A variable was used without being given a value
+    MissingInput(String),
+    /// Rendering straight from templates was asked for while plugins were registered, which
+    /// only run when rendering from a Dom
+    PluginsUnsupported,
+}
+
+impl fmt::Display for RenderingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderingError::Parsing(error) => write!(f, "{error}"),
+            RenderingError::MissingInput(name) => write!(f, "no input was given for `{name}`"),
+            RenderingError::PluginsUnsupported => {
+                write!(
+                    f,
+                    "plugins can't run when rendering straight from templates"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for RenderingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RenderingError::Parsing(error) => Some(error),
+            RenderingError::MissingInput(_) | RenderingError::PluginsUnsupported => None,
+        }
+    }
+}
+
+/// The variables and slot content available to the elements being rendered. Every component
+/// gets its own scope made from the attributes it was given at its call site.
+struct Scope<'a> {
+    /// The component whose elements are being rendered
+    component: &'a str,
+    inputs: Cow<'a, HashMap<String, String>>,
+    /// The call site of the component we're inside of, for filling its slots
+    call_site: Option<&'a Document>,
+    parent: Option<&'a Scope<'a>>,
+}
+
+impl Scope<'_> {
+    fn get(&self, variable: &Variable) -> Result<&str, RenderingError> {
+        self.inputs
+            .get(variable.name.as_str())
+            .map(String::as_str)
+            .ok_or_else(|| RenderingError::MissingInput(variable.name.to_string()))
+    }
+
+    fn evaluate(&self, values: &[AttributeValues]) -> Result<String, RenderingError> {
+        let mut value = String::new();
+
+        for part in values {
+            match part {
+                AttributeValues::Text(text) => value.push_str(&text.value),
+                AttributeValues::Variable(variable) => value.push_str(self.get(variable)?),
+            }
+        }
+
+        Ok(value)
+    }
+}
+
+/// Escapes text so it can't be mistaken for markup, both between tags and within quoted
+/// attribute values
+pub fn escape_into(output: &mut String, text: &str) {
+    for char in text.chars() {
+        match char {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#39;"),
+            char => output.push(char),
+        }
+    }
+}
+
+struct Renderer<'p, F> {
+    output: HtmlWriter,
+    rendering_callback: F,
+    plugins: &'p Plugins,
+}
+
+impl<F> Renderer<'_, F>
+where
+    F: Fn(Element) -> Option<Element> + Copy,
+{
+    fn render_attributes(
+        &mut self,
+        name: &str,
+        attributes: &Attributes,
+        scope: &Scope,
+    ) -> Result<(), RenderingError> {
+        if !self.plugins.is_empty() {
+            return self.render_plugin_attributes(name, attributes, scope);
+        }
+
+        for (key, values) in attributes {
+            match values.is_empty() {
+                true => self.output.attribute(key, None),
+                false => self.output.attribute(key, Some(&scope.evaluate(values)?)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Renders attributes after letting every plugin rewrite them
+    fn render_plugin_attributes(
+        &mut self,
+        name: &str,
+        attributes: &Attributes,
+        scope: &Scope,
+    ) -> Result<(), RenderingError> {
+        let mut rendered = attributes
+            .iter()
+            .map(|(key, values)| {
+                let value = match values.is_empty() {
+                    true => None,
+                    false => Some(scope.evaluate(values)?),
+                };
+                Ok((key.to_string(), value))
+            })
+            .collect::<Result<RenderedAttributes, RenderingError>>()?;
+
+        for plugin in self.plugins.iter() {
+            plugin.attributes(name, &mut rendered);
+        }
+
+        for (key, value) in rendered {
+            self.output.attribute(&key, value.as_deref());
+        }
+
+        Ok(())
+    }
+
+    fn render_children(
+        &mut self,
+        children: &[std::sync::Arc<Element>],
+        scope: &Scope,
+    ) -> Result<(), RenderingError> {
+        children
+            .iter()
+            .try_for_each(|child| self.render_element(child, scope))
+    }
+
+    fn render_element(&mut self, element: &Element, scope: &Scope) -> Result<(), RenderingError> {
+        let Some(mut element) = (self.rendering_callback)(element.clone()) else {
+            return Ok(());
+        };
+
+        for plugin in self.plugins.iter() {
+            match plugin.element(&element) {
+                Transform::Keep => (),
+                Transform::Replace(replacement) => element = replacement,
+                Transform::Remove => return Ok(()),
+            }
+        }
+
+        match &element {
+            Element::Document(document) => {
+                let inputs = document
+                    .attributes
+                    .iter()
+                    .map(|(key, values)| Ok((key.to_string(), scope.evaluate(values)?)))
+                    .collect::<Result<_, RenderingError>>()?;
+
+                let component_scope = Scope {
+                    component: &document.name,
+                    inputs: Cow::Owned(inputs),
+                    call_site: Some(document),
+                    parent: Some(scope),
+                };
+
+                let call = document
+                    .location
+                    .as_ref()
+                    .map(|location| (&*location.component, location.span.start));
+                self.output.open_component(&document.name, call);
+                self.render_children(&document.children, &component_scope)?;
+                self.output.close_component(&document.name);
+            }
+            Element::Node(node) => {
+                self.output.open_tag(&node.name);
+                self.render_attributes(&node.name, &node.attributes, scope)?;
+                self.output.end_open_tag(&node.name, false);
+                self.render_children(&node.children, scope)?;
+                self.output.close_tag(&node.name);
+            }
+            Element::Slot(slot) => {
+                let content = scope
+                    .call_site
+                    .and_then(|call_site| call_site.slot_content.get(&slot.name));
+
+                let declared = slot.location.as_ref().map(|location| location.span.start);
+                self.output.open_slot(scope.component, &slot.name, declared);
+                match (content, scope.parent) {
+                    // Slot content belongs to the caller, so it's rendered with their inputs
+                    (Some(content), Some(parent)) => self.render_children(content, parent)?,
+                    _ => self.render_children(&slot.children, scope)?,
+                }
+                self.output.close_slot(scope.component);
+            }
+            Element::Text(text) => self.output.text(&text.value),
+            Element::Variable(variable) => self.output.escaped(scope.get(variable)?),
+            Element::Void(void) => {
+                self.output.open_tag(&void.name);
+                self.render_attributes(&void.name, &void.attributes, scope)?;
+                self.output.end_open_tag(&void.name, true);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Renders a parsed component to HTML. Every element is passed through `rendering_callback`
+/// before it's rendered, which can swap it out or drop it by returning [`None`], and then through
+/// the plugins.
+pub(crate) fn render<F>(
+    dom: &Dom,
+    inputs: &HashMap<String, String>,
+    rendering_callback: F,
+    plugins: &Plugins,
+    options: &RenderOptions,
+    origins: Origins,
+) -> Result<String, RenderingError>
+where
+    F: Fn(Element) -> Option<Element> + Copy,
+{
+    let mut renderer = Renderer {
+        output: HtmlWriter::new(options).with_origins(origins),
+        rendering_callback,
+        plugins,
+    };
+
+    let scope = Scope {
+        component: &dom.tree.name,
+        inputs: Cow::Borrowed(inputs),
+        call_site: None,
+        parent: None,
+    };
+
+    renderer.output.open_root(&dom.tree.name);
+    renderer.render_children(&dom.tree.children, &scope)?;
+    renderer.output.close_component(&dom.tree.name);
+
+    let mut output = renderer.output.finish();
+    for plugin in plugins.iter() {
+        plugin.post_process(&dom.tree.name, &mut output);
+    }
+
+    Ok(output)
+}
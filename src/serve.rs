@@ -0,0 +1,331 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! A small development server for a [`Site`], which rebuilds it whenever it changes and reloads
+//! any browsers looking at it.
+//!
+//! Every HTML page it serves gets a script that listens for rebuilds over server-sent events. When
+//! the last build didn't go cleanly, the pages also get an overlay listing what went wrong, so a
+//! broken template shows up where you're looking rather than only in the terminal.
+//!
+//! This is meant for working on a site locally. It serves one request per thread and only knows
+//! enough HTTP for a browser, so anything public should serve the built files with something else.
+
+use crate::{
+    loading,
+    rendering::escape_into,
+    ssg::{self, BuildReport, Site, SiteError},
+    RenderingError,
+};
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    path::Path,
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::Duration,
+};
+
+/// Where browsers listen for rebuilds
+const RELOAD_PATH: &str = "/__vel/reload";
+
+/// How often the site is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long an idle reload stream waits before checking the browser is still there
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+const RELOAD_SCRIPT: &str =
+    "<script>new EventSource(\"/__vel/reload\").onmessage = () => location.reload();</script>";
+
+/// What the last build left behind, shared with every connection
+#[derive(Debug, Default)]
+struct Status {
+    /// Counts builds, so reload streams can tell when there's been a new one
+    build: u64,
+    /// What went wrong in the last build, ready to be shown
+    errors: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    status: Mutex<Status>,
+    rebuilt: Condvar,
+}
+
+impl Shared {
+    /// Tells browsers about a build. One that didn't run, or failed the same way as the last
+    /// one, changes nothing they show, so they're left alone.
+    fn update(&self, errors: Vec<String>, built: bool) {
+        let mut status = self.status.lock().unwrap();
+        if !built && status.errors == errors {
+            return;
+        }
+
+        status.build += 1;
+        status.errors = errors;
+        self.rebuilt.notify_all();
+    }
+}
+
+#[derive(Debug)]
+pub struct Server {
+    site: Site,
+    listener: TcpListener,
+    shared: Arc<Shared>,
+}
+
+impl Server {
+    /// Builds `site` and starts listening on `address`. Pages that fail to build don't stop
+    /// the server, they're shown in the overlay instead.
+    pub fn bind(mut site: Site, address: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+
+        let shared = Arc::new(Shared::default());
+        let report = site.build();
+        shared.update(errors(&site, report), true);
+
+        Ok(Self {
+            site,
+            listener,
+            shared,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serves the site until listening fails, rebuilding it in the background.
+    pub fn run(self) -> io::Result<()> {
+        let Server {
+            mut site,
+            listener,
+            shared,
+        } = self;
+
+        let output: Arc<Path> = site.config().output.clone().into();
+        let watching = shared.clone();
+        thread::spawn(move || loop {
+            let report = site.wait(POLL_INTERVAL);
+            let built = report.is_ok();
+            watching.update(errors(&site, report), built);
+        });
+
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let shared = shared.clone();
+            let output = output.clone();
+
+            // A browser that goes away halfway through a response isn't our problem
+            thread::spawn(move || _ = respond(stream, &output, &shared));
+        }
+
+        Ok(())
+    }
+}
+
+/// Turns how a build went into what the overlay shows
+fn errors(site: &Site, report: Result<BuildReport, SiteError>) -> Vec<String> {
+    let report = match report {
+        Ok(report) => report,
+        Err(error) => return vec![error.to_string()],
+    };
+
+    report
+        .failed
+        .iter()
+        .map(|(route, error)| format!("{route}: {}", locate(site, error)))
+        .collect()
+}
+
+/// Adds where a parsing error is to it, like `ui/Button.vel:3:7`, when the file can be found
+fn locate(site: &Site, error: &RenderingError) -> String {
+    let RenderingError::Parsing(parsing) = error else {
+        return error.to_string();
+    };
+
+    let config = site.config();
+    let located = loading::scan_dir(&config.components, config.naming)
+        .ok()
+        .and_then(|mut paths| paths.remove(&parsing.component))
+        .and_then(|path| Some((fs::read_to_string(&path).ok()?, path)));
+
+    match located {
+        Some((source, path)) => {
+            let (line, column) = parsing.span.line_column(&source);
+            format!("{}:{line}:{column}: {error}", path.display())
+        }
+        None => error.to_string(),
+    }
+}
+
+fn respond(stream: TcpStream, output: &Path, shared: &Shared) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+
+    // The headers don't change anything, but have to be read before answering
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut stream = reader.into_inner();
+    let mut parts = request.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return send(&mut stream, "400 Bad Request", "text/plain", b"bad request");
+    };
+
+    if method != "GET" {
+        return send(
+            &mut stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            b"only GET is allowed",
+        );
+    }
+
+    let path = decode(target.split(['?', '#']).next().unwrap_or("/"));
+    if path == RELOAD_PATH {
+        return reload_stream(stream, shared);
+    }
+
+    let file = ssg::output_path(&path).map(|file| output.join(file));
+    let (status, file) = match file {
+        Some(file) if file.is_file() => ("200 OK", file),
+        _ => ("404 Not Found", output.join("404.html")),
+    };
+
+    let kind = content_type(&file);
+    let contents = match fs::read(&file) {
+        Ok(contents) => contents,
+        // Without a 404 page, a page is still needed to show the overlay on
+        Err(_) if kind == "text/html" => b"<!DOCTYPE html><title>Not found</title>".to_vec(),
+        Err(_) => return send(&mut stream, status, "text/plain", b"not found"),
+    };
+
+    let body = match kind {
+        "text/html" => inject(
+            &String::from_utf8_lossy(&contents),
+            &shared.status.lock().unwrap().errors,
+        )
+        .into_bytes(),
+        _ => contents,
+    };
+
+    send(&mut stream, status, kind, &body)
+}
+
+/// Sends an event down the stream every time the site is rebuilt
+fn reload_stream(mut stream: TcpStream, shared: &Shared) -> io::Result<()> {
+    stream.write_all(
+        b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-store\r\n\r\n",
+    )?;
+
+    let mut build = shared.status.lock().unwrap().build;
+    loop {
+        let status = shared.status.lock().unwrap();
+        let (status, _) = shared
+            .rebuilt
+            .wait_timeout_while(status, KEEP_ALIVE, |status| status.build == build)
+            .unwrap();
+
+        let event: &[u8] = match status.build == build {
+            // Comments keep the connection open, and fail once the browser has gone
+            true => b": still here\n\n",
+            false => b"data: reload\n\n",
+        };
+        build = status.build;
+        drop(status);
+
+        stream.write_all(event)?;
+    }
+}
+
+fn send(stream: &mut TcpStream, status: &str, kind: &str, body: &[u8]) -> io::Result<()> {
+    let charset = match kind.starts_with("text/") {
+        true => "; charset=utf-8",
+        false => "",
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {kind}{charset}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+/// Adds the reload script to a page, and the overlay when there are `errors`, just before its
+/// `</body>` or at the end when it doesn't have one.
+pub fn inject(html: &str, errors: &[String]) -> String {
+    let mut injected = String::from(RELOAD_SCRIPT);
+
+    if !errors.is_empty() {
+        injected.push_str(
+            "<div id=\"vel-errors\" style=\"position: fixed; inset: 0; z-index: 2147483647; overflow: auto; padding: 2rem; background: rgba(20, 0, 0, 0.9); color: #fdd; font: 14px/1.5 monospace; white-space: pre-wrap\">",
+        );
+        injected.push_str("<strong>The site didn't build</strong>");
+        for error in errors {
+            injected.push_str("<p>");
+            escape_into(&mut injected, error);
+            injected.push_str("</p>");
+        }
+        injected.push_str("</div>");
+    }
+
+    match html.rfind("</body>") {
+        Some(end) => format!("{}{injected}{}", &html[..end], &html[end..]),
+        None => format!("{html}{injected}"),
+    }
+}
+
+/// Undoes percent escapes, leaving any that are broken as they are
+pub(crate) fn decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%')
+            .then(|| path.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("html" | "htm") => "text/html",
+        Some("css") => "text/css",
+        Some("js" | "mjs") => "text/javascript",
+        Some("json") => "application/json",
+        Some("txt") => "text/plain",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("ico") => "image/x-icon",
+        Some("woff2") => "font/woff2",
+        Some("wasm") => "application/wasm",
+        _ => "application/octet-stream",
+    }
+}
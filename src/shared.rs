@@ -0,0 +1,367 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! An instance that can be shared between threads, for rendering from many workers at once.
+
+use crate::{
+    ast::{elements::Element, Dom},
+    loading::ComponentSource,
+    output::{Origins, RenderOptions},
+    parsing::{self, ComponentStore, Lookup, Span},
+    plugin::{Plugin, Plugins},
+    rendering, ComponentsCache, LazyDom, ParsingError, ParsingErrorKind, RenderingError,
+    VelInstance,
+};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    path::PathBuf,
+    sync::{Arc, OnceLock, PoisonError, RwLock},
+};
+
+/// A component's template alongside its Dom, which is parsed the first time it's needed
+struct SharedComponent {
+    /// Components that were handed over already parsed may not have a template
+    template: Option<Arc<str>>,
+    /// The components the template uses, found by scanning it for their tags
+    references: BTreeSet<String>,
+    dom: OnceLock<Result<Dom, ParsingError>>,
+}
+
+impl SharedComponent {
+    fn new(template: Option<Arc<str>>) -> Self {
+        Self {
+            references: template
+                .as_deref()
+                .map(parsing::referenced_components)
+                .unwrap_or_default(),
+            template,
+            dom: OnceLock::new(),
+        }
+    }
+}
+
+/// A [`VelInstance`] that renders through `&self`, so one instance can be shared between threads
+/// behind an [`Arc`].
+///
+/// Components are still parsed lazily, but each one is parsed at most once no matter how many
+/// threads need it at the same time. Once a component has been parsed, rendering it never takes
+/// a lock. Components fetched from the instance's [`ComponentSource`] are the exception, as
+/// they're kept behind a [`RwLock`] since they're added after the instance was made.
+pub struct SharedVelInstance {
+    components: HashMap<String, Arc<SharedComponent>>,
+    discovered: RwLock<HashMap<String, Arc<SharedComponent>>>,
+    /// The file each component was read from, for debug markers
+    paths: HashMap<String, PathBuf>,
+    source: Option<Box<dyn ComponentSource>>,
+    plugins: Plugins,
+    options: RenderOptions,
+}
+
+impl SharedVelInstance {
+    pub fn new(components: HashMap<String, String>) -> Self {
+        Self {
+            components: components
+                .into_iter()
+                .map(|(name, template)| {
+                    (name, Arc::new(SharedComponent::new(Some(template.into()))))
+                })
+                .collect(),
+            discovered: RwLock::new(HashMap::new()),
+            paths: HashMap::new(),
+            source: None,
+            plugins: Plugins::default(),
+            options: RenderOptions::default(),
+        }
+    }
+
+    /// Sets where components are fetched from when they're used without having been registered.
+    pub fn set_source(&mut self, source: impl ComponentSource + 'static) -> &mut Self {
+        self.source = Some(Box::new(source));
+        self
+    }
+
+    /// Adds a plugin to run on everything rendered, after the ones already added.
+    pub fn add_plugin(&mut self, plugin: impl Plugin + 'static) -> &mut Self {
+        self.plugins.push(plugin);
+        self
+    }
+
+    /// Sets how everything rendered is written out, like whether it's minified.
+    pub fn set_render_options(&mut self, options: RenderOptions) -> &mut Self {
+        self.options = options;
+        self
+    }
+
+    fn component(&self, name: &str) -> Option<Arc<SharedComponent>> {
+        if let Some(component) = self.components.get(name) {
+            return Some(component.clone());
+        }
+
+        let source = self.source.as_ref()?;
+
+        if let Some(component) = self
+            .discovered
+            .read()
+            .expect("discovered components lock poisoned")
+            .get(name)
+        {
+            return Some(component.clone());
+        }
+
+        let template = source.load(name)?;
+
+        Some(
+            self.discovered
+                .write()
+                .expect("discovered components lock poisoned")
+                .entry(name.to_string())
+                // Another thread may have fetched it while we weren't holding the lock
+                .or_insert_with(|| Arc::new(SharedComponent::new(Some(template.into()))))
+                .clone(),
+        )
+    }
+
+    /// Returns whether `name` ends up using itself. Parsing it would wait on itself forever, as
+    /// every component waits for the ones it uses to be parsed first.
+    fn is_cyclic(&self, name: &str) -> bool {
+        let mut visited = BTreeSet::new();
+        let mut queue: Vec<String> = self
+            .component(name)
+            .map(|component| component.references.iter().cloned().collect())
+            .unwrap_or_default();
+
+        while let Some(current) = queue.pop() {
+            if current == name {
+                return true;
+            }
+
+            if visited.insert(current.clone()) {
+                if let Some(component) = self.component(&current) {
+                    queue.extend(component.references.iter().cloned());
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Gets the Dom of a component, parsing it, and the components it uses, if nobody has yet.
+    pub fn dom(&self, name: &str) -> Result<Dom, ParsingError> {
+        let error = |kind| ParsingError::new(name, kind, Span::default());
+
+        let component = self
+            .component(name)
+            .ok_or_else(|| error(ParsingErrorKind::UnknownComponent(name.to_string())))?;
+
+        if let Some(dom) = component.dom.get() {
+            return dom.clone();
+        }
+
+        // Parsing a component that ends up using itself would wait on itself forever, so those
+        // are parsed on their own instead, which also finds where it uses itself
+        let cyclic = self.is_cyclic(name);
+
+        component
+            .dom
+            .get_or_init(|| {
+                // Parsing everything it uses first means the parse itself never has to wait on
+                // another thread
+                if !cyclic {
+                    for reference in &component.references {
+                        // Any problems are left for the parser to find, so they're reported from
+                        // where they are
+                        let _ = self.dom(reference);
+                    }
+                }
+
+                let mut store = SharedStore {
+                    instance: self,
+                    root: name,
+                    template: component.template.clone(),
+                    dom: None,
+                    failed_component: None,
+                    cyclic,
+                };
+
+                if let Err(error) = parsing::parse(name, &mut store) {
+                    // Parsing only fails on a component it uses when that component is broken
+                    return Err(store.failed_component.unwrap_or(error));
+                }
+
+                Ok(store
+                    .dom
+                    .expect("parse always stores the component it was asked for"))
+            })
+            .clone()
+    }
+
+    pub fn render<F>(
+        &self,
+        component: &str,
+        inputs: &HashMap<String, String>,
+        rendering_callback: F,
+    ) -> Result<String, RenderingError>
+    where
+        F: Fn(Element) -> Option<Element> + std::marker::Copy,
+    {
+        let dom = self.dom(component).map_err(RenderingError::Parsing)?;
+
+        rendering::render(
+            &dom,
+            inputs,
+            rendering_callback,
+            &self.plugins,
+            &self.options,
+            self.origins(),
+        )
+    }
+
+    /// Gets where every template came from, which is only needed for debug markers
+    fn origins(&self) -> Origins {
+        if !self.options.debug {
+            return Origins::default();
+        }
+
+        let discovered = self
+            .discovered
+            .read()
+            .expect("discovered components lock poisoned");
+        let templates: HashMap<String, Arc<str>> = self
+            .components
+            .iter()
+            .chain(discovered.iter())
+            .filter_map(|(name, component)| Some((name.clone(), component.template.clone()?)))
+            .collect();
+
+        Origins::new(templates, &self.paths, self.source.as_deref())
+    }
+
+    /// Keeps any components that have already been parsed, so they aren't parsed again.
+    pub(crate) fn from_cache(mut cache: ComponentsCache) -> Self {
+        let mut components: HashMap<String, Arc<SharedComponent>> = HashMap::new();
+
+        for (name, entry) in cache.entries {
+            let component = SharedComponent::new(cache.templates.remove(&name));
+
+            if let LazyDom::Parsed(dom) = entry {
+                let _ = component.dom.set(Ok(dom));
+            }
+
+            components.insert(name, Arc::new(component));
+        }
+
+        Self {
+            components,
+            discovered: RwLock::new(HashMap::new()),
+            paths: cache.paths,
+            source: cache.source,
+            plugins: Plugins::default(),
+            options: RenderOptions::default(),
+        }
+    }
+
+    /// Hands every component back, parsed if it has been and parsed successfully.
+    pub(crate) fn into_cache(self) -> ComponentsCache {
+        let mut cache = ComponentsCache {
+            paths: self.paths,
+            source: self.source,
+            ..Default::default()
+        };
+
+        // A thread that panicked while holding the lock can't have left the map half changed, as
+        // it's only ever inserted into
+        let discovered = self
+            .discovered
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        for (name, component) in self.components.into_iter().chain(discovered) {
+            let component = Arc::into_inner(component)
+                .expect("nothing else holds onto components once the instance is gone");
+
+            if let Some(template) = &component.template {
+                cache.insert(name.clone(), LazyDom::Unparsed(template.clone()));
+            }
+
+            if let Some(Ok(dom)) = component.dom.into_inner() {
+                cache.insert(name, LazyDom::Parsed(dom));
+            }
+        }
+
+        cache
+    }
+}
+
+impl From<VelInstance> for SharedVelInstance {
+    fn from(instance: VelInstance) -> Self {
+        Self {
+            plugins: instance.plugins,
+            options: instance.options,
+            ..Self::from_cache(instance.components)
+        }
+    }
+}
+
+impl fmt::Debug for SharedVelInstance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedVelInstance")
+            .field("components", &self.components.keys())
+            .field("source", &self.source.is_some())
+            .field("plugins", &self.plugins)
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+/// Feeds the parser a single component's template, with every component it uses already parsed
+struct SharedStore<'a> {
+    instance: &'a SharedVelInstance,
+    root: &'a str,
+    template: Option<Arc<str>>,
+    dom: Option<Dom>,
+    /// Why a component it uses couldn't be parsed, if one couldn't be
+    failed_component: Option<ParsingError>,
+    /// Whether the components it uses are parsed along with it, rather than fetched already
+    /// parsed
+    cyclic: bool,
+}
+
+impl ComponentStore for SharedStore<'_> {
+    fn lookup(&mut self, name: &str) -> Option<Lookup> {
+        if name == self.root {
+            // The root is only handed out once, any later lookups are it trying to use itself
+            return Some(Lookup::Unparsed(self.template.take()?));
+        }
+
+        if self.cyclic {
+            let component = self.instance.component(name)?;
+
+            return match (component.dom.get(), &component.template) {
+                (Some(Ok(dom)), _) => Some(Lookup::Parsed(dom.clone())),
+                (_, Some(template)) => Some(Lookup::Unparsed(template.clone())),
+                _ => None,
+            };
+        }
+
+        match self.instance.dom(name) {
+            Ok(dom) => Some(Lookup::Parsed(dom)),
+            // Components that don't exist are left for the parser to report from the caller
+            Err(ParsingError {
+                kind: ParsingErrorKind::UnknownComponent(unknown),
+                ..
+            }) if unknown == name => None,
+            Err(error) => {
+                self.failed_component = Some(error);
+                None
+            }
+        }
+    }
+
+    fn store(&mut self, name: String, dom: Dom) {
+        if name == self.root {
+            self.dom = Some(dom);
+        }
+    }
+}
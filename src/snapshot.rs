@@ -0,0 +1,500 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Saving parsed components so later runs can skip parsing them.
+//!
+//! The binary format is a magic number and format version, followed by each component's name,
+//! the hash of the template it was parsed from and its tree. Lengths, counts and offsets into
+//! templates are LEB128 varints and strings are UTF-8.
+
+use crate::{
+    ast::{elements::*, Dom},
+    json::Value,
+    parsing::Span,
+};
+use std::{collections::BTreeMap, fmt, sync::Arc};
+
+const MAGIC: &[u8; 4] = b"VELS";
+
+/// Bumped whenever the layout of the binary format changes. Snapshots from other versions are
+/// rejected rather than misread.
+pub const FORMAT_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// The bytes don't start with the snapshot magic number
+    NotASnapshot,
+    /// The snapshot was made by a version of the format we can't read
+    UnsupportedVersion(u32),
+    /// The snapshot ended part way through
+    Truncated,
+    /// The snapshot has data in it that the format doesn't allow
+    Malformed,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::NotASnapshot => write!(f, "not a vel snapshot"),
+            SnapshotError::UnsupportedVersion(version) => write!(
+                f,
+                "snapshot format version {version} isn't supported, expected {FORMAT_VERSION}"
+            ),
+            SnapshotError::Truncated => write!(f, "the snapshot is truncated"),
+            SnapshotError::Malformed => write!(f, "the snapshot is malformed"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+/// Hashes a template with 64 bit FNV-1a. Unlike std's hasher, it's guaranteed to give the same
+/// hash across builds, which is the whole point of storing it.
+pub(crate) fn template_hash(template: &str) -> u64 {
+    template.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Parsed components, each alongside the hash of the template they were parsed from.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub(crate) components: BTreeMap<String, (u64, Dom)>,
+}
+
+impl Snapshot {
+    /// The names of every component in the snapshot.
+    pub fn components(&self) -> impl Iterator<Item = &str> {
+        self.components.keys().map(String::as_str)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer(Vec::from(*MAGIC));
+        writer.0.extend(FORMAT_VERSION.to_le_bytes());
+
+        writer.length(self.components.len());
+
+        for (name, (hash, dom)) in &self.components {
+            writer.string(name);
+            writer.0.extend(hash.to_le_bytes());
+            writer.document(&dom.tree);
+        }
+
+        writer.0
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let mut reader = Reader(bytes);
+
+        if reader.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err(SnapshotError::NotASnapshot);
+        }
+
+        let version = u32::from_le_bytes(reader.array()?);
+        if version != FORMAT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let mut components = BTreeMap::new();
+
+        for _ in 0..reader.length()? {
+            let name = reader.string()?;
+            let hash = u64::from_le_bytes(reader.array()?);
+            let document = reader.document()?;
+
+            components.insert(name, (hash, Dom::new(document)));
+        }
+
+        if !reader.0.is_empty() {
+            return Err(SnapshotError::Malformed);
+        }
+
+        Ok(Self { components })
+    }
+
+    /// Writes the snapshot out as JSON, for reading when debugging. It can't be loaded back in.
+    pub fn to_json(&self) -> String {
+        Value::from([
+            ("version", Value::Number((FORMAT_VERSION as usize).into())),
+            (
+                "components",
+                Value::Object(
+                    self.components
+                        .iter()
+                        .map(|(name, (hash, dom))| {
+                            (
+                                name.clone(),
+                                Value::from([
+                                    ("hash", Value::String(format!("{hash:016x}"))),
+                                    ("tree", document_json(&dom.tree)),
+                                ]),
+                            )
+                        })
+                        .collect(),
+                ),
+            ),
+        ])
+        .to_string()
+    }
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn length(&mut self, length: usize) {
+        self.number(length);
+    }
+
+    fn number(&mut self, mut number: usize) {
+        loop {
+            let byte = (number & 0x7f) as u8;
+            number >>= 7;
+
+            if number == 0 {
+                self.0.push(byte);
+                return;
+            }
+
+            self.0.push(byte | 0x80);
+        }
+    }
+
+    fn string(&mut self, string: &str) {
+        self.length(string.len());
+        self.0.extend(string.as_bytes());
+    }
+
+    fn values(&mut self, values: &[AttributeValues]) {
+        self.length(values.len());
+
+        for value in values {
+            match value {
+                AttributeValues::Text(text) => {
+                    self.0.push(0);
+                    self.string(&text.value);
+                }
+                AttributeValues::Variable(variable) => {
+                    self.0.push(1);
+                    self.string(&variable.name);
+                }
+            }
+        }
+    }
+
+    fn attributes(&mut self, attributes: &Attributes) {
+        self.length(attributes.len());
+
+        for (key, values) in attributes {
+            self.string(key);
+            self.values(values);
+        }
+    }
+
+    fn children(&mut self, children: &[Arc<Element>]) {
+        self.length(children.len());
+
+        for child in children {
+            self.element(child);
+        }
+    }
+
+    fn location(&mut self, location: &Option<Location>) {
+        match location {
+            Some(location) => {
+                self.0.push(1);
+                self.string(&location.component);
+                self.number(location.span.start);
+                self.number(location.span.end);
+            }
+            None => self.0.push(0),
+        }
+    }
+
+    fn document(&mut self, document: &Document) {
+        self.string(&document.name);
+        self.location(&document.location);
+        self.attributes(&document.attributes);
+
+        // Sorted so the same tree always gives the same bytes
+        let mut slot_content: Vec<_> = document.slot_content.iter().collect();
+        slot_content.sort_unstable_by_key(|(name, _)| *name);
+
+        self.length(slot_content.len());
+        for (name, content) in slot_content {
+            self.values(name);
+            self.children(content);
+        }
+
+        self.children(&document.children);
+    }
+
+    fn element(&mut self, element: &Element) {
+        match element {
+            Element::Document(document) => {
+                self.0.push(0);
+                self.document(document);
+            }
+            Element::Node(node) => {
+                self.0.push(1);
+                self.string(&node.name);
+                self.attributes(&node.attributes);
+                self.children(&node.children);
+            }
+            Element::Slot(slot) => {
+                self.0.push(2);
+                self.values(&slot.name);
+                self.location(&slot.location);
+                self.attributes(&slot.attributes);
+                self.children(&slot.children);
+            }
+            Element::Text(text) => {
+                self.0.push(3);
+                self.string(&text.value);
+            }
+            Element::Variable(variable) => {
+                self.0.push(4);
+                self.string(&variable.name);
+            }
+            Element::Void(void) => {
+                self.0.push(5);
+                self.string(&void.name);
+                self.attributes(&void.attributes);
+            }
+        }
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.0.len() < length {
+            return Err(SnapshotError::Truncated);
+        }
+
+        let (taken, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], SnapshotError> {
+        Ok(self
+            .take(N)?
+            .try_into()
+            .expect("take always returns as many bytes as asked for"))
+    }
+
+    fn byte(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn length(&mut self) -> Result<usize, SnapshotError> {
+        let length = self.number()?;
+
+        // Every length counts something that takes up at least a byte, so anything longer than
+        // what's left can't be right
+        match length <= self.0.len() {
+            true => Ok(length),
+            false => Err(SnapshotError::Truncated),
+        }
+    }
+
+    fn number(&mut self) -> Result<usize, SnapshotError> {
+        let mut number = 0usize;
+
+        for shift in (0..usize::BITS).step_by(7) {
+            let byte = self.byte()?;
+            number |= ((byte & 0x7f) as usize)
+                .checked_shl(shift)
+                .ok_or(SnapshotError::Malformed)?;
+
+            if byte & 0x80 == 0 {
+                return Ok(number);
+            }
+        }
+
+        Err(SnapshotError::Malformed)
+    }
+
+    fn string(&mut self) -> Result<String, SnapshotError> {
+        let length = self.length()?;
+        String::from_utf8(self.take(length)?.to_vec()).map_err(|_| SnapshotError::Malformed)
+    }
+
+    fn values(&mut self) -> Result<Vec<AttributeValues>, SnapshotError> {
+        (0..self.length()?)
+            .map(|_| match self.byte()? {
+                0 => Ok(AttributeValues::Text(Text {
+                    value: self.string()?.into(),
+                })),
+                1 => Ok(AttributeValues::Variable(Variable {
+                    name: self.string()?.into(),
+                })),
+                _ => Err(SnapshotError::Malformed),
+            })
+            .collect()
+    }
+
+    fn attributes(&mut self) -> Result<Attributes, SnapshotError> {
+        (0..self.length()?)
+            .map(|_| Ok((self.string()?.into(), self.values()?)))
+            .collect()
+    }
+
+    fn children(&mut self) -> Result<Vec<Arc<Element>>, SnapshotError> {
+        (0..self.length()?)
+            .map(|_| Ok(Arc::new(self.element()?)))
+            .collect()
+    }
+
+    fn location(&mut self) -> Result<Option<Location>, SnapshotError> {
+        match self.byte()? {
+            0 => Ok(None),
+            1 => Ok(Some(Location {
+                component: self.string()?.into(),
+                span: Span::new(self.number()?, self.number()?),
+            })),
+            _ => Err(SnapshotError::Malformed),
+        }
+    }
+
+    fn document(&mut self) -> Result<Document, SnapshotError> {
+        let mut document = Document::new(self.string()?);
+        document.location = self.location()?;
+        document.attributes = self.attributes()?;
+
+        for _ in 0..self.length()? {
+            let name = self.values()?;
+            document.slot_content.insert(name, self.children()?);
+        }
+
+        document.children = self.children()?;
+
+        Ok(document)
+    }
+
+    fn element(&mut self) -> Result<Element, SnapshotError> {
+        Ok(match self.byte()? {
+            0 => Element::Document(self.document()?),
+            1 => Element::Node(Node {
+                name: self.string()?,
+                attributes: self.attributes()?,
+                children: self.children()?,
+            }),
+            2 => Element::Slot(Slot {
+                name: self.values()?,
+                location: self.location()?,
+                attributes: self.attributes()?,
+                children: self.children()?,
+            }),
+            3 => Element::Text(Text {
+                value: self.string()?.into(),
+            }),
+            4 => Element::Variable(Variable {
+                name: self.string()?.into(),
+            }),
+            5 => Element::Void(Void {
+                name: self.string()?,
+                attributes: self.attributes()?,
+            }),
+            _ => return Err(SnapshotError::Malformed),
+        })
+    }
+}
+
+fn values_json(values: &[AttributeValues]) -> Value {
+    Value::Array(
+        values
+            .iter()
+            .map(|value| match value {
+                AttributeValues::Text(text) => Value::from([("text", text.value.as_str().into())]),
+                AttributeValues::Variable(variable) => {
+                    Value::from([("variable", variable.name.as_str().into())])
+                }
+            })
+            .collect(),
+    )
+}
+
+fn attributes_json(attributes: &Attributes) -> Value {
+    Value::Object(
+        attributes
+            .iter()
+            .map(|(key, values)| (key.to_string(), values_json(values)))
+            .collect(),
+    )
+}
+
+fn location_json(location: &Option<Location>) -> Value {
+    match location {
+        Some(location) => Value::from([
+            ("component", (*location.component).into()),
+            ("start", Value::Number(location.span.start.into())),
+            ("end", Value::Number(location.span.end.into())),
+        ]),
+        None => Value::Null,
+    }
+}
+
+fn children_json(children: &[Arc<Element>]) -> Value {
+    Value::Array(children.iter().map(|child| element_json(child)).collect())
+}
+
+fn document_json(document: &Document) -> Value {
+    let mut slot_content: Vec<_> = document.slot_content.iter().collect();
+    slot_content.sort_unstable_by_key(|(name, _)| *name);
+
+    Value::from([
+        ("name", document.name.as_str().into()),
+        ("location", location_json(&document.location)),
+        ("attributes", attributes_json(&document.attributes)),
+        (
+            "slot_content",
+            Value::Array(
+                slot_content
+                    .into_iter()
+                    .map(|(name, content)| {
+                        Value::from([
+                            ("slot", values_json(name)),
+                            ("content", children_json(content)),
+                        ])
+                    })
+                    .collect(),
+            ),
+        ),
+        ("children", children_json(&document.children)),
+    ])
+}
+
+fn element_json(element: &Element) -> Value {
+    match element {
+        Element::Document(document) => Value::from([("document", document_json(document))]),
+        Element::Node(node) => Value::from([(
+            "node",
+            Value::from([
+                ("name", node.name.as_str().into()),
+                ("attributes", attributes_json(&node.attributes)),
+                ("children", children_json(&node.children)),
+            ]),
+        )]),
+        Element::Slot(slot) => Value::from([(
+            "slot",
+            Value::from([
+                ("name", values_json(&slot.name)),
+                ("location", location_json(&slot.location)),
+                ("attributes", attributes_json(&slot.attributes)),
+                ("children", children_json(&slot.children)),
+            ]),
+        )]),
+        Element::Text(text) => Value::from([("text", text.value.as_str().into())]),
+        Element::Variable(variable) => Value::from([("variable", variable.name.as_str().into())]),
+        Element::Void(void) => Value::from([(
+            "void",
+            Value::from([
+                ("name", void.name.as_str().into()),
+                ("attributes", attributes_json(&void.attributes)),
+            ]),
+        )]),
+    }
+}
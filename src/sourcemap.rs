@@ -0,0 +1,156 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Source maps, which say which part of which template each part of the rendered HTML came from.
+//!
+//! They're made by [`crate::VelInstance::render_with_source_map`], which renders straight from
+//! the templates like [`crate::VelInstance::render_streaming`] does, so it knows where in them
+//! everything is. Mappings are made for text, variables, elements and their attributes. Nothing
+//! is mapped to a component's call site or a slot, what they render is mapped to where it's
+//! written instead.
+
+use crate::{json::Value, Span};
+use std::{collections::HashMap, ops::Range, sync::Arc};
+
+/// What rendered part of the output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MappingKind {
+    Text,
+    /// A variable, with its name
+    Variable(String),
+    /// An element, with its name. Its output runs from its opening tag to its closing tag.
+    Node(String),
+    /// An attribute, with its key
+    Attribute(String),
+}
+
+impl MappingKind {
+    fn name(&self) -> &'static str {
+        match self {
+            MappingKind::Text => "text",
+            MappingKind::Variable(_) => "variable",
+            MappingKind::Node(_) => "node",
+            MappingKind::Attribute(_) => "attribute",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mapping {
+    /// The bytes of the output it rendered. Whitespace and closing tags that were left out when
+    /// minifying, or added when pretty printing, aren't always included.
+    pub output: Range<usize>,
+    pub component: String,
+    /// Where it is in the component's template. Elements point at their opening tag, and
+    /// attributes at their key.
+    pub span: Span,
+    /// The line and column `span` starts at, both counting from 1
+    pub line: usize,
+    pub column: usize,
+    pub kind: MappingKind,
+}
+
+/// A mapping as it's recorded while rendering, before its line and column are worked out
+pub(crate) type RawMapping<'t> = (Range<usize>, &'t str, Span, MappingKind);
+
+/// Where each line of `source` starts, so the line of any offset can be found with a binary
+/// search instead of counting from the start each time
+pub(crate) fn line_starts(source: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+        .collect()
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    /// Sorted by where they start in the output, with elements before what's inside of them
+    mappings: Vec<Mapping>,
+}
+
+impl SourceMap {
+    pub(crate) fn new(raw: Vec<RawMapping>, templates: &HashMap<String, Arc<str>>) -> Self {
+        // Where each line starts in the templates that were used, so finding lines is quick
+        let mut lines: HashMap<&str, Vec<usize>> = HashMap::new();
+
+        let mut mappings: Vec<Mapping> = raw
+            .into_iter()
+            .map(|(output, component, span, kind)| {
+                let source = templates.get(component).map_or("", |source| source);
+                let starts = lines
+                    .entry(component)
+                    .or_insert_with(|| line_starts(source));
+
+                let line = starts.partition_point(|start| *start <= span.start);
+                let line_start = starts[line - 1];
+                let column = source
+                    .get(line_start..span.start)
+                    .map_or(0, |before| before.chars().count())
+                    + 1;
+
+                Mapping {
+                    output,
+                    component: component.to_string(),
+                    span,
+                    line,
+                    column,
+                    kind,
+                }
+            })
+            .collect();
+
+        mappings.sort_by_key(|mapping| (mapping.output.start, usize::MAX - mapping.output.end));
+
+        Self { mappings }
+    }
+
+    pub fn mappings(&self) -> &[Mapping] {
+        &self.mappings
+    }
+
+    /// Finds the innermost mapping covering byte `offset` of the output
+    pub fn at(&self, offset: usize) -> Option<&Mapping> {
+        self.mappings
+            .iter()
+            .filter(|mapping| mapping.output.contains(&offset))
+            .min_by_key(|mapping| mapping.output.len())
+    }
+
+    /// Writes the source map out as JSON, for tools outside of Rust. Each mapping is an object
+    /// with `output` and `span` as `[start, end]` byte ranges, and a `kind` of `text`,
+    /// `variable`, `node` or `attribute` which all but text also give a `name` with.
+    pub fn to_json(&self) -> String {
+        let range = |start: usize, end: usize| {
+            Value::Array(vec![Value::Number(start.into()), Value::Number(end.into())])
+        };
+
+        let mappings = self
+            .mappings
+            .iter()
+            .map(|mapping| {
+                let mut json = Value::from([
+                    ("output", range(mapping.output.start, mapping.output.end)),
+                    ("component", Value::from(mapping.component.as_str())),
+                    ("span", range(mapping.span.start, mapping.span.end)),
+                    ("line", Value::Number(mapping.line.into())),
+                    ("column", Value::Number(mapping.column.into())),
+                    ("kind", Value::from(mapping.kind.name())),
+                ]);
+
+                if let (
+                    Value::Object(entries),
+                    MappingKind::Variable(name)
+                    | MappingKind::Node(name)
+                    | MappingKind::Attribute(name),
+                ) = (&mut json, &mapping.kind)
+                {
+                    entries.insert("name".to_string(), Value::from(name.as_str()));
+                }
+
+                json
+            })
+            .collect();
+
+        Value::from([("mappings", Value::Array(mappings))]).to_string()
+    }
+}
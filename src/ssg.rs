@@ -0,0 +1,399 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Rendering a whole site to files ahead of time, so it can be served by anything that serves
+//! files.
+//!
+//! A site is a directory of components, a manifest saying which component each route renders
+//! with which inputs, and optionally a directory of assets that are copied as they are. The
+//! manifest is JSON or TOML:
+//!
+//! ```toml
+//! # Inputs every page gets
+//! [data]
+//! site = "Vel"
+//!
+//! [pages."/"]
+//! component = "Home"
+//!
+//! [pages."/blog/hello"]
+//! component = "Post"
+//! title = "Hello"
+//! ```
+//!
+//! Everything in a page besides its `component` is its inputs, flattened like
+//! [`crate::inputs`] does, and overrides the `data` shared by every page. `/blog/hello` is
+//! written to `blog/hello/index.html`, while routes that end with a file name, like `/404.html`,
+//! are written as that file.
+//!
+//! Building a site again only renders the pages whose inputs or components changed since the
+//! last build, including the components they use, and only copies the assets that changed.
+
+use crate::{
+    inputs::{self, InputsError},
+    json::Value,
+    loading::{LoadingError, Naming},
+    watch::{self, PollWatcher, Stamp},
+    RenderingError, VelInstance,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+#[derive(Debug, Clone)]
+pub struct SiteConfig {
+    /// Where the components are
+    pub components: PathBuf,
+    pub naming: Naming,
+    /// The JSON or TOML file listing the pages
+    pub manifest: PathBuf,
+    /// Files copied into the output as they are
+    pub assets: Option<PathBuf>,
+    /// Where the site is written
+    pub output: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum SiteError {
+    Loading(LoadingError),
+    /// The manifest couldn't be read as JSON or TOML
+    Manifest(InputsError),
+    /// The manifest was read, but isn't laid out like one
+    InvalidManifest(String),
+    /// A route that can't be turned into a path inside the output, like `/../secrets`
+    InvalidRoute(String),
+    Io(PathBuf, io::Error),
+}
+
+impl fmt::Display for SiteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SiteError::Loading(error) => write!(f, "{error}"),
+            SiteError::Manifest(error) => write!(f, "failed to read the manifest: {error}"),
+            SiteError::InvalidManifest(message) => write!(f, "invalid manifest: {message}"),
+            SiteError::InvalidRoute(route) => {
+                write!(f, "`{route}` can't be written inside the output")
+            }
+            SiteError::Io(path, error) => write!(f, "{}: {error}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for SiteError {}
+
+impl From<LoadingError> for SiteError {
+    fn from(error: LoadingError) -> Self {
+        SiteError::Loading(error)
+    }
+}
+
+impl From<InputsError> for SiteError {
+    fn from(error: InputsError) -> Self {
+        SiteError::Manifest(error)
+    }
+}
+
+/// A route and what it's rendered from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page {
+    pub route: String,
+    pub component: String,
+    pub inputs: HashMap<String, String>,
+}
+
+/// What a build did
+#[derive(Debug, Default)]
+pub struct BuildReport {
+    /// The routes that were rendered, in order
+    pub rendered: Vec<String>,
+    /// How many pages were already up to date
+    pub unchanged: usize,
+    /// Routes that were taken out of the manifest, whose files were deleted
+    pub removed: Vec<String>,
+    /// Pages that couldn't be rendered, which are tried again on the next build
+    pub failed: Vec<(String, RenderingError)>,
+    /// How many assets were copied
+    pub assets: usize,
+}
+
+/// A site that's kept around between builds, so rebuilding it only does what's needed.
+#[derive(Debug)]
+pub struct Site {
+    config: SiteConfig,
+    instance: VelInstance,
+    /// What every page was last rendered from, which pages that failed are left out of
+    built: HashMap<String, Page>,
+    /// Every route in the manifest the last time it was read
+    routes: HashSet<String>,
+    assets: HashMap<PathBuf, Stamp>,
+    watchers: Vec<PollWatcher>,
+    manifest: Option<Stamp>,
+}
+
+impl Site {
+    /// Loads the site's components. Nothing is written until [`Self::build`].
+    pub fn new(config: SiteConfig) -> Result<Self, SiteError> {
+        let instance = VelInstance::from_dir(&config.components, config.naming)?;
+
+        Ok(Self {
+            config,
+            instance,
+            built: HashMap::new(),
+            routes: HashSet::new(),
+            assets: HashMap::new(),
+            watchers: vec![],
+            manifest: None,
+        })
+    }
+
+    pub fn config(&self) -> &SiteConfig {
+        &self.config
+    }
+
+    /// The instance pages are rendered with, for adding plugins
+    pub fn instance_mut(&mut self) -> &mut VelInstance {
+        // Anything already built could render differently now
+        self.built.clear();
+        &mut self.instance
+    }
+
+    /// Reads the manifest, without rendering anything.
+    pub fn pages(&self) -> Result<Vec<Page>, SiteError> {
+        let path = &self.config.manifest;
+        let source =
+            fs::read_to_string(path).map_err(|error| SiteError::Io(path.clone(), error))?;
+        let mut manifest = inputs::parse_file_contents(path, &source)?;
+
+        let data = match manifest.remove("data") {
+            None => HashMap::new(),
+            Some(Value::Object(entries)) => inputs::flatten(entries)?,
+            Some(_) => return invalid("`data` should be a table"),
+        };
+
+        let Some(Value::Object(entries)) = manifest.remove("pages") else {
+            return invalid("expected a `pages` table");
+        };
+
+        if let Some(key) = manifest.keys().next() {
+            return invalid(&format!("unknown key `{key}`"));
+        }
+
+        let mut pages = vec![];
+        for (route, page) in entries {
+            let Value::Object(mut page) = page else {
+                return invalid(&format!("`{route}` should be a table"));
+            };
+            let Some(Value::String(component)) = page.remove("component") else {
+                return invalid(&format!("`{route}` needs a `component` to render"));
+            };
+
+            let mut inputs = data.clone();
+            inputs.extend(inputs::flatten(page)?);
+
+            pages.push(Page {
+                route,
+                component,
+                inputs,
+            });
+        }
+
+        Ok(pages)
+    }
+
+    /// Renders every page that changed since the last build, and copies every asset that did.
+    /// The first build renders everything.
+    ///
+    /// Components that were deleted are kept around until the site is made again.
+    pub fn build(&mut self) -> Result<BuildReport, SiteError> {
+        let mut report = BuildReport::default();
+
+        // Since every page has been parsed by the time it's built, reloading resets every page
+        // that uses a component that changed, however deeply
+        let changed: HashSet<String> = self
+            .instance
+            .reload_dir(&self.config.components, self.config.naming)?
+            .into_iter()
+            .collect();
+
+        self.manifest = stamp(&self.config.manifest);
+        let pages = self.pages()?;
+        let mut outputs = Vec::with_capacity(pages.len());
+        for page in &pages {
+            let path = output_path(&page.route)
+                .ok_or_else(|| SiteError::InvalidRoute(page.route.clone()))?;
+            outputs.push(self.config.output.join(path));
+        }
+
+        for (page, output) in pages.iter().zip(outputs) {
+            let up_to_date = self.built.get(&page.route) == Some(page);
+            if up_to_date && !changed.contains(&page.component) {
+                report.unchanged += 1;
+                continue;
+            }
+
+            self.built.remove(&page.route);
+            let html = match self
+                .instance
+                .render(page.component.clone(), page.inputs.clone(), Some)
+            {
+                Ok(html) => html,
+                Err(error) => {
+                    report.failed.push((page.route.clone(), error));
+                    continue;
+                }
+            };
+
+            write(&output, html.as_bytes())?;
+            self.built.insert(page.route.clone(), page.clone());
+            report.rendered.push(page.route.clone());
+        }
+
+        let routes: HashSet<String> = pages.into_iter().map(|page| page.route).collect();
+        let mut removed: Vec<&String> = self.routes.difference(&routes).collect();
+        removed.sort_unstable();
+
+        for route in removed {
+            self.built.remove(route);
+            if let Some(path) = output_path(route) {
+                remove(&self.config.output.join(path))?;
+            }
+            report.removed.push(route.clone());
+        }
+        self.routes = routes;
+
+        report.assets = self.copy_assets()?;
+
+        Ok(report)
+    }
+
+    /// Copies every asset that changed, and deletes the copies of ones that were deleted
+    fn copy_assets(&mut self) -> Result<usize, SiteError> {
+        let Some(root) = &self.config.assets else {
+            return Ok(0);
+        };
+
+        let assets = watch::snapshot(root).map_err(|error| SiteError::Io(root.clone(), error))?;
+        let mut copied = 0;
+
+        for (path, stamp) in &assets {
+            if self.assets.get(path) == Some(stamp) {
+                continue;
+            }
+
+            let output = self.asset_output(root, path);
+            let contents = fs::read(path).map_err(|error| SiteError::Io(path.clone(), error))?;
+            write(&output, &contents)?;
+            copied += 1;
+        }
+
+        for path in self.assets.keys() {
+            if !assets.contains_key(path) {
+                remove(&self.asset_output(root, path))?;
+            }
+        }
+
+        self.assets = assets;
+
+        Ok(copied)
+    }
+
+    fn asset_output(&self, root: &Path, path: &Path) -> PathBuf {
+        self.config
+            .output
+            .join(path.strip_prefix(root).unwrap_or(path))
+    }
+
+    /// Blocks, polling every `interval`, until the components, manifest or assets change, then
+    /// builds the site again. When polling fails it still waits out the interval before saying
+    /// so, so callers that wait again straight away don't spin.
+    pub fn wait(&mut self, interval: Duration) -> Result<BuildReport, SiteError> {
+        loop {
+            match self.changed() {
+                Ok(true) => return self.build(),
+                Ok(false) => thread::sleep(interval),
+                Err(error) => {
+                    thread::sleep(interval);
+                    return Err(error);
+                }
+            }
+        }
+    }
+
+    /// Polls the components, manifest and assets once for changes
+    fn changed(&mut self) -> Result<bool, SiteError> {
+        if self.watchers.is_empty() {
+            for root in [Some(&self.config.components), self.config.assets.as_ref()]
+                .into_iter()
+                .flatten()
+            {
+                let watcher =
+                    PollWatcher::new(root).map_err(|error| SiteError::Io(root.clone(), error))?;
+                self.watchers.push(watcher);
+            }
+        }
+
+        let mut changed = stamp(&self.config.manifest) != self.manifest;
+        for watcher in &mut self.watchers {
+            let root = watcher.root().to_path_buf();
+            changed |= !watcher
+                .poll()
+                .map_err(|error| SiteError::Io(root, error))?
+                .is_empty();
+        }
+
+        Ok(changed)
+    }
+}
+
+fn invalid<T>(message: &str) -> Result<T, SiteError> {
+    Err(SiteError::InvalidManifest(message.to_string()))
+}
+
+fn stamp(path: &Path) -> Option<Stamp> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+fn write(path: &Path, contents: &[u8]) -> Result<(), SiteError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|error| SiteError::Io(parent.to_path_buf(), error))?;
+    }
+
+    fs::write(path, contents).map_err(|error| SiteError::Io(path.to_path_buf(), error))
+}
+
+fn remove(path: &Path) -> Result<(), SiteError> {
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => {
+            Err(SiteError::Io(path.to_path_buf(), error))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Where a route is written to inside the output directory, like `blog/hello/index.html` for
+/// `/blog/hello`. Routes that end with a file name, like `/404.html`, are written as that file.
+///
+/// Returns [`None`] for routes that don't start with a `/` or would escape the output directory.
+pub fn output_path(route: &str) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+
+    for part in route.strip_prefix('/')?.split('/') {
+        match part {
+            "" => continue,
+            "." | ".." => return None,
+            _ if part.contains('\\') => return None,
+            _ => path.push(part),
+        }
+    }
+
+    match route.ends_with('/') || path.extension().is_none() {
+        true => Some(path.join("index.html")),
+        false => Some(path),
+    }
+}
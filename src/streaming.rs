@@ -0,0 +1,498 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Rendering straight from the templates' tokens, without ever building a Dom.
+//!
+//! Components are rendered where they're used, and the content given to their slots is only
+//! split up, not parsed, until the slot it's for is rendered. Because of that, mistakes in slot
+//! content that never ends up rendered aren't reported, unlike when rendering from a Dom.
+
+use crate::{
+    ast::elements::AttributeValues,
+    lexing::{offset_of, unescape_into, Lexer, RawAttribute, Token},
+    output::{HtmlWriter, Origins, RenderOptions},
+    parsing::{self, Span, VOID_ELEMENTS},
+    sourcemap::{MappingKind, RawMapping, SourceMap},
+    ParsingError, ParsingErrorKind, RenderingError,
+};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+/// The slot content given at a call site, as where each of its top level children are in the
+/// caller's template
+type SlotFragments<'t> = Vec<(Vec<AttributeValues>, Vec<(&'t str, Span)>)>;
+
+struct Scope<'a, 't> {
+    /// The component whose template is being rendered, for errors
+    component: &'t str,
+    inputs: Cow<'a, HashMap<String, String>>,
+    slots: Option<&'a SlotFragments<'t>>,
+    parent: Option<&'a Scope<'a, 't>>,
+}
+
+impl Scope<'_, '_> {
+    fn error(&self, kind: ParsingErrorKind, span: Span) -> RenderingError {
+        RenderingError::Parsing(ParsingError::new(self.component, kind, span))
+    }
+
+    fn get(&self, name: &str) -> Result<&str, RenderingError> {
+        self.inputs
+            .get(name)
+            .map(String::as_str)
+            .ok_or_else(|| RenderingError::MissingInput(name.to_string()))
+    }
+
+    /// Fills in the variables of a raw attribute value. Returns [`None`] for attributes without
+    /// a value, which are rendered without an `=`.
+    fn evaluate(&self, raw: &str) -> Result<Option<String>, RenderingError> {
+        let mut value = String::new();
+        let mut has_value = false;
+        let mut chars = raw.chars();
+
+        while let Some(char) = chars.next() {
+            match char {
+                '{' => {
+                    let name: String = chars.by_ref().take_while(|char| char != &'}').collect();
+                    value.push_str(self.get(&name)?);
+                    has_value = true;
+                }
+                '\\' => {
+                    if let Some(char) = chars.next() {
+                        value.push(char);
+                        has_value = true;
+                    }
+                }
+                char => {
+                    value.push(char);
+                    has_value = true;
+                }
+            }
+        }
+
+        Ok(has_value.then_some(value))
+    }
+}
+
+/// Gets the value of an attribute, where the last one wins if it's given more than once
+fn attribute<'t>(attributes: &[RawAttribute<'t>], key: &str) -> Option<&'t str> {
+    attributes
+        .iter()
+        .rev()
+        .find(|(name, _)| *name == key)
+        .map(|(_, value)| *value)
+}
+
+fn slot_name(raw: Option<&str>) -> Vec<AttributeValues> {
+    parsing::parse_attribute_value(raw.unwrap_or("default"))
+}
+
+/// How a run of tokens came to an end
+enum Ending<'t> {
+    Close(&'t str, Span),
+    End,
+}
+
+struct Streamer<'t> {
+    templates: &'t HashMap<String, Arc<str>>,
+    output: HtmlWriter,
+    /// What's been rendered so far, when making a source map
+    mappings: Option<Vec<RawMapping<'t>>>,
+}
+
+impl<'t> Streamer<'t> {
+    /// Runs `write`, mapping what it renders back to `span` of the scope's template when making
+    /// a source map
+    fn mapped(
+        &mut self,
+        scope: &Scope<'_, 't>,
+        span: Span,
+        kind: impl FnOnce() -> MappingKind,
+        write: impl FnOnce(&mut Self) -> Result<(), RenderingError>,
+    ) -> Result<(), RenderingError> {
+        if self.mappings.is_none() {
+            return write(self);
+        }
+
+        self.output.begin();
+        write(self)?;
+
+        if let (Some(output), Some(mappings)) = (self.output.end(), &mut self.mappings) {
+            mappings.push((output, scope.component, span, kind()));
+        }
+
+        Ok(())
+    }
+
+    fn attributes(
+        &mut self,
+        attributes: &[RawAttribute<'t>],
+        source: &'t str,
+        scope: &Scope<'_, 't>,
+    ) -> Result<(), RenderingError> {
+        // Sorted, with later duplicates winning, to match rendering from a Dom
+        let attributes: BTreeMap<&str, &RawAttribute> = attributes
+            .iter()
+            .map(|attribute| (attribute.0, attribute))
+            .collect();
+
+        for (key, raw) in attributes.into_values() {
+            let start = offset_of(source, key);
+            let span = Span::new(start, start + key.len());
+
+            self.mapped(
+                scope,
+                span,
+                || MappingKind::Attribute(key.to_string()),
+                |this| {
+                    let value = scope.evaluate(raw)?;
+                    this.output.attribute(key, value.as_deref());
+                    Ok(())
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves past everything up to the tag closing `name`, which was opened at `open`, without
+    /// rendering any of it
+    fn skip(
+        &self,
+        lexer: &mut Lexer<'t>,
+        name: &str,
+        open: Span,
+        scope: &Scope,
+    ) -> Result<(), RenderingError> {
+        loop {
+            match lexer.next_spanned() {
+                (Token::Open { name, .. }, span) if !VOID_ELEMENTS.contains(&name) => {
+                    self.skip(lexer, name, span, scope)?
+                }
+                (Token::Close(found), _) if found == name => return Ok(()),
+                (Token::Close(found), span) => {
+                    return Err(scope.error(
+                        ParsingErrorKind::MismatchedClosingTag {
+                            expected: name.to_string(),
+                            found: found.to_string(),
+                        },
+                        span,
+                    ))
+                }
+                (Token::End, _) => {
+                    return Err(
+                        scope.error(ParsingErrorKind::UnclosedElement(name.to_string()), open)
+                    )
+                }
+                (
+                    Token::Text(_) | Token::Variable(_) | Token::Comment(_) | Token::Open { .. },
+                    _,
+                ) => (),
+            }
+        }
+    }
+
+    /// Splits up the content between a component's tags by the slot each child goes in
+    fn fragments(
+        &self,
+        lexer: &mut Lexer<'t>,
+        component: &str,
+        open: Span,
+        scope: &Scope,
+    ) -> Result<SlotFragments<'t>, RenderingError> {
+        let mut fragments: SlotFragments = vec![];
+
+        loop {
+            let (token, span) = lexer.next_spanned();
+
+            let slot = match token {
+                Token::Text(_) | Token::Variable(_) => slot_name(None),
+                Token::Comment(_) => continue,
+                Token::Open { name, attributes } => {
+                    if !VOID_ELEMENTS.contains(&name) {
+                        self.skip(lexer, name, span, scope)?;
+                    }
+
+                    slot_name(attribute(&attributes, "slot"))
+                }
+                Token::Close(found) if found == component => return Ok(fragments),
+                Token::Close(found) => {
+                    return Err(scope.error(
+                        ParsingErrorKind::MismatchedClosingTag {
+                            expected: component.to_string(),
+                            found: found.to_string(),
+                        },
+                        span,
+                    ))
+                }
+                Token::End => {
+                    return Err(scope.error(
+                        ParsingErrorKind::UnclosedElement(component.to_string()),
+                        open,
+                    ))
+                }
+            };
+
+            let fragment = (lexer.source(), Span::new(span.start, lexer.position()));
+
+            match fragments.iter_mut().find(|(name, _)| *name == slot) {
+                Some((_, content)) => content.push(fragment),
+                None => fragments.push((slot, vec![fragment])),
+            }
+        }
+    }
+
+    fn component(
+        &mut self,
+        lexer: &mut Lexer<'t>,
+        name: &'t str,
+        attributes: &[RawAttribute<'t>],
+        open: Span,
+        scope: &Scope<'_, 't>,
+    ) -> Result<(), RenderingError> {
+        // Slot content is rendered in its caller's scope, so only the components it's really
+        // inside of are in the chain
+        if std::iter::successors(Some(scope), |scope| scope.parent)
+            .any(|scope| scope.component == name)
+        {
+            return Err(scope.error(ParsingErrorKind::RecursiveComponent(name.to_string()), open));
+        }
+
+        let Some(template) = self.templates.get(name) else {
+            return Err(scope.error(ParsingErrorKind::UnknownComponent(name.to_string()), open));
+        };
+
+        let fragments = self.fragments(lexer, name, open, scope)?;
+
+        let mut inputs = HashMap::new();
+        for (key, raw) in attributes {
+            inputs.insert(key.to_string(), scope.evaluate(raw)?.unwrap_or_default());
+        }
+
+        let component_scope = Scope {
+            component: name,
+            inputs: Cow::Owned(inputs),
+            slots: Some(&fragments),
+            parent: Some(scope),
+        };
+
+        self.output
+            .open_component(name, Some((scope.component, open.start)));
+        self.body(&mut Lexer::new(template), &component_scope)?;
+        self.output.close_component(name);
+
+        Ok(())
+    }
+
+    fn slot(
+        &mut self,
+        lexer: &mut Lexer<'t>,
+        attributes: &[RawAttribute<'t>],
+        open: Span,
+        scope: &Scope<'_, 't>,
+    ) -> Result<(), RenderingError> {
+        let name = slot_name(attribute(attributes, "name"));
+
+        let content = scope
+            .slots
+            .and_then(|slots| slots.iter().find(|(slot, _)| *slot == name));
+
+        self.output
+            .open_slot(scope.component, &name, Some(open.start));
+        match (content, scope.parent) {
+            // Slot content belongs to the caller, so it's rendered with their inputs
+            (Some((_, content)), Some(parent)) => {
+                self.skip(lexer, "Slot", open, scope)?;
+
+                for (template, span) in content {
+                    self.children(&mut Lexer::within(template, *span), parent, true)?;
+                }
+            }
+            _ => self.element(lexer, "Slot", open, scope, false)?,
+        }
+        self.output.close_slot(scope.component);
+
+        Ok(())
+    }
+
+    /// Renders the children of an element and its closing tag
+    fn element(
+        &mut self,
+        lexer: &mut Lexer<'t>,
+        name: &str,
+        open: Span,
+        scope: &Scope<'_, 't>,
+        tags: bool,
+    ) -> Result<(), RenderingError> {
+        match self.children(lexer, scope, false)? {
+            Ending::Close(found, _) if found == name => (),
+            Ending::Close(found, span) => {
+                return Err(scope.error(
+                    ParsingErrorKind::MismatchedClosingTag {
+                        expected: name.to_string(),
+                        found: found.to_string(),
+                    },
+                    span,
+                ))
+            }
+            Ending::End => {
+                return Err(scope.error(ParsingErrorKind::UnclosedElement(name.to_string()), open))
+            }
+        }
+
+        if tags {
+            self.output.close_tag(name);
+        }
+
+        Ok(())
+    }
+
+    /// Renders a component's template, which ends once it runs out
+    fn body(&mut self, lexer: &mut Lexer<'t>, scope: &Scope<'_, 't>) -> Result<(), RenderingError> {
+        match self.children(lexer, scope, false)? {
+            // Closing the component itself finishes its body early, like it does when parsing
+            Ending::Close(name, _) if name == scope.component => Ok(()),
+            Ending::Close(name, span) => Err(scope.error(
+                ParsingErrorKind::UnexpectedClosingTag(name.to_string()),
+                span,
+            )),
+            Ending::End => Ok(()),
+        }
+    }
+
+    /// Renders tokens until a closing tag or the end of the template. `slot_content` is set when
+    /// they were given as slot content, where the `slot` attribute only picks the slot.
+    fn children(
+        &mut self,
+        lexer: &mut Lexer<'t>,
+        scope: &Scope<'_, 't>,
+        slot_content: bool,
+    ) -> Result<Ending<'t>, RenderingError> {
+        loop {
+            match lexer.next_spanned() {
+                (Token::Text(raw), span) => {
+                    let mut text = String::with_capacity(raw.len());
+                    unescape_into(&mut text, raw);
+                    self.mapped(
+                        scope,
+                        span,
+                        || MappingKind::Text,
+                        |this| {
+                            this.output.text(&text);
+                            Ok(())
+                        },
+                    )?;
+                }
+                (Token::Variable(name), span) => {
+                    let kind = || MappingKind::Variable(name.to_string());
+                    self.mapped(scope, span, kind, |this| {
+                        this.output.escaped(scope.get(name)?);
+                        Ok(())
+                    })?;
+                }
+                (
+                    Token::Open {
+                        name,
+                        mut attributes,
+                    },
+                    open,
+                ) => {
+                    if slot_content {
+                        attributes.retain(|(key, _)| *key != "slot");
+                    }
+
+                    if name == "Slot" {
+                        self.slot(lexer, &attributes, open, scope)?;
+                    } else if parsing::is_component_name(name) {
+                        self.component(lexer, name, &attributes, open, scope)?;
+                    } else {
+                        let void = VOID_ELEMENTS.contains(&name);
+                        let source = lexer.source();
+                        let kind = || MappingKind::Node(name.to_string());
+
+                        self.mapped(scope, open, kind, |this| {
+                            this.output.open_tag(name);
+                            this.attributes(&attributes, source, scope)?;
+                            this.output.end_open_tag(name, void);
+
+                            if !void {
+                                this.element(lexer, name, open, scope, true)?;
+                            }
+
+                            Ok(())
+                        })?;
+                    }
+                }
+                (Token::Comment(_), _) => (),
+                (Token::Close(name), span) => return Ok(Ending::Close(name, span)),
+                (Token::End, _) => return Ok(Ending::End),
+            }
+        }
+    }
+}
+
+/// Renders `component` from `templates`, which needs to have the template of every component it
+/// uses.
+pub(crate) fn render(
+    templates: &HashMap<String, Arc<str>>,
+    component: &str,
+    inputs: &HashMap<String, String>,
+    options: &RenderOptions,
+    origins: Origins,
+) -> Result<String, RenderingError> {
+    stream(templates, component, inputs, options, origins, false).map(|(output, _)| output)
+}
+
+/// Renders like [`render`], and makes a source map of where in the templates the output came
+/// from.
+pub(crate) fn render_mapped(
+    templates: &HashMap<String, Arc<str>>,
+    component: &str,
+    inputs: &HashMap<String, String>,
+    options: &RenderOptions,
+    origins: Origins,
+) -> Result<(String, SourceMap), RenderingError> {
+    let (output, mappings) = stream(templates, component, inputs, options, origins, true)?;
+    let map = SourceMap::new(mappings.unwrap_or_default(), templates);
+
+    Ok((output, map))
+}
+
+fn stream<'t>(
+    templates: &'t HashMap<String, Arc<str>>,
+    component: &str,
+    inputs: &HashMap<String, String>,
+    options: &RenderOptions,
+    origins: Origins,
+    map: bool,
+) -> Result<(String, Option<Vec<RawMapping<'t>>>), RenderingError> {
+    let (component, template) = templates.get_key_value(component).ok_or_else(|| {
+        RenderingError::Parsing(ParsingError::new(
+            component,
+            ParsingErrorKind::UnknownComponent(component.to_string()),
+            Span::default(),
+        ))
+    })?;
+
+    let mut streamer = Streamer {
+        templates,
+        output: HtmlWriter::new(options).with_origins(origins),
+        mappings: map.then(Vec::new),
+    };
+
+    let scope = Scope {
+        component,
+        inputs: Cow::Borrowed(inputs),
+        slots: None,
+        parent: None,
+    };
+
+    streamer.output.open_root(component);
+    streamer.body(&mut Lexer::new(template), &scope)?;
+    streamer.output.close_component(component);
+
+    Ok((streamer.output.finish(), streamer.mappings))
+}
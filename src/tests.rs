@@ -0,0 +1,1515 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use crate::{
+    ast::{
+        elements::{AttributeValues, Element, Node, TemplateStr, Variable},
+        Dom,
+    },
+    builder,
+    codegen::{self, CodegenError},
+    cst::Cst,
+    format::{self, FormatOptions},
+    inputs::{self, InputsError},
+    json,
+    loading::{
+        self, ChainedSource, ComponentSource, DirectorySource, LoadingError, MemorySource, Naming,
+    },
+    lsp,
+    output::{OutputStyle, RenderOptions},
+    plugin::{Plugin, RenderedAttributes, Transform},
+    query::{Selector, SelectorError},
+    rendering,
+    serve::{self, Server},
+    shared::SharedVelInstance,
+    snapshot::{Snapshot, SnapshotError},
+    sourcemap::MappingKind,
+    ssg::{self, Site, SiteConfig},
+    unused::UndeclaredSlot,
+    visit::{self, Visit, VisitMut},
+    watch::PollWatcher,
+    LazyDom, ParsingError, ParsingErrorKind, RenderingError, Span, VelInstance,
+};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Read, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+
+fn components(templates: &[(&str, &str)]) -> HashMap<String, String> {
+    templates
+        .iter()
+        .map(|(name, template)| (name.to_string(), template.to_string()))
+        .collect()
+}
+
+/// Makes a fresh directory under the system temp dir filled with the given files.
+fn temp_components(test_name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("vel-{test_name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+
+    for (path, contents) in files {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    root
+}
+
+#[test]
+fn load_dir_names_components() {
+    let root = temp_components(
+        "load-dir-names",
+        &[
+            ("Page.vel", "<p>page</p>"),
+            ("ui/Button.vel", "<button></button>"),
+            ("ui/notes.txt", "not a component"),
+        ],
+    );
+
+    let stems = loading::load_dir(&root, Naming::FileStem).unwrap();
+    assert_eq!(stems.len(), 2);
+    assert_eq!(stems["Button"], "<button></button>");
+
+    let paths = loading::load_dir(&root, Naming::RelativePath).unwrap();
+    assert!(paths.contains_key("Page"));
+    assert!(paths.contains_key("ui.Button"));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn load_dir_reports_duplicates() {
+    let root = temp_components(
+        "load-dir-duplicates",
+        &[("a/Card.vel", ""), ("b/Card.vel", ""), ("Page.vel", "")],
+    );
+
+    match loading::load_dir(&root, Naming::FileStem) {
+        Err(LoadingError::DuplicateNames(duplicates)) => {
+            assert_eq!(duplicates.len(), 1);
+            assert_eq!(duplicates[0].0, "Card");
+            assert_eq!(duplicates[0].1.len(), 2);
+        }
+        other => panic!("expected duplicate names, got {other:?}"),
+    }
+
+    assert!(loading::load_dir(&root, Naming::RelativePath).is_ok());
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn scan_dir_skips_symlinked_directories() {
+    let root = temp_components(
+        "scan-dir-symlinks",
+        &[("ui/Button.vel", "<button></button>")],
+    );
+    std::os::unix::fs::symlink(&root, root.join("ui/loop")).unwrap();
+
+    let paths = loading::scan_dir(&root, Naming::RelativePath).unwrap();
+    assert_eq!(paths.into_keys().collect::<Vec<_>>(), ["ui.Button"]);
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn directory_source_finds_stems_that_moved() {
+    let root = temp_components(
+        "directory-source-stems",
+        &[("ui/Button.vel", "<button></button>")],
+    );
+    let source = DirectorySource::new(&root, Naming::FileStem);
+
+    assert_eq!(source.path_of("Button"), Some(root.join("ui/Button.vel")));
+    assert_eq!(source.load("Card"), None);
+
+    fs::create_dir_all(root.join("forms")).unwrap();
+    fs::rename(root.join("ui/Button.vel"), root.join("forms/Button.vel")).unwrap();
+    fs::write(root.join("Card.vel"), "<div></div>").unwrap();
+
+    assert_eq!(
+        source.path_of("Button"),
+        Some(root.join("forms/Button.vel"))
+    );
+    assert_eq!(source.load("Card").as_deref(), Some("<div></div>"));
+
+    // Names that weren't there are remembered until the source is refreshed
+    assert_eq!(source.load("Nav"), None);
+    fs::write(root.join("Nav.vel"), "<nav></nav>").unwrap();
+    assert_eq!(source.load("Nav"), None);
+    source.refresh().unwrap();
+    assert_eq!(source.load("Nav").as_deref(), Some("<nav></nav>"));
+
+    // A scan that fails keeps what was found before
+    fs::write(root.join("forms/Card.vel"), "<div></div>").unwrap();
+    assert!(matches!(
+        source.refresh(),
+        Err(LoadingError::DuplicateNames(_))
+    ));
+    assert_eq!(
+        source.path_of("Button"),
+        Some(root.join("forms/Button.vel"))
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn parse_falls_back_to_source() {
+    let mut instance = VelInstance::new(components(&[(
+        "Page",
+        "<main><Card title=\"hi\"><p>body</p></Card></main>",
+    )]));
+
+    assert!(instance.parse("Page".to_string()).is_err());
+
+    instance.set_source(
+        ChainedSource::new()
+            .or(MemorySource::new())
+            .or(MemorySource::from([(
+                "Card",
+                "<div>{title}<Slot></Slot></div>",
+            )])),
+    );
+
+    assert!(instance.parse("Page".to_string()).is_ok());
+    assert!(matches!(
+        instance.components.get_mut("Card"),
+        Some(LazyDom::Parsed(_))
+    ));
+}
+
+#[test]
+fn render_fills_slots_and_inputs() {
+    let mut instance = VelInstance::new(components(&[
+        (
+            "Page",
+            "<main><Card title=\"Hi {name}\"><b>{name}</b><i slot=footer>bye</i></Card></main>",
+        ),
+        (
+            "Card",
+            "<h1 class=title>{title}</h1><Slot></Slot><Slot name=footer></Slot><Slot name=empty>fallback</Slot>",
+        ),
+    ]));
+
+    let inputs = HashMap::from([("name".to_string(), "<Name>".to_string())]);
+
+    assert_eq!(
+        instance.render("Page".to_string(), inputs, Some).unwrap(),
+        "<main><h1 class=\"title\">Hi &lt;Name&gt;</h1><b>&lt;Name&gt;</b><i>bye</i>fallback</main>"
+    );
+    assert_eq!(
+        instance.render("Page".to_string(), HashMap::new(), Some),
+        Err(RenderingError::MissingInput("name".to_string()))
+    );
+}
+
+#[test]
+fn reload_invalidates_dependants() {
+    let mut instance = VelInstance::new(components(&[
+        ("Page", "<Layout><Card></Card></Layout>"),
+        ("Layout", "<main><Slot></Slot></main>"),
+        ("Card", "<p>old</p>"),
+        ("Other", "<p>other</p>"),
+    ]));
+
+    assert_eq!(
+        instance
+            .render_cached("Page".to_string(), HashMap::new())
+            .unwrap(),
+        "<main><p>old</p></main>"
+    );
+    instance.parse("Other".to_string()).unwrap();
+
+    assert_eq!(
+        instance.reload(components(&[
+            ("Card", "<p>new</p>"),
+            ("Other", "<p>other</p>")
+        ])),
+        vec!["Card", "Page"]
+    );
+    assert!(matches!(
+        instance.components.get_mut("Other"),
+        Some(LazyDom::Parsed(_))
+    ));
+    assert_eq!(
+        instance
+            .render_cached("Page".to_string(), HashMap::new())
+            .unwrap(),
+        "<main><p>new</p></main>"
+    );
+}
+
+#[test]
+fn poll_watcher_sees_changes() {
+    let root = temp_components("poll-watcher", &[("Page.vel", "<p>page</p>")]);
+    let mut watcher = PollWatcher::new(&root).unwrap();
+
+    assert!(watcher.poll().unwrap().is_empty());
+
+    fs::write(root.join("Page.vel"), "<p>changed page</p>").unwrap();
+    fs::write(root.join("Card.vel"), "<p>card</p>").unwrap();
+
+    assert_eq!(
+        watcher.poll().unwrap(),
+        vec![root.join("Card.vel"), root.join("Page.vel")]
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn graph_follows_component_uses() {
+    let mut instance = VelInstance::new(components(&[
+        ("Page", "<Layout><Card></Card></Layout>"),
+        ("Layout", "<main><Slot></Slot></main>"),
+        ("Card", "<Icon></Icon>"),
+        ("Icon", "<svg></svg>"),
+    ]));
+
+    let graph = instance.graph().unwrap();
+
+    assert_eq!(
+        graph.dependencies("Page").collect::<Vec<_>>(),
+        vec!["Card", "Layout"]
+    );
+    assert_eq!(graph.dependants("Card").collect::<Vec<_>>(), vec!["Page"]);
+    assert_eq!(
+        graph.topological_order(),
+        vec!["Icon", "Card", "Layout", "Page"]
+    );
+    assert!(graph.to_dot().contains("    \"Page\" -> \"Layout\";\n"));
+}
+
+#[test]
+fn unused_reports_components_and_slots() {
+    let mut instance = VelInstance::new(components(&[
+        (
+            "Page",
+            "<Layout>\n  <p>body</p>\n  <p slot=sidebar>side</p>\n</Layout>",
+        ),
+        (
+            "Layout",
+            "<main><Slot></Slot><Slot name=footer></Slot></main>",
+        ),
+        ("Old", "<Layout></Layout>"),
+        ("Orphan", "<p>nobody uses me</p>"),
+    ]));
+
+    let report = instance.unused(&["Page"]).unwrap();
+
+    assert_eq!(report.unreachable, vec!["Old", "Orphan"]);
+    assert_eq!(
+        report.unfilled_slots,
+        vec![("Layout".to_string(), "footer".to_string())]
+    );
+    assert_eq!(
+        report.undeclared_slots,
+        vec![UndeclaredSlot {
+            caller: "Page".to_string(),
+            component: "Layout".to_string(),
+            slot: "sidebar".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn shared_instance_renders_across_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<SharedVelInstance>();
+
+    let mut instance = VelInstance::new(components(&[
+        ("Page", "<Layout><Card title={title}></Card></Layout>"),
+        ("Layout", "<main><Slot></Slot></main>"),
+        ("Card", "<h1>{title}</h1>"),
+        ("Loop", "<Loop></Loop>"),
+        ("Ping", "<Pong></Pong>"),
+        ("Pong", "<Ping></Ping>"),
+    ]));
+    instance.parse("Layout".to_string()).unwrap();
+
+    let shared = Arc::new(SharedVelInstance::from(instance));
+
+    let threads: Vec<_> = (0..8)
+        .map(|thread| {
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                let inputs = HashMap::from([("title".to_string(), thread.to_string())]);
+                shared.render("Page", &inputs, Some).unwrap()
+            })
+        })
+        .collect();
+
+    for (thread, handle) in threads.into_iter().enumerate() {
+        assert_eq!(
+            handle.join().unwrap(),
+            format!("<main><h1>{thread}</h1></main>")
+        );
+    }
+
+    assert!(shared.dom("Loop").is_err());
+    assert!(shared.dom("Ping").is_err());
+}
+
+#[test]
+fn parsed_values_share_the_template() {
+    let template = "<p title=\"Hi {name}\" alt=\"\\{x}\">Hello {name}<b>a\\{b}</b></p>";
+    let mut instance = VelInstance::new(components(&[("Page", template)]));
+    let source = instance.components.templates["Page"].clone();
+    let dom = instance.dom("Page").unwrap();
+
+    let shared = |value: &TemplateStr| Arc::ptr_eq(value.source(), &source);
+    let Element::Node(p) = dom.tree.children[0].as_ref() else {
+        panic!("expected the paragraph");
+    };
+
+    let (key, title) = p.attributes.get_key_value("title").unwrap();
+    assert!(shared(key));
+    assert_eq!(key.span(), Span::new(3, 8));
+    match title.as_slice() {
+        [AttributeValues::Text(text), AttributeValues::Variable(variable)] => {
+            assert_eq!(
+                (text.value.as_str(), variable.name.as_str()),
+                ("Hi ", "name")
+            );
+            assert!(shared(&text.value) && shared(&variable.name));
+        }
+        other => panic!("unexpected title {other:?}"),
+    }
+
+    // Escaped text can't be a range of the template, so it gets copied
+    match p.attributes["alt"].as_slice() {
+        [AttributeValues::Text(text)] => assert_eq!(text.value, "{x}"),
+        other => panic!("unexpected alt {other:?}"),
+    }
+
+    match p
+        .children
+        .iter()
+        .map(AsRef::as_ref)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [Element::Text(text), Element::Variable(variable), Element::Node(b)] => {
+            assert_eq!(
+                (text.value.as_str(), variable.name.as_str()),
+                ("Hello ", "name")
+            );
+            assert!(shared(&text.value) && shared(&variable.name));
+
+            let Element::Text(escaped) = b.children[0].as_ref() else {
+                panic!("expected text in the bold");
+            };
+            assert_eq!(escaped.value, "a{b}");
+            assert!(!shared(&escaped.value));
+        }
+        other => panic!("unexpected children {other:?}"),
+    }
+}
+
+#[test]
+fn compile_all_collects_every_error() {
+    let templates = components(&[
+        ("Page", "<Card></Card><Broken></Broken><Lost></Lost>"),
+        ("Card", "<p>card</p>"),
+        ("Broken", "<div><p></div>"),
+        ("Lost", "<Missing></Missing>"),
+        ("Loop", "<p><Loop></Loop></p>"),
+    ]);
+
+    let expected = vec![
+        ParsingError {
+            component: "Broken".to_string(),
+            kind: ParsingErrorKind::MismatchedClosingTag {
+                expected: "p".to_string(),
+                found: "div".to_string(),
+            },
+            span: Span::new(8, 14),
+        },
+        ParsingError {
+            component: "Loop".to_string(),
+            kind: ParsingErrorKind::RecursiveComponent("Loop".to_string()),
+            span: Span::new(3, 9),
+        },
+        ParsingError {
+            component: "Lost".to_string(),
+            kind: ParsingErrorKind::UnknownComponent("Missing".to_string()),
+            span: Span::new(0, 9),
+        },
+    ];
+
+    for threads in [1, 0] {
+        let mut instance = VelInstance::new(templates.clone());
+
+        assert_eq!(instance.compile_all(threads), Err(expected.clone()));
+        assert!(matches!(
+            instance.components.get_mut("Card"),
+            Some(LazyDom::Parsed(_))
+        ));
+        assert!(matches!(
+            instance.components.get_mut("Page"),
+            Some(LazyDom::Unparsed(_))
+        ));
+    }
+}
+
+#[test]
+fn compile_all_keeps_components_when_a_thread_panics() {
+    struct Exploding;
+
+    impl ComponentSource for Exploding {
+        fn load(&self, _: &str) -> Option<String> {
+            panic!("the source broke");
+        }
+
+        fn names(&self) -> Vec<String> {
+            vec!["Boom".to_string()]
+        }
+    }
+
+    let mut instance = VelInstance::new(components(&[("Page", "<p>{title}</p>")]));
+    instance.set_source(Exploding);
+
+    let compiled =
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| instance.compile_all(2)));
+    assert!(compiled.is_err());
+
+    assert_eq!(
+        instance.render("Page".to_string(), components(&[("title", "Hi")]), Some),
+        Ok("<p>Hi</p>".to_string())
+    );
+}
+
+#[test]
+fn snapshot_round_trips_and_drops_stale_components() {
+    let templates = components(&[
+        ("Page", "<Card title=\"{title}\"><p>body</p></Card>"),
+        ("Card", "<h1>{title}</h1><Slot></Slot>"),
+        ("Footer", "<footer>bye</footer>"),
+    ]);
+
+    let mut instance = VelInstance::new(templates.clone());
+    instance.compile_all(1).unwrap();
+
+    let bytes = instance.snapshot().to_bytes();
+    let snapshot = Snapshot::from_bytes(&bytes).unwrap();
+    assert_eq!(snapshot.to_bytes(), bytes);
+    assert_eq!(
+        snapshot.components().collect::<Vec<_>>(),
+        ["Card", "Footer", "Page"]
+    );
+    assert!(snapshot.to_json().contains("\"name\":\"h1\""));
+    // Where components were called and slots declared is kept for debug markers
+    assert!(snapshot
+        .to_json()
+        .contains("\"location\":{\"component\":\"Page\",\"end\":22,\"start\":0}"));
+
+    assert_eq!(
+        Snapshot::from_bytes(b"nope").err(),
+        Some(SnapshotError::NotASnapshot)
+    );
+    assert_eq!(
+        Snapshot::from_bytes(&bytes[..bytes.len() - 1]).err(),
+        Some(SnapshotError::Truncated)
+    );
+
+    let mut changed = templates.clone();
+    changed.insert(
+        "Card".to_string(),
+        "<h2>{title}</h2><Slot></Slot>".to_string(),
+    );
+
+    let mut instance = VelInstance::new(changed);
+    assert_eq!(instance.load_snapshot(snapshot), ["Card"]);
+
+    assert!(matches!(
+        instance.components.get_mut("Footer"),
+        Some(LazyDom::Parsed(_))
+    ));
+    assert!(matches!(
+        instance.components.get_mut("Page"),
+        Some(LazyDom::Unparsed(_))
+    ));
+    assert_eq!(
+        instance.render("Page".to_string(), components(&[("title", "Hi")]), Some),
+        Ok("<h2>Hi</h2><p>body</p>".to_string())
+    );
+}
+
+#[test]
+fn codegen_writes_a_struct_per_component() {
+    let mut instance = VelInstance::new(components(&[
+        (
+            "blog.Post",
+            "<Card title=\"{post-title}\"><p>{type}</p><p>{Author}, {HTMLBody}</p></Card>",
+        ),
+        ("Card", "<h1 class=\"a&b\">{title}</h1><Slot></Slot>"),
+        ("Empty", "<br>"),
+    ]));
+
+    let code = codegen::generate(&mut instance, &["blog.Post", "Empty"]).unwrap();
+
+    assert!(code.contains(
+        "pub struct BlogPost<'a> {\n    pub author: &'a str,\n    pub html_body: &'a str,\n    pub post_title: &'a str,\n    pub r#type: &'a str,\n}"
+    ));
+    assert!(code.contains("output.push_str(\"<h1 class=\\\"a&amp;b\\\">\");\n        ::vel::codegen::escape_into(output, self.post_title);"));
+    assert!(code.contains("::vel::codegen::escape_into(output, self.author);"));
+    assert!(code.contains("pub struct Empty;\n\nimpl Empty {"));
+    assert!(code.contains("output.push_str(\"<br>\");"));
+
+    let mut instance = VelInstance::new(components(&[
+        ("Page", "<Card></Card>"),
+        ("Card", "<h1>{title}</h1>"),
+    ]));
+
+    assert!(matches!(
+        codegen::generate(&mut instance, &["Page"]),
+        Err(CodegenError::MissingInput { component, input }) if component == "Card" && input == "title"
+    ));
+
+    let mut instance = VelInstance::new(components(&[("Page", "{postTitle}{post-title}")]));
+
+    assert!(matches!(
+        codegen::generate(&mut instance, &["Page"]),
+        Err(CodegenError::ConflictingInputs { field, .. }) if field == "post_title"
+    ));
+}
+
+#[test]
+fn streaming_render_matches_dom_render() {
+    let templates = components(&[
+        (
+            "Page",
+            "<!DOCTYPE html><Layout title=\"{title}\">\n  <p class='intro' id=main>Hi {name}\\{!</p>\n  <Box><Box slot=\"footer\">nested {name}</Box></Box>\n  <nav slot=\"footer\"><br/><a href=\"/{name}\" b a>x</a></nav>\n</Layout>",
+        ),
+        (
+            "Layout",
+            "<head><title>{title}</title></head><main><Slot></Slot></main><footer><Slot name=\"footer\">none</Slot></footer><aside><Slot name=\"empty\">fallback {title}</Slot></aside>",
+        ),
+        ("Box", "<div class=\"box\"><Slot></Slot></div>"),
+    ]);
+
+    let inputs = components(&[("title", "T&C"), ("name", "<b>")]);
+
+    let mut instance = VelInstance::new(templates);
+    let expected = instance
+        .render("Page".to_string(), inputs.clone(), Some)
+        .unwrap();
+
+    assert_eq!(
+        instance.render_streaming("Page".to_string(), inputs),
+        Ok(expected)
+    );
+
+    for (template, kind, span) in [
+        (
+            "<div><p></div>",
+            ParsingErrorKind::MismatchedClosingTag {
+                expected: "p".to_string(),
+                found: "div".to_string(),
+            },
+            Span::new(8, 14),
+        ),
+        (
+            "<div>",
+            ParsingErrorKind::UnclosedElement("div".to_string()),
+            Span::new(0, 5),
+        ),
+        (
+            "</div>",
+            ParsingErrorKind::UnexpectedClosingTag("div".to_string()),
+            Span::new(0, 6),
+        ),
+        (
+            "<Missing></Missing>",
+            ParsingErrorKind::UnknownComponent("Missing".to_string()),
+            Span::new(0, 9),
+        ),
+        (
+            "<Box><Page></Page></Box>",
+            ParsingErrorKind::RecursiveComponent("Page".to_string()),
+            Span::new(5, 11),
+        ),
+    ] {
+        let mut instance =
+            VelInstance::new(components(&[("Page", template), ("Box", "<Slot></Slot>")]));
+
+        let error = Err(RenderingError::Parsing(ParsingError {
+            component: "Page".to_string(),
+            kind,
+            span,
+        }));
+
+        assert_eq!(
+            instance.render("Page".to_string(), HashMap::new(), Some),
+            error
+        );
+        assert_eq!(
+            instance.render_streaming("Page".to_string(), HashMap::new()),
+            error
+        );
+    }
+}
+
+#[test]
+fn parse_errors_point_into_the_template() {
+    let template = "<main>\n  <p>ünï {x}\n</main>";
+    let mut instance = VelInstance::new(components(&[("Page", template)]));
+
+    let error = instance.parse("Page".to_string()).unwrap_err();
+
+    assert_eq!(
+        error.kind,
+        ParsingErrorKind::MismatchedClosingTag {
+            expected: "p".to_string(),
+            found: "main".to_string(),
+        }
+    );
+    assert_eq!(&template[error.span.start..error.span.end], "</main>");
+    assert_eq!(error.span.line_column(template), (3, 1));
+    assert_eq!(Span::new(15, 15).line_column(template), (2, 8));
+}
+
+/// Hands out a few bytes at a time, to split tokens and chars across reads
+struct Trickle<'a>(&'a [u8], usize);
+
+impl std::io::Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let length = self.1.min(self.0.len()).min(buf.len());
+        buf[..length].copy_from_slice(&self.0[..length]);
+        self.0 = &self.0[length..];
+        Ok(length)
+    }
+}
+
+#[test]
+fn parse_reader_matches_parsing_the_whole_template() {
+    let template = "<Card title=\"{title} — ünï\">\n  <p class='a b' hidden>\\{not a variable\\} and \\\\ {name}</p>\n  <br/><em slot=\"footer\">ok</em>\n</Card>";
+    let card = "<h1>{title}</h1><Slot></Slot><footer><Slot name=\"footer\"></Slot></footer>";
+    let inputs = components(&[("title", "T"), ("name", "<n>")]);
+
+    let mut instance = VelInstance::new(components(&[("Page", template), ("Card", card)]));
+    let expected = instance
+        .render("Page".to_string(), inputs.clone(), Some)
+        .unwrap();
+
+    for chunk in [1, 2, 3, 7, 1024] {
+        let mut instance = VelInstance::new(components(&[("Card", card)]));
+        instance
+            .parse_reader("Page".to_string(), Trickle(template.as_bytes(), chunk))
+            .unwrap();
+
+        assert_eq!(
+            instance.render("Page".to_string(), inputs.clone(), Some),
+            Ok(expected.clone())
+        );
+    }
+
+    let broken = "<main>\n  <p>ünï\n</main>";
+    let mut instance = VelInstance::new(HashMap::new());
+    let Err(LoadingError::Parsing(error)) =
+        instance.parse_reader("Page".to_string(), Trickle(broken.as_bytes(), 1))
+    else {
+        panic!("a broken template was parsed");
+    };
+    assert_eq!(&broken[error.span.start..error.span.end], "</main>");
+
+    assert!(matches!(
+        instance.parse_reader("Page".to_string(), Trickle(b"<p>\xff</p>", 1)),
+        Err(LoadingError::Io(_))
+    ));
+}
+
+#[test]
+fn select_finds_elements_written_in_the_component() {
+    let mut instance = VelInstance::new(components(&[
+        (
+            "Page",
+            r#"<main id="top"><img src="a.png" alt="a"><Card title="x"><img src="b.png"><a class="link {kind}" href="https://example.com">out</a></Card><p><a class="link" href="/in">in</a></p></main>"#,
+        ),
+        (
+            "Card",
+            "<section><img src=\"card.png\"><Slot></Slot></section>",
+        ),
+    ]));
+
+    let dom = instance.dom("Page").unwrap();
+    let count = |selector: &str| dom.select(&selector.parse::<Selector>().unwrap()).len();
+
+    // The Card's own image isn't written in Page, so it's never found
+    assert_eq!(count("img"), 2);
+    assert_eq!(count("img:not([alt])"), 1);
+    assert_eq!(count("Card > img"), 1);
+    assert_eq!(count("main > img"), 1);
+    assert_eq!(count("#top a"), 2);
+    assert_eq!(count("a[href^=https]"), 1);
+    assert_eq!(count("p > a.link, section"), 1);
+    // Words touching a variable aren't known until rendering
+    assert_eq!(count("a.link"), 2);
+    assert_eq!(count("a.kind"), 0);
+
+    let links = dom.select(&Selector::parse("a").unwrap());
+    assert!(links.iter().all(|link| link.name() == "a"));
+
+    assert_eq!(Selector::parse("img >").unwrap_err(), SelectorError::Empty);
+    assert_eq!(
+        Selector::parse("img[alt").unwrap_err(),
+        SelectorError::UnexpectedEnd
+    );
+}
+
+#[test]
+fn visitors_walk_and_rewrite_components() {
+    #[derive(Default)]
+    struct Lint<'a> {
+        depth: usize,
+        deepest: usize,
+        variables: Vec<&'a str>,
+        slot_content: usize,
+    }
+
+    impl<'a> Visit<'a> for Lint<'a> {
+        fn enter(&mut self, _element: &'a Element) {
+            self.depth += 1;
+            self.deepest = self.deepest.max(self.depth);
+        }
+
+        fn exit(&mut self, _element: &'a Element) {
+            self.depth -= 1;
+        }
+
+        fn visit_variable(&mut self, variable: &'a Variable) {
+            self.variables.push(&variable.name);
+        }
+
+        fn visit_slot_content(
+            &mut self,
+            _slot: &'a [AttributeValues],
+            content: &'a [Arc<Element>],
+        ) {
+            self.slot_content += content.len();
+            visit::walk_children(self, content);
+        }
+    }
+
+    /// Swaps `<b>` for `<strong>` and drops every `<br>`
+    struct Strengthen;
+
+    impl VisitMut for Strengthen {
+        fn visit_children(&mut self, children: &mut Vec<Arc<Element>>) {
+            children.retain(
+                |child| !matches!(child.as_ref(), Element::Void(void) if void.name == "br"),
+            );
+            visit::walk_children_mut(self, children);
+        }
+
+        fn visit_node(&mut self, node: &mut Node) {
+            if node.name == "b" {
+                node.name = "strong".to_string();
+            }
+            self.visit_children(&mut node.children);
+        }
+    }
+
+    let mut instance = VelInstance::new(components(&[
+        (
+            "Page",
+            "<p><b>{name}</b><br><Card><i>{title}</i></Card></p>",
+        ),
+        ("Card", "<div><Slot></Slot><b>card</b></div>"),
+    ]));
+    let dom = instance.dom("Page").unwrap().clone();
+
+    let mut lint = Lint::default();
+    dom.visit(&mut lint);
+    assert_eq!(lint.depth, 0);
+    // Slot content is visited where it was written, so the deepest is p > Card > div > b > card
+    assert_eq!(lint.deepest, 5);
+    assert_eq!(lint.variables, ["name", "title"]);
+    assert_eq!(lint.slot_content, 1);
+
+    let mut rewritten = dom.clone();
+    rewritten.visit_mut(&mut Strengthen);
+
+    let inputs = HashMap::from([
+        ("name".to_string(), "Vel".to_string()),
+        ("title".to_string(), "Hi".to_string()),
+    ]);
+    assert_eq!(
+        rendering::render(
+            &rewritten,
+            &inputs,
+            Some,
+            &Default::default(),
+            &Default::default(),
+            Default::default()
+        )
+        .unwrap(),
+        "<p><strong>Vel</strong><div><i>Hi</i><strong>card</strong></div></p>"
+    );
+    // The original shares nothing that was changed
+    assert_eq!(
+        rendering::render(
+            &dom,
+            &inputs,
+            Some,
+            &Default::default(),
+            &Default::default(),
+            Default::default()
+        )
+        .unwrap(),
+        "<p><b>Vel</b><br><div><i>Hi</i><b>card</b></div></p>"
+    );
+}
+
+#[test]
+fn plugins_rewrite_rendering_in_order() {
+    struct Cdn;
+
+    impl Plugin for Cdn {
+        fn attributes(&self, _name: &str, attributes: &mut RenderedAttributes) {
+            if let Some(Some(src)) = attributes.get_mut("src") {
+                if src.starts_with("/assets/") {
+                    src.insert_str(0, "https://cdn.example");
+                }
+            }
+        }
+    }
+
+    struct LazyImages;
+
+    impl Plugin for LazyImages {
+        fn element(&self, element: &Element) -> Transform {
+            match element {
+                Element::Node(node) if node.name == "script" => Transform::Remove,
+                _ => Transform::Keep,
+            }
+        }
+
+        fn attributes(&self, name: &str, attributes: &mut RenderedAttributes) {
+            // Only images the CDN plugin has already moved are made lazy
+            let moved = attributes
+                .get("src")
+                .is_some_and(|src| src.as_deref().unwrap_or("").starts_with("https://"));
+
+            if name == "img" && moved {
+                attributes.insert("loading".to_string(), Some("lazy".to_string()));
+            }
+        }
+
+        fn post_process(&self, component: &str, output: &mut String) {
+            output.push_str(&format!("<!-- {component} -->"));
+        }
+    }
+
+    let mut instance = VelInstance::new(components(&[(
+        "Page",
+        "<p><img src=\"/assets/{name}.png\"><img src=\"/other.png\" hidden><script>alert()</script></p>",
+    )]));
+    let inputs = components(&[("name", "cat")]);
+
+    assert_eq!(
+        instance.render_cached("Page".to_string(), inputs.clone()).unwrap(),
+        "<p><img src=\"/assets/cat.png\"><img hidden src=\"/other.png\"><script>alert()</script></p>"
+    );
+
+    // Adding a plugin throws away what was cached without it
+    instance.add_plugin(Cdn).add_plugin(LazyImages);
+    assert_eq!(
+        instance.render_cached("Page".to_string(), inputs).unwrap(),
+        "<p><img loading=\"lazy\" src=\"https://cdn.example/assets/cat.png\"><img hidden src=\"/other.png\"></p><!-- Page -->"
+    );
+
+    // Rendering straight from templates can't run them, so it refuses rather than skipping them
+    assert_eq!(
+        instance.render_streaming("Page".to_string(), HashMap::new()),
+        Err(RenderingError::PluginsUnsupported)
+    );
+    assert_eq!(
+        instance
+            .render_with_source_map("Page".to_string(), HashMap::new())
+            .map(|(output, _)| output),
+        Err(RenderingError::PluginsUnsupported)
+    );
+}
+
+#[test]
+fn built_components_render_like_parsed_ones() {
+    let mut instance = VelInstance::new(components(&[
+        (
+            "Page",
+            "<main><Table kind=\"wide\"><b>{title}</b></Table></main>",
+        ),
+        ("Cell", "<td><Slot></Slot></td>"),
+    ]));
+    assert!(instance
+        .render("Page".to_string(), HashMap::new(), Some)
+        .is_err());
+
+    let rows = ["a", "b"].map(|row| {
+        builder::node("tr").child(
+            builder::call("Cell")
+                .child(builder::text(row))
+                .child(builder::void("br").attribute("data-row", row)),
+        )
+    });
+    let table = builder::component("Table")
+        .child(
+            builder::node("table")
+                .attribute("class", "{kind}")
+                .children(rows)
+                .child(builder::slot("default").child(builder::text("empty"))),
+        )
+        .build();
+    instance.register("Table".to_string(), table).unwrap();
+
+    // Pages that failed without it are parsed again and find it
+    assert_eq!(
+        instance
+            .render("Page".to_string(), components(&[("title", "Hi")]), Some)
+            .unwrap(),
+        "<main><table class=\"wide\"><tr><td>a<br data-row=\"a\"></td></tr><tr><td>b<br data-row=\"b\"></td></tr><b>Hi</b></table></main>"
+    );
+
+    let recursive = builder::component("Loop")
+        .child(builder::call("Loop"))
+        .build();
+    assert_eq!(
+        instance
+            .register("Loop".to_string(), recursive)
+            .unwrap_err()
+            .kind,
+        ParsingErrorKind::RecursiveComponent("Loop".to_string())
+    );
+}
+
+/// Lists everything a Dom has in it, with runs of whitespace in text counted the same as a space
+fn flatten(dom: &Dom) -> Vec<String> {
+    #[derive(Default)]
+    struct Flatten(Vec<String>);
+
+    impl<'a> Visit<'a> for Flatten {
+        fn enter(&mut self, element: &'a Element) {
+            let event = match element {
+                Element::Document(document) => {
+                    format!("<{} {:?}>", document.name, document.attributes)
+                }
+                Element::Node(node) => format!("<{} {:?}>", node.name, node.attributes),
+                Element::Slot(slot) => format!("<Slot {:?} {:?}>", slot.name, slot.attributes),
+                Element::Void(void) => format!("<{} {:?}>", void.name, void.attributes),
+                Element::Variable(variable) => format!("{{{}}}", variable.name),
+                Element::Text(text) => {
+                    let mut collapsed = String::new();
+                    for char in text.value.chars() {
+                        match char.is_whitespace() {
+                            true if collapsed.ends_with(' ') => (),
+                            true => collapsed.push(' '),
+                            false => collapsed.push(char),
+                        }
+                    }
+                    collapsed
+                }
+            };
+            self.0.push(event);
+        }
+
+        fn visit_slot_content(&mut self, slot: &'a [AttributeValues], content: &'a [Arc<Element>]) {
+            self.0.push(format!("slot {slot:?}"));
+            self.visit_children(content);
+        }
+    }
+
+    let mut flatten = Flatten::default();
+    dom.visit(&mut flatten);
+    flatten.0
+}
+
+#[test]
+fn format_reindents_without_changing_the_parse() {
+    let page = "<!DOCTYPE html>\n<main>\n<!-- the card -->\n      <Card title=\"{title}\" class='wide' open>\n<p>hi {name}\n   there</p>\n\n\n\n<b slot=footer>bye</b></Card>\n<pre>\n  kept\n    as is</pre><img src=\"a.png\" alt=\"a cat sitting on a mat, looking very pleased with itself\"/>\n    </main>\n";
+    let card = "<section><Slot></Slot><footer><Slot name=\"footer\"></Slot></footer></section>";
+
+    let options = FormatOptions {
+        indent: 2,
+        width: 60,
+    };
+    let formatted = format::format("Page", page, &options).unwrap();
+
+    assert_eq!(
+        formatted,
+        "<!DOCTYPE html>\n<main>\n  <!-- the card -->\n  <Card title=\"{title}\" class='wide' open>\n    <p>hi {name}\n      there</p>\n\n    <b slot=footer>bye</b></Card>\n  <pre>\n  kept\n    as is</pre>\n  <img\n    src=\"a.png\"\n    alt=\"a cat sitting on a mat, looking very pleased with itself\"\n  />\n</main>\n"
+    );
+    assert_eq!(
+        format::format("Page", &formatted, &options).unwrap(),
+        formatted
+    );
+
+    // The tree covers every char of the template
+    let cst = Cst::parse("Page", page).unwrap();
+    assert_eq!(cst.nodes.first().unwrap().span().start, 0);
+    assert!(cst
+        .nodes
+        .windows(2)
+        .all(|pair| pair[0].span().end == pair[1].span().start));
+    assert_eq!(cst.nodes.last().unwrap().span().end, page.len());
+
+    // Whitespace is only added or taken away next to block elements, where it isn't rendered
+    let parse = |page: &str, card: &str| {
+        let mut instance = VelInstance::new(components(&[("Page", page), ("Card", card)]));
+        let dom: Vec<String> = flatten(instance.dom("Page").unwrap())
+            .into_iter()
+            .map(|event| event.trim().to_string())
+            .filter(|event| !event.is_empty())
+            .collect();
+
+        instance.set_render_options(RenderOptions {
+            style: OutputStyle::Minified,
+            ..Default::default()
+        });
+        let inputs = components(&[("title", "Hi"), ("name", "Vel"), ("two", "Two")]);
+        (
+            dom,
+            instance.render("Page".to_string(), inputs, Some).unwrap(),
+        )
+    };
+    assert_eq!(parse(&formatted, card), parse(page, card));
+
+    // Nesting written on one line is put on lines of its own
+    let nested = "<div><ul><li>One</li><li>{two}</li></ul><Card><p>hi <b>{name}</b></p><p slot=footer>bye</p></Card></div>";
+    let formatted = format::format("Page", nested, &options).unwrap();
+    assert_eq!(
+        formatted,
+        "<div>\n  <ul>\n    <li>One</li>\n    <li>{two}</li>\n  </ul>\n  <Card>\n    <p>hi <b>{name}</b></p>\n    <p slot=footer>bye</p>\n  </Card>\n</div>"
+    );
+    assert_eq!(
+        format::format("Page", &formatted, &options).unwrap(),
+        formatted
+    );
+
+    let formatted_card = format::format("Card", card, &options).unwrap();
+    assert_eq!(
+        formatted_card,
+        "<section>\n  <Slot></Slot>\n  <footer><Slot name=\"footer\"></Slot></footer>\n</section>"
+    );
+    assert_eq!(parse(&formatted, &formatted_card), parse(nested, card));
+
+    // Comments aren't rendered
+    let mut instance = VelInstance::new(components(&[("Page", "a<!-- <b> -->b")]));
+    assert_eq!(
+        instance.render("Page".to_string(), HashMap::new(), Some),
+        Ok("ab".to_string())
+    );
+
+    assert_eq!(
+        format::format("Page", "<p></b>", &options)
+            .unwrap_err()
+            .span,
+        Span::new(3, 7)
+    );
+}
+
+#[test]
+fn inputs_read_from_json_and_toml() {
+    let expected = components(&[
+        ("title", "Home"),
+        ("count", "3"),
+        ("draft", "false"),
+        ("user.name", "Vel \"the\" cat"),
+        ("tags.0", "a"),
+        ("tags.1", "b"),
+    ]);
+
+    let json = r#"{"title": "Home", "count": 3, "draft": false,
+        "user": {"name": "Vel \"the\" cat"}, "tags": ["a", "b"]}"#;
+    assert_eq!(inputs::from_json(json).unwrap(), expected);
+
+    let toml = "# inputs\ntitle = 'Home'\ncount = 3\ndraft = false # not yet\ntags = [\"a\", \"b\"]\n\n[user]\nname = \"Vel \\\"the\\\" cat\"\n";
+    assert_eq!(inputs::from_toml(toml).unwrap(), expected);
+
+    assert_eq!(
+        inputs::from_toml("title = \"Home\"\ncount = three\n"),
+        Err(InputsError::Syntax {
+            line: 2,
+            column: 9,
+            message: "expected a value"
+        })
+    );
+
+    // Integers too big for an f64 aren't rounded
+    let big = components(&[("id", "9007199254740993"), ("ratio", "0.5")]);
+    assert_eq!(
+        inputs::from_json("{\"id\": 9007199254740993, \"ratio\": 0.5}").unwrap(),
+        big
+    );
+    assert_eq!(
+        inputs::from_toml("id = 9_007_199_254_740_993\nratio = 0.5\n").unwrap(),
+        big
+    );
+    assert_eq!(
+        json::parse("[9007199254740993,-1e400]")
+            .unwrap()
+            .to_string(),
+        "[9007199254740993,-1e400]"
+    );
+
+    assert_eq!(
+        inputs::from_json("{\"title\": null}"),
+        Err(InputsError::Unsupported("title".to_string()))
+    );
+    assert_eq!(
+        inputs::from_pair("name=a=b"),
+        Ok(("name".to_string(), "a=b".to_string()))
+    );
+}
+
+#[test]
+fn sites_build_and_rebuild_what_changed() {
+    let root = temp_components(
+        "ssg",
+        &[
+            ("components/Home.vel", "<Layout><h1>{site}</h1></Layout>"),
+            ("components/Post.vel", "<Layout><h1>{title}</h1></Layout>"),
+            ("components/About.vel", "<p>about {site}</p>"),
+            ("components/Layout.vel", "<main><Slot></Slot></main>"),
+            ("static/style.css", "p {}"),
+            (
+                "site.toml",
+                "[data]\nsite = \"Vel\"\n\n[pages.\"/\"]\ncomponent = \"Home\"\n\n[pages.\"/about\"]\ncomponent = \"About\"\n\n[pages.\"/blog/hello\"]\ncomponent = \"Post\"\ntitle = \"Hello\"\n",
+            ),
+        ],
+    );
+    let output = root.join("dist");
+
+    let mut site = Site::new(SiteConfig {
+        components: root.join("components"),
+        naming: Naming::FileStem,
+        manifest: root.join("site.toml"),
+        assets: Some(root.join("static")),
+        output: output.clone(),
+    })
+    .unwrap();
+
+    let report = site.build().unwrap();
+    assert_eq!(report.rendered, ["/", "/about", "/blog/hello"]);
+    assert_eq!(report.assets, 1);
+    assert_eq!(
+        fs::read_to_string(output.join("blog/hello/index.html")).unwrap(),
+        "<main><h1>Hello</h1></main>"
+    );
+    assert_eq!(
+        fs::read_to_string(output.join("index.html")).unwrap(),
+        "<main><h1>Vel</h1></main>"
+    );
+    assert!(output.join("style.css").exists());
+
+    fs::write(
+        root.join("components/Layout.vel"),
+        "<main class=new><Slot></Slot></main>",
+    )
+    .unwrap();
+
+    let report = site.build().unwrap();
+    assert_eq!(report.rendered, ["/", "/blog/hello"]);
+    assert_eq!((report.unchanged, report.assets), (1, 0));
+    assert_eq!(
+        fs::read_to_string(output.join("index.html")).unwrap(),
+        "<main class=\"new\"><h1>Vel</h1></main>"
+    );
+
+    assert_eq!(
+        ssg::output_path("/404.html"),
+        Some(PathBuf::from("404.html"))
+    );
+    assert_eq!(ssg::output_path("/../secrets"), None);
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn served_pages_reload_and_show_errors() {
+    let root = temp_components(
+        "serve",
+        &[
+            ("components/Home.vel", "<p>home</p>"),
+            ("components/Broken.vel", "<p>broken</div>"),
+            (
+                "site.json",
+                r#"{"pages": {"/": {"component": "Home"}, "/broken": {"component": "Broken"}}}"#,
+            ),
+        ],
+    );
+
+    let site = Site::new(SiteConfig {
+        components: root.join("components"),
+        naming: Naming::FileStem,
+        manifest: root.join("site.json"),
+        assets: None,
+        output: root.join("dist"),
+    })
+    .unwrap();
+
+    let server = Server::bind(site, "127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    std::thread::spawn(move || server.run());
+
+    let mut stream = std::net::TcpStream::connect(address).unwrap();
+    stream
+        .write_all(b"GET /?from=test HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("<p>home</p><script>new EventSource(\"/__vel/reload\")"));
+    assert!(response.contains("Broken.vel:1:10: in `Broken`: expected `&lt;/p&gt;`"));
+
+    assert_eq!(
+        serve::inject("<body><p>hi</p></body>", &[]),
+        "<body><p>hi</p><script>new EventSource(\"/__vel/reload\").onmessage = () => location.reload();</script></body>"
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn language_server_answers_requests() {
+    let root = temp_components(
+        "lsp",
+        &[(
+            "Card.vel",
+            "<div class=card>{title}<Slot></Slot><Slot name=footer></Slot></div>",
+        )],
+    );
+    let page = format!("file://{}/Page.vel", root.display());
+
+    let requests = [
+        format!(
+            r#"{{"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {{"rootUri": "file://{}"}}}}"#,
+            root.display()
+        ),
+        format!(
+            r#"{{"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {{"textDocument": {{"uri": "{page}", "text": "<Card title={{name}}></Card>\n<Nope></Nope>"}}}}}}"#
+        ),
+        format!(
+            r#"{{"jsonrpc": "2.0", "id": 2, "method": "textDocument/definition", "params": {{"textDocument": {{"uri": "{page}"}}, "position": {{"line": 0, "character": 3}}}}}}"#
+        ),
+        format!(
+            r#"{{"jsonrpc": "2.0", "id": 3, "method": "textDocument/hover", "params": {{"textDocument": {{"uri": "{page}"}}, "position": {{"line": 0, "character": 1}}}}}}"#
+        ),
+        format!(
+            r#"{{"jsonrpc": "2.0", "method": "textDocument/didChange", "params": {{"textDocument": {{"uri": "{page}"}}, "contentChanges": [{{"text": "<Card><p slot="}}]}}}}"#
+        ),
+        format!(
+            r#"{{"jsonrpc": "2.0", "id": 4, "method": "textDocument/completion", "params": {{"textDocument": {{"uri": "{page}"}}, "position": {{"line": 0, "character": 14}}}}}}"#
+        ),
+        r#"{"jsonrpc": "2.0", "method": "exit"}"#.to_string(),
+    ];
+    let input: String = requests
+        .iter()
+        .map(|request| format!("Content-Length: {}\r\n\r\n{request}", request.len()))
+        .collect();
+
+    let mut output = vec![];
+    lsp::run(input.as_bytes(), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    assert!(output.contains(r#""definitionProvider":true"#));
+    assert!(output.contains(r#""message":"in `Page`: unknown component `Nope`","range":{"end":{"character":6,"line":1},"start":{"character":0,"line":1}}"#));
+    assert!(output.contains(&format!(r#""id":2,"jsonrpc":"2.0","result":{{"range":{{"end":{{"character":0,"line":0}},"start":{{"character":0,"line":0}}}},"uri":"file://{}/Card.vel"}}"#, root.display())));
+    assert!(output.contains(r#""value":"**Card** takes `title`""#));
+    assert!(
+        output.contains(r#""result":[{"kind":10,"label":"default"},{"kind":10,"label":"footer"}]"#)
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn minified_output() {
+    let mut instance = VelInstance::new(components(&[
+        (
+            "Page",
+            "<div class=\"card main\">\n  <ul>\n    <li>One</li>\n    <li>{two}</li>\n  </ul>\n  <Note>Some   <b>bold</b>   text</Note>\n  <input disabled=\"disabled\" type=\"text\">\n  <pre>  keep\n   this </pre>\n</div>\n",
+        ),
+        ("Note", "<p><Slot></Slot></p>"),
+    ]));
+    instance.set_render_options(RenderOptions {
+        style: OutputStyle::Minified,
+        ..Default::default()
+    });
+
+    let expected = "<div class=\"card main\"><ul><li>One<li>Two</ul><p>Some <b>bold</b> text</p><input disabled type=text><pre>  keep\n   this </pre></div>";
+    let inputs = components(&[("two", "Two")]);
+
+    assert_eq!(
+        instance.render("Page".to_string(), inputs.clone(), Some),
+        Ok(expected.to_string())
+    );
+    assert_eq!(
+        instance.render_streaming("Page".to_string(), inputs),
+        Ok(expected.to_string())
+    );
+
+    // Closes are left out before inline elements too, but not with whitespace in between
+    instance.reload(components(&[(
+        "Page",
+        "<select><option>a</option><option>b</option></select><select><option>a</option> <option>b</option></select><table><thead><tr><th>a</th></tr></thead><tbody><tr><td>b</td></tr></tbody></table><ruby>x<rp>(</rp><rt>y</rt><rp>)</rp></ruby>",
+    )]));
+    let expected = "<select><option>a<option>b</select><select><option>a</option> <option>b</select><table><thead><tr><th>a<tbody><tr><td>b</table><ruby>x<rp>(<rt>y<rp>)</ruby>";
+
+    assert_eq!(
+        instance.render("Page".to_string(), HashMap::new(), Some),
+        Ok(expected.to_string())
+    );
+    assert_eq!(
+        instance.render_streaming("Page".to_string(), HashMap::new()),
+        Ok(expected.to_string())
+    );
+}
+
+#[test]
+fn pretty_output() {
+    let mut instance = VelInstance::new(components(&[
+        (
+            "Page",
+            "<div class=\"card\"><h1>{title}</h1>  <ul><li>One</li><li><a href=\"/\">Two</a></li></ul><Note>Some <b>bold</b> text</Note><pre>  keep\n   this </pre></div>",
+        ),
+        ("Note", "<p><Slot></Slot></p>after"),
+    ]));
+    instance.set_render_options(RenderOptions {
+        style: OutputStyle::Pretty,
+        ..Default::default()
+    });
+
+    let expected = "<div class=\"card\">\n  <h1>\n    Hi\n  </h1>\n  <ul>\n    <li>\n      One\n    </li>\n    <li>\n      <a href=\"/\">Two</a>\n    </li>\n  </ul>\n  <p>\n    Some <b>bold</b> text\n  </p>\n  after\n  <pre>  keep\n   this </pre>\n</div>";
+    let inputs = components(&[("title", "Hi")]);
+
+    assert_eq!(
+        instance.render("Page".to_string(), inputs.clone(), Some),
+        Ok(expected.to_string())
+    );
+    assert_eq!(
+        instance.render_streaming("Page".to_string(), inputs),
+        Ok(expected.to_string())
+    );
+}
+
+#[test]
+fn debug_markers() {
+    let mut instance = VelInstance::new(components(&[
+        (
+            "Page",
+            "<!DOCTYPE html><main>\n<Card><b slot=\"{which}\">Hi</b></Card><script><Card></Card></script></main>",
+        ),
+        (
+            "Card",
+            "<div>\n<Slot name=\"{which}\">Empty</Slot></div>\n\n<Slot name=\"x--y\"></Slot>",
+        ),
+    ]));
+    instance.set_render_options(RenderOptions {
+        debug: true,
+        ..Default::default()
+    });
+
+    // Markers say where in its own template each component starts and each slot is declared, and
+    // where the component was called from. Nothing can come before the doctype.
+    let expected = "<!DOCTYPE html><!-- <Page> from Page:1 --><main>\n<!-- <Card> from Card:1, called at Page:2 --><div>\n<!-- <Slot name=\"{which}\"> of <Card> from Card:2 --><b>Hi</b><!-- </Slot> of <Card> --></div>\n\n<!-- <Slot name=\"x- -y\"> of <Card> from Card:4 --><!-- </Slot> of <Card> --><!-- </Card> --><script><div>\nEmpty</div>\n\n</script></main><!-- </Page> -->";
+    let inputs = components(&[("which", "main")]);
+
+    assert_eq!(
+        instance.render("Page".to_string(), inputs.clone(), Some),
+        Ok(expected.to_string())
+    );
+    assert_eq!(
+        instance.render_streaming("Page".to_string(), inputs),
+        Ok(expected.to_string())
+    );
+}
+
+#[test]
+fn source_maps() {
+    let mut instance = VelInstance::new(components(&[
+        (
+            "Page",
+            "<main class=\"page\">\n  <p>Hi {name}</p>\n  <Card title=\"{name}\"></Card>\n</main>",
+        ),
+        ("Card", "<b>{title}</b>"),
+    ]));
+    instance.set_render_options(RenderOptions {
+        style: OutputStyle::Minified,
+        ..Default::default()
+    });
+
+    let inputs = components(&[("name", "Ada")]);
+    let (output, map) = instance
+        .render_with_source_map("Page".to_string(), inputs.clone())
+        .unwrap();
+    assert_eq!(
+        instance.render_streaming("Page".to_string(), inputs),
+        Ok(output.clone())
+    );
+    assert_eq!(output, "<main class=page><p>Hi Ada</p><b>Ada</b></main>");
+
+    let at = |needle: &str| {
+        let mapping = map.at(output.find(needle).unwrap()).unwrap();
+        (
+            mapping.component.as_str(),
+            mapping.line,
+            mapping.column,
+            mapping.kind.clone(),
+            &output[mapping.output.clone()],
+        )
+    };
+
+    assert_eq!(
+        at("class"),
+        (
+            "Page",
+            1,
+            7,
+            MappingKind::Attribute("class".to_string()),
+            "class=page"
+        )
+    );
+    assert_eq!(
+        at("<p>"),
+        (
+            "Page",
+            2,
+            3,
+            MappingKind::Node("p".to_string()),
+            "<p>Hi Ada"
+        )
+    );
+    assert_eq!(at("Hi"), ("Page", 2, 6, MappingKind::Text, "Hi"));
+    assert_eq!(
+        at("Ada"),
+        (
+            "Page",
+            2,
+            9,
+            MappingKind::Variable("name".to_string()),
+            "Ada"
+        )
+    );
+    assert_eq!(
+        at("Ada</b>"),
+        (
+            "Card",
+            1,
+            4,
+            MappingKind::Variable("title".to_string()),
+            "Ada"
+        )
+    );
+    assert_eq!(map.mappings()[0].output, 0..output.len());
+    assert!(map.to_json().starts_with(
+        "{\"mappings\":[{\"column\":1,\"component\":\"Page\",\"kind\":\"node\",\"line\":1,\"name\":\"main\""
+    ));
+}
@@ -0,0 +1,134 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Finding components and slots that are never used.
+
+use crate::{
+    ast::elements::{AttributeValues, Element},
+    graph::ComponentGraph,
+    ComponentsCache, ParsingError,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+
+/// A slot that a component gets given content for but never declares with `<Slot>`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct UndeclaredSlot {
+    /// The component giving the slot content
+    pub caller: String,
+    /// The component being given the slot content
+    pub component: String,
+    pub slot: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UnusedReport {
+    /// Components that aren't used by any of the roots, even indirectly
+    pub unreachable: Vec<String>,
+    /// Slots, as `(component, slot)`, that are declared but that none of the component's callers
+    /// fill. Only components that are used somewhere are checked, as the slots of components
+    /// that are rendered directly can never be filled.
+    pub unfilled_slots: Vec<(String, String)>,
+    /// Slots that callers fill that the component never declares
+    pub undeclared_slots: Vec<UndeclaredSlot>,
+}
+
+/// Gets the name of a slot, as long as it's written out rather than coming from a variable.
+/// Slots named with variables could be anything, so they're left out of the report.
+fn static_slot_name(name: &[AttributeValues]) -> Option<String> {
+    name.iter()
+        .map(|value| match value {
+            AttributeValues::Text(text) => Some(text.value.as_str()),
+            AttributeValues::Variable(_) => None,
+        })
+        .collect()
+}
+
+/// Content that's nothing but whitespace, like the indentation between a component's tags,
+/// doesn't count as filling its default slot.
+fn is_blank(content: &[Arc<Element>]) -> bool {
+    content.iter().all(|element| match element.as_ref() {
+        Element::Text(text) => text.value.trim().is_empty(),
+        _ => false,
+    })
+}
+
+/// Parses every component and checks which of them, and which of their slots, are never used.
+pub(crate) fn report(
+    components: &mut ComponentsCache,
+    graph: &ComponentGraph,
+    roots: &[&str],
+) -> Result<UnusedReport, ParsingError> {
+    let reachable = graph.reachable_from(roots.iter().copied());
+
+    let mut declared: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    // Every filled slot as (caller, component, slot)
+    let mut filled: BTreeSet<(String, String, String)> = BTreeSet::new();
+
+    for caller in graph.components() {
+        let dom = components.parsed(caller)?;
+
+        declared.insert(
+            caller.to_string(),
+            dom.slots()
+                .into_iter()
+                .filter_map(static_slot_name)
+                .collect(),
+        );
+
+        for (component, slot_content) in dom.calls() {
+            // Calls without any content still count as using the component
+            filled.insert((caller.to_string(), component.to_string(), String::new()));
+
+            for (name, content) in slot_content {
+                if let (false, Some(slot)) = (is_blank(content), static_slot_name(name)) {
+                    filled.insert((caller.to_string(), component.to_string(), slot));
+                }
+            }
+        }
+    }
+
+    let filled_by_anyone: BTreeSet<(&str, &str)> = filled
+        .iter()
+        .map(|(_, component, slot)| (component.as_str(), slot.as_str()))
+        .collect();
+
+    let unfilled_slots = declared
+        .iter()
+        .filter(|(component, _)| graph.dependants(component).next().is_some())
+        .flat_map(|(component, slots)| {
+            slots
+                .iter()
+                .filter(|slot| !filled_by_anyone.contains(&(component.as_str(), slot.as_str())))
+                .map(|slot| (component.clone(), slot.clone()))
+        })
+        .collect();
+
+    let undeclared_slots = filled
+        .into_iter()
+        .filter(|(_, component, slot)| {
+            !slot.is_empty()
+                && !declared
+                    .get(component)
+                    .is_some_and(|slots| slots.contains(slot))
+        })
+        .map(|(caller, component, slot)| UndeclaredSlot {
+            caller,
+            component,
+            slot,
+        })
+        .collect();
+
+    Ok(UnusedReport {
+        unreachable: graph
+            .components()
+            .filter(|component| !reachable.contains(component))
+            .map(str::to_string)
+            .collect(),
+        unfilled_slots,
+        undeclared_slots,
+    })
+}
@@ -0,0 +1,160 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Walking parsed components, for linting, transforming and picking apart templates.
+//!
+//! Every hook recurses by default by calling the matching `walk_*` function, so overriding one and
+//! calling its `walk_*` from inside of it still visits everything below it. [`Visit::enter`] and
+//! [`Visit::exit`] are called around every element of every kind, before and after its own hook.
+//!
+//! A component call is visited as an [`Element::Document`], whose slot content is what the caller
+//! wrote and whose children are the used component's inlined body.
+
+use crate::ast::elements::{AttributeValues, Document, Element, Node, Slot, Text, Variable, Void};
+#[cfg(doc)]
+use crate::ast::Dom;
+use std::sync::Arc;
+
+pub trait Visit<'a> {
+    fn enter(&mut self, _element: &'a Element) {}
+
+    fn exit(&mut self, _element: &'a Element) {}
+
+    fn visit_element(&mut self, element: &'a Element) {
+        walk_element(self, element);
+    }
+
+    fn visit_children(&mut self, children: &'a [Arc<Element>]) {
+        walk_children(self, children);
+    }
+
+    fn visit_document(&mut self, document: &'a Document) {
+        walk_document(self, document);
+    }
+
+    /// Visits what a call site gave to one of the used component's slots
+    fn visit_slot_content(&mut self, _slot: &'a [AttributeValues], content: &'a [Arc<Element>]) {
+        self.visit_children(content);
+    }
+
+    fn visit_node(&mut self, node: &'a Node) {
+        self.visit_children(&node.children);
+    }
+
+    fn visit_slot(&mut self, slot: &'a Slot) {
+        self.visit_children(&slot.children);
+    }
+
+    fn visit_text(&mut self, _text: &'a Text) {}
+
+    fn visit_variable(&mut self, _variable: &'a Variable) {}
+
+    fn visit_void(&mut self, _void: &'a Void) {}
+}
+
+pub fn walk_element<'a, V: Visit<'a> + ?Sized>(visitor: &mut V, element: &'a Element) {
+    visitor.enter(element);
+
+    match element {
+        Element::Document(document) => visitor.visit_document(document),
+        Element::Node(node) => visitor.visit_node(node),
+        Element::Slot(slot) => visitor.visit_slot(slot),
+        Element::Text(text) => visitor.visit_text(text),
+        Element::Variable(variable) => visitor.visit_variable(variable),
+        Element::Void(void) => visitor.visit_void(void),
+    }
+
+    visitor.exit(element);
+}
+
+pub fn walk_children<'a, V: Visit<'a> + ?Sized>(visitor: &mut V, children: &'a [Arc<Element>]) {
+    for child in children {
+        visitor.visit_element(child);
+    }
+}
+
+/// Visits the slot content in order of the slot it's for, then the component's body
+pub fn walk_document<'a, V: Visit<'a> + ?Sized>(visitor: &mut V, document: &'a Document) {
+    let mut slot_content: Vec<_> = document.slot_content.iter().collect();
+    slot_content.sort_unstable_by_key(|(slot, _)| *slot);
+
+    for (slot, content) in slot_content {
+        visitor.visit_slot_content(slot, content);
+    }
+
+    visitor.visit_children(&document.children);
+}
+
+/// Like [`Visit`], but able to change the elements it visits. Elements shared with another [`Dom`]
+/// are cloned before they're handed over, so only the Dom being visited sees the changes.
+pub trait VisitMut {
+    fn enter(&mut self, _element: &mut Element) {}
+
+    fn exit(&mut self, _element: &mut Element) {}
+
+    fn visit_element(&mut self, element: &mut Element) {
+        walk_element_mut(self, element);
+    }
+
+    /// Gets the whole list so elements can be added or taken out of it, not just changed
+    fn visit_children(&mut self, children: &mut Vec<Arc<Element>>) {
+        walk_children_mut(self, children);
+    }
+
+    fn visit_document(&mut self, document: &mut Document) {
+        walk_document_mut(self, document);
+    }
+
+    fn visit_slot_content(&mut self, _slot: &[AttributeValues], content: &mut Vec<Arc<Element>>) {
+        self.visit_children(content);
+    }
+
+    fn visit_node(&mut self, node: &mut Node) {
+        self.visit_children(&mut node.children);
+    }
+
+    fn visit_slot(&mut self, slot: &mut Slot) {
+        self.visit_children(&mut slot.children);
+    }
+
+    fn visit_text(&mut self, _text: &mut Text) {}
+
+    fn visit_variable(&mut self, _variable: &mut Variable) {}
+
+    fn visit_void(&mut self, _void: &mut Void) {}
+}
+
+pub fn walk_element_mut<V: VisitMut + ?Sized>(visitor: &mut V, element: &mut Element) {
+    visitor.enter(element);
+
+    match element {
+        Element::Document(document) => visitor.visit_document(document),
+        Element::Node(node) => visitor.visit_node(node),
+        Element::Slot(slot) => visitor.visit_slot(slot),
+        Element::Text(text) => visitor.visit_text(text),
+        Element::Variable(variable) => visitor.visit_variable(variable),
+        Element::Void(void) => visitor.visit_void(void),
+    }
+
+    visitor.exit(element);
+}
+
+pub fn walk_children_mut<V: VisitMut + ?Sized>(visitor: &mut V, children: &mut [Arc<Element>]) {
+    for child in children {
+        visitor.visit_element(Arc::make_mut(child));
+    }
+}
+
+pub fn walk_document_mut<V: VisitMut + ?Sized>(visitor: &mut V, document: &mut Document) {
+    let mut slots: Vec<Vec<AttributeValues>> = document.slot_content.keys().cloned().collect();
+    slots.sort_unstable();
+
+    for slot in slots {
+        if let Some(content) = document.slot_content.get_mut(&slot) {
+            visitor.visit_slot_content(&slot, content);
+        }
+    }
+
+    visitor.visit_children(&mut document.children);
+}
@@ -0,0 +1,117 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Noticing when template files change, for reloading them during development.
+//!
+//! This polls file modification times rather than relying on OS specific file watching services,
+//! so it works the same everywhere at the cost of a short delay.
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+/// When a file was last modified alongside its length, as quick successive writes can share a
+/// modification time on filesystems with coarse timestamps
+pub(crate) type Stamp = (SystemTime, u64);
+
+/// Watches every file under a directory by comparing their modification times between polls.
+#[derive(Debug, Clone)]
+pub struct PollWatcher {
+    root: PathBuf,
+    modified: HashMap<PathBuf, Stamp>,
+}
+
+impl PollWatcher {
+    /// Starts watching `root`. Files that already exist aren't reported as changed.
+    pub fn new(root: impl Into<PathBuf>) -> io::Result<Self> {
+        let root = root.into();
+        let modified = snapshot(&root)?;
+
+        Ok(Self { root, modified })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Gets every file that was added, modified or removed since the last poll.
+    pub fn poll(&mut self) -> io::Result<Vec<PathBuf>> {
+        let modified = snapshot(&self.root)?;
+
+        let mut changed: Vec<PathBuf> = modified
+            .iter()
+            .filter(|(path, time)| self.modified.get(*path) != Some(time))
+            .map(|(path, _)| path.clone())
+            .chain(
+                self.modified
+                    .keys()
+                    .filter(|path| !modified.contains_key(*path))
+                    .cloned(),
+            )
+            .collect();
+        changed.sort_unstable();
+
+        self.modified = modified;
+
+        Ok(changed)
+    }
+
+    /// Blocks, polling every `interval`, until something changes.
+    pub fn wait(&mut self, interval: Duration) -> io::Result<Vec<PathBuf>> {
+        loop {
+            let changed = self.poll()?;
+
+            if !changed.is_empty() {
+                return Ok(changed);
+            }
+
+            thread::sleep(interval);
+        }
+    }
+}
+
+pub(crate) fn snapshot(root: &Path) -> io::Result<HashMap<PathBuf, Stamp>> {
+    let mut modified = HashMap::new();
+    let mut directories = vec![root.to_path_buf()];
+
+    while let Some(directory) = directories.pop() {
+        // Unlike a directory inside it, the root going away is worth hearing about
+        let entries = match directory == root {
+            true => fs::read_dir(&directory)?,
+            false => match missing(fs::read_dir(&directory))? {
+                Some(entries) => entries,
+                None => continue,
+            },
+        };
+
+        for entry in entries {
+            let entry = entry?;
+            let Some(metadata) = missing(entry.metadata())? else {
+                continue;
+            };
+
+            if metadata.is_dir() {
+                directories.push(entry.path());
+            } else if let Some(time) = missing(metadata.modified())? {
+                modified.insert(entry.path(), (time, metadata.len()));
+            }
+        }
+    }
+
+    Ok(modified)
+}
+
+/// Turns something having been deleted into [`None`], since anything under the root can be
+/// deleted while it's being looked at
+fn missing<T>(result: io::Result<T>) -> io::Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error),
+    }
+}
@@ -0,0 +1,176 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use std::{fs, path::PathBuf, process::Command};
+
+fn temp_components(test_name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("vel-cli-{test_name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+
+    for (path, contents) in files {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    root
+}
+
+#[test]
+fn render_marks_components_with_debug() {
+    let root = temp_components(
+        "render-debug",
+        &[
+            ("Page.vel", "<main>\n<Card>Hi</Card></main>"),
+            ("ui/Card.vel", "<b><Slot></Slot></b>"),
+        ],
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vel"))
+        .args(["render", "Page", "--debug", "--dir"])
+        .arg(&root)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "<!-- <Page> from Page.vel:1 --><main>\n<!-- <Card> from ui/Card.vel:1, called at Page.vel:2 --><b><!-- <Slot name=\"default\"> of <Card> from ui/Card.vel:1 -->Hi<!-- </Slot> of <Card> --></b><!-- </Card> --></main><!-- </Page> -->\n"
+    );
+
+    // Components named by their path still point at their files
+    fs::write(
+        root.join("Page.vel"),
+        "<main>\n<ui.Card>Hi</ui.Card></main>",
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_vel"))
+        .args(["render", "Page", "--debug", "--relative-names", "--dir"])
+        .arg(&root)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "<!-- <Page> from Page.vel:1 --><main>\n<!-- <ui.Card> from ui/Card.vel:1, called at Page.vel:2 --><b><!-- <Slot name=\"default\"> of <ui.Card> from ui/Card.vel:1 -->Hi<!-- </Slot> of <ui.Card> --></b><!-- </ui.Card> --></main><!-- </Page> -->\n"
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn render_takes_inputs_from_files_and_set() {
+    let root = temp_components(
+        "render-inputs",
+        &[
+            (
+                "Page.vel",
+                "<main><Card title=\"{title}\">{body}</Card></main>",
+            ),
+            ("ui/Card.vel", "<h1>{title}</h1><Slot></Slot>"),
+            (
+                "inputs.json",
+                "{\"title\": \"From a file\", \"body\": \"Body\"}",
+            ),
+        ],
+    );
+
+    // `--set` wins over the files, whichever order they're given in
+    let output = Command::new(env!("CARGO_BIN_EXE_vel"))
+        .args(["render", "Page", "--set", "title=Set", "--inputs"])
+        .arg(root.join("inputs.json"))
+        .arg("--dir")
+        .arg(&root)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "<main><h1>Set</h1>Body</main>\n"
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn check_reports_where_problems_are() {
+    let root = temp_components(
+        "check",
+        &[
+            ("Broken.vel", "<main>\n  <p>\n</div>"),
+            ("ui/Other.vel", "<Missing></Missing>"),
+            ("Fine.vel", "<b></b>"),
+        ],
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vel"))
+        .arg("check")
+        .arg(&root)
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let mut lines: Vec<&str> = stderr.lines().collect();
+    lines.sort_unstable();
+    assert_eq!(
+        lines,
+        [
+            format!(
+                "{}:3:1: in `Broken`: expected `</p>` but found `</div>`",
+                root.join("Broken.vel").display()
+            ),
+            format!(
+                "{}:1:1: in `Other`: unknown component `Missing`",
+                root.join("ui/Other.vel").display()
+            ),
+            "found 2 problems".to_string(),
+        ]
+    );
+
+    fs::remove_file(root.join("Broken.vel")).unwrap();
+    fs::remove_file(root.join("ui/Other.vel")).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_vel"))
+        .arg("check")
+        .arg(&root)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "checked 1 components\n"
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn deps_prints_a_tree() {
+    let root = temp_components(
+        "deps",
+        &[
+            ("Page.vel", "<main><Card></Card><Footer></Footer></main>"),
+            ("ui/Card.vel", "<div><Icon></Icon></div>"),
+            ("ui/Footer.vel", "<footer><Icon></Icon></footer>"),
+            ("ui/Icon.vel", "<i></i>"),
+        ],
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vel"))
+        .args(["deps", "Page", "--dir"])
+        .arg(&root)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "Page\n├── Card\n│   └── Icon\n└── Footer\n    └── Icon\n"
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,66 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Compiles what codegen writes, by including a copy of it that's checked against what it
+//! writes now, and makes sure it renders what the instance does.
+
+use std::collections::HashMap;
+use vel::{codegen, VelInstance};
+
+mod generated {
+    include!("generated/templates.rs");
+}
+
+fn instance() -> VelInstance {
+    VelInstance::new(HashMap::from([
+        (
+            "blog.Post".to_string(),
+            "<Card title=\"{post-title}\"><p>{type}</p><p>{Author}, {HTMLBody}</p></Card>"
+                .to_string(),
+        ),
+        (
+            "Card".to_string(),
+            "<h1 class=\"a&b\">{title}</h1><Slot></Slot>".to_string(),
+        ),
+        ("Empty".to_string(), "<br>".to_string()),
+    ]))
+}
+
+#[test]
+fn generated_code_is_up_to_date() {
+    let included = include_str!("generated/templates.rs");
+    // Everything after the license header is exactly what codegen writes
+    let (_, code) = included.split_once("\n\n").unwrap();
+
+    assert_eq!(
+        codegen::generate(&mut instance(), &["blog.Post", "Empty"]).unwrap(),
+        code
+    );
+}
+
+#[test]
+fn generated_code_renders_like_the_instance() {
+    let post = generated::BlogPost {
+        author: "Sam & co",
+        html_body: "<p>not markup</p>",
+        post_title: "\"Quoted\"",
+        r#type: "note",
+    };
+    let inputs = HashMap::from([
+        ("Author".to_string(), post.author.to_string()),
+        ("HTMLBody".to_string(), post.html_body.to_string()),
+        ("post-title".to_string(), post.post_title.to_string()),
+        ("type".to_string(), post.r#type.to_string()),
+    ]);
+
+    let mut instance = instance();
+    assert_eq!(
+        instance.render("blog.Post".to_string(), inputs, Some),
+        Ok(post.render())
+    );
+    assert_eq!(
+        instance.render("Empty".to_string(), HashMap::new(), Some),
+        Ok(generated::Empty.render())
+    );
+}
@@ -0,0 +1,48 @@
+// SPDX-FileCopyrightText: 2025 Name <lasagna@garfunkle.space>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+// Generated by vel, don't edit this by hand!
+
+/// Generated from the `blog.Post` component.
+pub struct BlogPost<'a> {
+    pub author: &'a str,
+    pub html_body: &'a str,
+    pub post_title: &'a str,
+    pub r#type: &'a str,
+}
+
+impl BlogPost<'_> {
+    pub fn render_into(&self, output: &mut String) {
+        output.push_str("<h1 class=\"a&amp;b\">");
+        ::vel::codegen::escape_into(output, self.post_title);
+        output.push_str("</h1><p>");
+        ::vel::codegen::escape_into(output, self.r#type);
+        output.push_str("</p><p>");
+        ::vel::codegen::escape_into(output, self.author);
+        output.push_str(", ");
+        ::vel::codegen::escape_into(output, self.html_body);
+        output.push_str("</p>");
+    }
+
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+        self.render_into(&mut output);
+        output
+    }
+}
+
+/// Generated from the `Empty` component.
+pub struct Empty;
+
+impl Empty {
+    pub fn render_into(&self, output: &mut String) {
+        output.push_str("<br>");
+    }
+
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+        self.render_into(&mut output);
+        output
+    }
+}